@@ -1,4 +1,4 @@
-use systree::SysTree;
+use systree::{SysAttrId, SysBranchNode, SysObj, SysTree};
 
 use crate::inode::{SysFsInode};
 
@@ -7,24 +7,490 @@ pub struct SysFs {
     sb: SuperBlock,
     systree: &'static SysTree,
     root: Arc<dyn Inode>,
+    // The node in the `SysTree` that is the root of this file system.
+    //
+    // The path of the node is not kept
+    // since the node or its ancestors may be renamed or moved.
+    root_sysnode: Arc<dyn SysBranchNode>,
+    stats: SysFsStats,
+    options: SysFsOptions,
+    write_limiter: WriteRateLimiter,
 }
 
 // These parameters are same as those of Linux.
 const MAGIC_NUMBER: u64 = 0x62656572;
-const BLOCK_SIZE: usize = 1024;
+pub(crate) const BLOCK_SIZE: usize = 1024;
 const NAME_MAX: usize = 255;
 
 impl SysFs {
-    pub(crate) fn new() -> Self {
-        let sb = SuperBlock::new(MAGIC_NUMBER, BLOCK_SIZE, NAME_MAX);
+    pub(crate) fn new() -> Arc<Self> {
         let systree = systree::singleton();
-        let root = SysFsInode::new_root();
-        let new_self = Self {
-            sb, 
-            systree, 
-            root,
-        };
-        Arc::new(new_self)
+        Self::do_new(systree, systree.root().clone())
+    }
+
+    /// Creates a file system whose root is the branching node
+    /// of the given path in the `SysTree`, i.e., one mounted at a subtree.
+    ///
+    /// Unlike the singleton of sysfs (see `crate::singleton`),
+    /// the returned file system is not shared,
+    /// so its statistics and options are separate from those of the singleton.
+    pub fn new_subtree(mount_root: &str) -> Result<Arc<Self>> {
+        Self::new_subtree_in(systree::singleton(), mount_root)
+    }
+
+    /// Creates a file system mounted at a subtree of the given `SysTree`
+    /// (see `new_subtree`).
+    pub(crate) fn new_subtree_in(systree: &'static SysTree, mount_root: &str) -> Result<Arc<Self>> {
+        let mut root_sysnode: Arc<dyn SysBranchNode> = systree.root().clone();
+        for name in mount_root.split('/').filter(|name| !name.is_empty()) {
+            root_sysnode = root_sysnode
+                .child(name)
+                .and_then(|child| child.arc_as_branch())
+                .ok_or_else(|| Error::new(Errno::ENOENT))?;
+        }
+        Ok(Self::do_new(systree, root_sysnode))
+    }
+
+    fn do_new(systree: &'static SysTree, root_sysnode: Arc<dyn SysBranchNode>) -> Arc<Self> {
+        Arc::new_cyclic(|this| {
+            let sb = SuperBlock::new(MAGIC_NUMBER, BLOCK_SIZE, NAME_MAX);
+            // The inodes refer back to the file system to which they belong
+            let root = SysFsInode::new_root(root_sysnode.clone(), this.clone());
+            Self {
+                sb,
+                systree,
+                root,
+                root_sysnode,
+                stats: SysFsStats::new(),
+                options: SysFsOptions::new(),
+                write_limiter: WriteRateLimiter::new(),
+            }
+        })
+    }
+
+    /// Returns the `SysTree` that this file system exposes.
+    pub fn systree(&self) -> &'static SysTree {
+        self.systree
+    }
+
+    /// Returns the current path of the node in the `SysTree`
+    /// that is the root of this file system.
+    pub fn mount_root(&self) -> String {
+        self.root_sysnode.path()
+    }
+
+    /// Returns the path of a node within this file system,
+    /// which differs from the path in the `SysTree`
+    /// if the file system is mounted at a subtree.
+    ///
+    /// `None` is returned if the node is not under the root of this file system.
+    pub fn path_of(&self, node: &dyn SysObj) -> Option<String> {
+        let path = node.path();
+        if !path.starts_with('/') {
+            // The node is not attached to the `SysTree`
+            return None;
+        }
+        self.path_in_mount(&path)
+    }
+
+    /// Rewrites the target path of a symlink node for this file system.
+    ///
+    /// If this file system is mounted at a subtree,
+    /// an absolute target path in the `SysTree` (e.g., `/devices/foo`)
+    /// would point outside the mount.
+    /// So it is rewritten into a path relative to the dir of the symlink
+    /// (e.g., `../foo`), which stays valid within the mounted view.
+    ///
+    /// `None` is returned if no rewriting is needed or possible,
+    /// i.e., if this file system is mounted at the root of the `SysTree`,
+    /// if the target path is already relative,
+    /// or if the symlink or the target is not under the mount root.
+    pub fn rewrite_link_target(&self, symlink: &dyn SysObj, target_path: &str) -> Option<String> {
+        if SysTree::is_root_path(&self.mount_root()) || !target_path.starts_with('/') {
+            return None;
+        }
+
+        let link_path = self.path_of(symlink)?;
+        let target_path = self.path_in_mount(target_path)?;
+        let mut link_dir_names: Vec<&str> = link_path.split('/').filter(|name| !name.is_empty()).collect();
+        // The target is relative to the dir containing the symlink
+        link_dir_names.pop();
+        let target_names: Vec<&str> = target_path.split('/').filter(|name| !name.is_empty()).collect();
+
+        let num_common_names = link_dir_names
+            .iter()
+            .zip(target_names.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let relative_names: Vec<&str> = core::iter::repeat("..")
+            .take(link_dir_names.len() - num_common_names)
+            .chain(target_names[num_common_names..].iter().copied())
+            .collect();
+        if relative_names.is_empty() {
+            return Some(".".to_string());
+        }
+        Some(relative_names.join("/"))
+    }
+
+    /// Converts an absolute path in the `SysTree` into the path within this file system.
+    fn path_in_mount(&self, path: &str) -> Option<String> {
+        let mount_root = self.mount_root();
+        if SysTree::is_root_path(&mount_root) {
+            return Some(path.to_string());
+        }
+
+        let relative_path = path.strip_prefix(mount_root.as_str())?;
+        if relative_path.is_empty() {
+            Some(SysTree::ROOT_PATH.to_string())
+        } else if relative_path.starts_with('/') {
+            Some(relative_path.to_string())
+        } else {
+            // The path is under a sibling with a common prefix (e.g., `/devices2`)
+            None
+        }
+    }
+
+    /// Returns the statistics of the read and write operations on attributes.
+    pub fn stats(&self) -> &SysFsStats {
+        &self.stats
+    }
+
+    /// Returns the options of the file system.
+    pub fn options(&self) -> &SysFsOptions {
+        &self.options
+    }
+
+    /// Sets the clock that measures the intervals between writes
+    /// to rate-limited attributes (see `SysNode::attr_write_interval`).
+    ///
+    /// The clock returns the current time as a duration since an arbitrary epoch.
+    /// By default, the monotonic clock of the system is used.
+    /// A different clock can be injected, e.g., to control time in tests.
+    pub fn set_clock(&self, clock: fn() -> Duration) {
+        *self.write_limiter.clock.write() = clock;
+    }
+
+    /// Accepts a write to a rate-limited attribute
+    /// once the minimum interval since the last accepted write has elapsed,
+    /// returning the slot of the write.
+    ///
+    /// If the interval has not elapsed,
+    /// the write either fails with `EBUSY` or sleeps until the interval elapses,
+    /// depending on `SysFsOptions::block_rate_limited_writes`.
+    /// The total sleep of a blocked write is bounded by the interval
+    /// that remains when the write is first rejected,
+    /// as measured by the monotonic clock of the system.
+    /// If the write is still rejected by then
+    /// (e.g., another write has taken the slot, or the injected clock does not advance),
+    /// it gives up with `EBUSY`.
+    ///
+    /// The slot is taken by the write tentatively.
+    /// If the write fails, the slot should be released with `release_write`,
+    /// so that a failed write does not delay the next one.
+    pub(crate) fn accept_write(&self, node_id: u64, attr_id: SysAttrId, interval: Duration) -> Result<WriteSlot> {
+        let key = (node_id, attr_id);
+        let mut deadline = None;
+        loop {
+            let remaining = match self.write_limiter.try_accept(key, interval) {
+                Ok(slot) => return Ok(slot),
+                Err(remaining) => remaining,
+            };
+            if !self.options.block_rate_limited_writes() {
+                return Err(Error::new(Errno::EBUSY));
+            }
+
+            let now = monotonic_now();
+            let deadline = *deadline.get_or_insert(now + remaining);
+            if now >= deadline {
+                return Err(Error::new(Errno::EBUSY));
+            }
+            sleep_for(remaining.min(deadline - now));
+        }
+    }
+
+    /// Releases the slot of a write that has failed (see `accept_write`).
+    pub(crate) fn release_write(&self, slot: WriteSlot) {
+        self.write_limiter.release(slot);
+    }
+}
+
+/// A write accepted by the rate limiter (see `SysFs::accept_write`).
+pub(crate) struct WriteSlot {
+    key: (u64, SysAttrId),
+    accepted_at: Duration,
+    // The last accepted write before this one
+    last_write: Option<LastWrite>,
+}
+
+/// A limiter of the rates of the writes to attributes.
+struct WriteRateLimiter {
+    // The last accepted write to an attribute,
+    // indexed by the node ID and the attribute ID.
+    //
+    // The writes whose intervals have elapsed no longer limit the next writes,
+    // so they are pruned once the map grows large.
+    // This also prunes the writes to the attributes of removed nodes.
+    last_writes: Mutex<BTreeMap<(u64, SysAttrId), LastWrite>>,
+    clock: RwLock<fn() -> Duration>,
+}
+
+#[derive(Clone, Copy)]
+struct LastWrite {
+    accepted_at: Duration,
+    interval: Duration,
+}
+
+impl LastWrite {
+    fn is_expired(&self, now: Duration) -> bool {
+        now.saturating_sub(self.accepted_at) >= self.interval
+    }
+}
+
+impl WriteRateLimiter {
+    // The number of the entries beyond which the expired writes are pruned
+    const PRUNE_THRESHOLD: usize = 256;
+
+    fn new() -> Self {
+        Self {
+            last_writes: Mutex::new(BTreeMap::new()),
+            clock: RwLock::new(monotonic_now),
+        }
+    }
+
+    /// Tries to accept a write, returning the remaining time
+    /// until the write can be accepted on failure.
+    fn try_accept(&self, key: (u64, SysAttrId), interval: Duration) -> core::result::Result<WriteSlot, Duration> {
+        let now = (self.clock.read())();
+        let mut last_writes = self.last_writes.lock();
+        let last_write = last_writes.get(&key).copied();
+        if let Some(last_write) = last_write {
+            let elapsed = now.saturating_sub(last_write.accepted_at);
+            if elapsed < interval {
+                return Err(interval - elapsed);
+            }
+        }
+
+        if last_writes.len() >= Self::PRUNE_THRESHOLD {
+            last_writes.retain(|_, last_write| !last_write.is_expired(now));
+        }
+        last_writes.insert(
+            key,
+            LastWrite {
+                accepted_at: now,
+                interval,
+            },
+        );
+        Ok(WriteSlot {
+            key,
+            accepted_at: now,
+            last_write,
+        })
+    }
+
+    /// Releases the slot of a failed write,
+    /// restoring the last accepted write before it.
+    fn release(&self, slot: WriteSlot) {
+        let mut last_writes = self.last_writes.lock();
+        // The slot may have been taken by a later write since then
+        let is_current = last_writes
+            .get(&slot.key)
+            .is_some_and(|last_write| last_write.accepted_at == slot.accepted_at);
+        if !is_current {
+            return;
+        }
+        match slot.last_write {
+            Some(last_write) => {
+                last_writes.insert(slot.key, last_write);
+            }
+            None => {
+                last_writes.remove(&slot.key);
+            }
+        }
+    }
+}
+
+fn monotonic_now() -> Duration {
+    crate::time::clocks::MonotonicCoarseClock::get().read_time()
+}
+
+/// Puts the current task to sleep for the given duration.
+fn sleep_for(duration: Duration) {
+    // The waker is never used, so the waiter wakes up only on the timeout
+    let (waiter, _waker) = Waiter::new_pair();
+    let _ = waiter.pause_timeout(&duration);
+}
+
+/// The options of sysfs, which can be changed at runtime.
+///
+/// The options are read with relaxed atomics,
+/// so that checking them on the hot path is cheap.
+#[derive(Debug)]
+pub struct SysFsOptions {
+    // The epoch of the options that expose synthetic entries (see `epoch`)
+    epoch: AtomicU64,
+    subsystem_links: AtomicBool,
+    debug_attrs: AtomicBool,
+    immutable_modes: AtomicBool,
+    chmod_events: AtomicBool,
+    negative_lookup_cache: AtomicBool,
+    block_rate_limited_writes: AtomicBool,
+    dir_permissions: AtomicBool,
+}
+
+impl SysFsOptions {
+    pub(crate) const fn new() -> Self {
+        Self {
+            epoch: AtomicU64::new(0),
+            subsystem_links: AtomicBool::new(false),
+            debug_attrs: AtomicBool::new(false),
+            immutable_modes: AtomicBool::new(false),
+            chmod_events: AtomicBool::new(false),
+            negative_lookup_cache: AtomicBool::new(false),
+            block_rate_limited_writes: AtomicBool::new(false),
+            dir_permissions: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns whether every branching or leaf node in a registered subsystem
+    /// exposes a synthetic `subsystem` symlink,
+    /// which points to the subsystem (see `SysTree::subsystem_of`).
+    ///
+    /// This option is off by default.
+    pub fn subsystem_links(&self) -> bool {
+        self.subsystem_links.load(Ordering::Relaxed)
+    }
+
+    pub fn set_subsystem_links(&self, enabled: bool) {
+        self.subsystem_links.store(enabled, Ordering::Relaxed);
+        self.advance_epoch();
+    }
+
+    /// Returns whether every branching or leaf node exposes
+    /// the synthetic, read-only `__sysfs_path` and `__sysfs_name` attributes,
+    /// whose values are the path and the name of the node.
+    ///
+    /// This option is intended for debugging and is off by default.
+    pub fn debug_attrs(&self) -> bool {
+        self.debug_attrs.load(Ordering::Relaxed)
+    }
+
+    pub fn set_debug_attrs(&self, enabled: bool) {
+        self.debug_attrs.store(enabled, Ordering::Relaxed);
+        self.advance_epoch();
+    }
+
+    /// Returns whether the modes of inodes are immutable,
+    /// in which case changing the mode of an inode (e.g., `chmod`) fails with `EPERM`.
+    ///
+    /// This option is off by default.
+    pub fn immutable_modes(&self) -> bool {
+        self.immutable_modes.load(Ordering::Relaxed)
+    }
+
+    pub fn set_immutable_modes(&self, enabled: bool) {
+        self.immutable_modes.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether changing the mode of an attribute inode (e.g., `chmod`)
+    /// publishes a `Change` event for the node of the attribute,
+    /// with the name of the attribute and the new mode (in octal) in the details.
+    ///
+    /// This option is off by default.
+    pub fn chmod_events(&self) -> bool {
+        self.chmod_events.load(Ordering::Relaxed)
+    }
+
+    pub fn set_chmod_events(&self, enabled: bool) {
+        self.chmod_events.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether dir inodes remember the names that were recently
+    /// found missing, so that repeated lookups of the names fail fast with `ENOENT`.
+    ///
+    /// This option is off by default.
+    pub fn negative_lookup_cache(&self) -> bool {
+        self.negative_lookup_cache.load(Ordering::Relaxed)
+    }
+
+    pub fn set_negative_lookup_cache(&self, enabled: bool) {
+        self.negative_lookup_cache.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether a write to a rate-limited attribute that arrives too soon
+    /// waits until the minimum interval elapses,
+    /// instead of failing with `EBUSY` (see `SysNode::attr_write_interval`).
+    ///
+    /// This option is off by default.
+    pub fn block_rate_limited_writes(&self) -> bool {
+        self.block_rate_limited_writes.load(Ordering::Relaxed)
+    }
+
+    pub fn set_block_rate_limited_writes(&self, enabled: bool) {
+        self.block_rate_limited_writes.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether the modes of dir inodes are enforced,
+    /// in which case looking up a name in a dir without the execute permission,
+    /// or listing a dir without the read permission, fails with `EACCES`.
+    ///
+    /// This option is off by default.
+    pub fn dir_permissions(&self) -> bool {
+        self.dir_permissions.load(Ordering::Relaxed)
+    }
+
+    pub fn set_dir_permissions(&self, enabled: bool) {
+        self.dir_permissions.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns the epoch of the options that expose synthetic entries
+    /// (i.e., `subsystem_links` and `debug_attrs`),
+    /// which advances whenever one of these options is set.
+    ///
+    /// Caches of the entries of dirs can compare the epoch at the time of caching
+    /// with the current one to detect staleness.
+    pub(crate) fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    fn advance_epoch(&self) {
+        self.epoch.fetch_add(1, Ordering::Release);
+    }
+}
+
+/// The statistics of the read and write operations on the attribute files of sysfs.
+///
+/// The counters are updated with relaxed atomics on the hot path,
+/// so the values are not synchronized with each other.
+#[derive(Debug)]
+pub struct SysFsStats {
+    reads: AtomicU64,
+    writes: AtomicU64,
+}
+
+impl SysFsStats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            reads: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the total number of successful reads.
+    pub fn reads(&self) -> u64 {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Returns the total number of successful writes.
+    pub fn writes(&self) -> u64 {
+        self.writes.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn inc_reads(&self) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn inc_writes(&self) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
     }
 }
 
@@ -45,3 +511,176 @@ impl FileSystem for SysFS {
         FsFlags::empty()
     }
 }
+
+#[cfg(ktest)]
+pub(crate) mod test {
+    use ostd::prelude::ktest;
+    use systree::{
+        test_util::{FixtureLeaf, TreeBuilder},
+        utils::no_parent,
+        SysNodeType,
+    };
+
+    use super::*;
+
+    /// Creates a file system mounted at a subtree of the `SysTree` built by `builder`.
+    ///
+    /// The `SysTree` is leaked since a file system refers to it statically.
+    pub(crate) fn new_fs(builder: TreeBuilder, mount_root: &str) -> Arc<SysFs> {
+        let systree: &'static SysTree = Box::leak(Box::new(builder.build().unwrap()));
+        SysFs::new_subtree_in(systree, mount_root).unwrap()
+    }
+
+    /// Looks up the inode of a path relative to the root inode of a file system.
+    pub(crate) fn lookup(fs: &SysFs, path: &str) -> Result<Arc<dyn Inode>> {
+        let mut inode = fs.root_inode();
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            inode = inode.lookup(name)?;
+        }
+        Ok(inode)
+    }
+
+    /// Reads the whole value of an attribute inode as a string.
+    pub(crate) fn read_to_string(inode: &dyn Inode) -> Result<String> {
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+        let read_len = inode.read_at(0, &mut writer)?;
+        buf.truncate(read_len);
+        Ok(String::from_utf8(buf).unwrap())
+    }
+
+    /// Writes a string to an attribute inode.
+    pub(crate) fn write_str(inode: &dyn Inode, value: &str) -> Result<usize> {
+        let mut reader = VmReader::from(value.as_bytes()).to_fallible();
+        inode.write_at(0, &mut reader)
+    }
+
+    fn new_subtree_fs() -> Arc<SysFs> {
+        let builder = TreeBuilder::new()
+            .add("/devices/pci0/eth0", SysNodeType::Leaf, &["mtu"])
+            .add_symlink("/devices/virtual/net/eth0", "/devices/pci0/eth0")
+            .add_symlink("/devices/virtual/net/lo", "../../pci0/eth0")
+            .add("/class/net", SysNodeType::Branch, &[]);
+        new_fs(builder, "/devices")
+    }
+
+    #[ktest]
+    fn subtree_mount_rewrites_absolute_link_targets() {
+        let fs = new_subtree_fs();
+        assert_eq!(fs.mount_root(), "/devices");
+
+        let link = lookup(&fs, "/virtual/net/eth0").unwrap();
+        assert_eq!(link.read_link().unwrap(), "../../pci0/eth0");
+        // The relative targets are kept as is
+        let link = lookup(&fs, "/virtual/net/lo").unwrap();
+        assert_eq!(link.read_link().unwrap(), "../../pci0/eth0");
+        // The nodes outside the mount are not visible
+        assert_eq!(lookup(&fs, "/class").unwrap_err().error(), Errno::ENOENT);
+    }
+
+    #[ktest]
+    fn path_of_nodes_within_and_outside_mount() {
+        let fs = new_subtree_fs();
+        let systree = fs.systree();
+
+        let eth0 = systree.lookup("/devices/pci0/eth0").unwrap();
+        assert_eq!(fs.path_of(eth0.as_ref()).as_deref(), Some("/pci0/eth0"));
+        let mount_root = systree.lookup("/devices").unwrap();
+        assert_eq!(fs.path_of(mount_root.as_ref()).as_deref(), Some("/"));
+
+        let class_net = systree.lookup("/class/net").unwrap();
+        assert_eq!(fs.path_of(class_net.as_ref()), None);
+        // Neither is a detached node
+        let detached = FixtureLeaf::new("eth1".into(), &[], no_parent());
+        assert_eq!(fs.path_of(detached.as_ref()), None);
+
+        // A file system mounted at the root keeps the paths intact
+        let root_fs = SysFs::new_subtree_in(systree, "/").unwrap();
+        assert_eq!(root_fs.path_of(class_net.as_ref()).as_deref(), Some("/class/net"));
+    }
+
+    #[ktest]
+    fn subtree_mount_inodes_belong_to_the_mount() {
+        let fs = new_subtree_fs();
+        let inode = lookup(&fs, "/pci0/eth0").unwrap();
+        let fs_of_inode = Arc::as_ptr(&inode.fs()) as *const ();
+        assert_eq!(fs_of_inode, Arc::as_ptr(&fs) as *const ());
+
+        // The options of the mount apply to its inodes
+        assert!(inode.lookup("__sysfs_path").is_err());
+        fs.options().set_debug_attrs(true);
+        let path_attr = inode.lookup("__sysfs_path").unwrap();
+        assert_eq!(read_to_string(path_attr.as_ref()).unwrap(), "/devices/pci0/eth0\n");
+    }
+
+    #[ktest]
+    fn subtree_mount_of_missing_path_fails() {
+        let systree: &'static SysTree = Box::leak(Box::new(TreeBuilder::new().build().unwrap()));
+        let error = SysFs::new_subtree_in(systree, "/devices").err().unwrap();
+        assert_eq!(error.error(), Errno::ENOENT);
+    }
+
+    #[ktest]
+    fn mount_root_follows_renames() {
+        let fs = new_subtree_fs();
+        fs.systree().move_node("/devices", "/devices2").unwrap();
+        assert_eq!(fs.mount_root(), "/devices2");
+        // The paths within the mount are relative to the new mount root
+        let eth0 = fs.systree().lookup("/devices2/pci0/eth0").unwrap();
+        assert_eq!(fs.path_of(eth0.as_ref()).as_deref(), Some("/pci0/eth0"));
+    }
+
+    // The current time of the injected clock, in milliseconds
+    static FAKE_NOW_MS: AtomicU64 = AtomicU64::new(0);
+
+    fn fake_now() -> Duration {
+        Duration::from_millis(FAKE_NOW_MS.load(Ordering::Relaxed))
+    }
+
+    #[ktest]
+    fn rate_limited_writes_are_spaced() {
+        let fs = new_fs(TreeBuilder::new(), "/");
+        fs.set_clock(fake_now);
+        let interval = Duration::from_millis(100);
+        let (node_id, attr_id) = (7, 0);
+
+        FAKE_NOW_MS.store(1000, Ordering::Relaxed);
+        fs.accept_write(node_id, attr_id, interval).unwrap();
+        // A rapid second write is rejected
+        FAKE_NOW_MS.store(1050, Ordering::Relaxed);
+        let error = fs.accept_write(node_id, attr_id, interval).err().unwrap();
+        assert_eq!(error.error(), Errno::EBUSY);
+        // The writes to the other attributes are not limited
+        fs.accept_write(node_id, attr_id + 1, interval).unwrap();
+
+        // An adequately-spaced write succeeds
+        FAKE_NOW_MS.store(1100, Ordering::Relaxed);
+        let slot = fs.accept_write(node_id, attr_id, interval).unwrap();
+        // Once failed, the write no longer delays the next one
+        fs.release_write(slot);
+        fs.accept_write(node_id, attr_id, interval).unwrap();
+
+        // A blocked write gives up eventually if the clock is frozen
+        fs.options().set_block_rate_limited_writes(true);
+        let error = fs.accept_write(node_id, attr_id, interval).err().unwrap();
+        assert_eq!(error.error(), Errno::EBUSY);
+    }
+
+    #[ktest]
+    fn blocked_rate_limited_writes_sleep_for_intervals() {
+        const NUM_WRITES: u32 = 3;
+
+        let fs = new_fs(TreeBuilder::new(), "/");
+        fs.options().set_block_rate_limited_writes(true);
+        let interval = Duration::from_millis(20);
+        let (node_id, attr_id) = (7, 0);
+
+        let start = monotonic_now();
+        fs.accept_write(node_id, attr_id, interval).unwrap();
+        // With the real clock, every blocked write is accepted once its interval elapses
+        for _ in 0..NUM_WRITES {
+            fs.accept_write(node_id, attr_id, interval).unwrap();
+        }
+        assert!(monotonic_now() - start >= interval * NUM_WRITES);
+    }
+}
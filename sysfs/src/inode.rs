@@ -1,5 +1,7 @@
 use std::sync::Arc;
 
+use crate::fs::{SysFs, SysFsOptions, BLOCK_SIZE};
+
 pub struct SysFsInode {
     // The corresponding node in the SysTree.
     inner_node: InnerNode,
@@ -10,11 +12,42 @@ pub struct SysFsInode {
     // can be determined upon the creation of an inode,
     // and are thus kept intact inside the immutable `metadata` field.
     //
-    // Currently, the only mutable metadata is `mode`,
-    // which allows user space to `chmod` an inode on sysfs.
+    // Currently, the mutable metadata are `mode`,
+    // which allows user space to `chmod` an inode on sysfs,
+    // and the `nlink` of a dir inode,
+    // which changes as the child nodes are added or removed.
     metadata: Metadata,
     mode: RwLock<InodeMode>,
+    // The number of the child dirs of a dir inode of a branching node
+    // (excluding the dirs of attribute groups),
+    // along with the generation of the branching node
+    // at which the number was computed (see `SysBranchNode::generation`).
+    num_child_dirs: Mutex<(u64, usize)>,
+    // The flags of the attribute from which `mode` was last derived.
+    //
+    // This field is only meaningful for attribute inodes.
+    // Since the flags of an attribute may be changed at runtime,
+    // `mode` is derived again once the flags are found changed.
+    attr_flags: RwLock<SysAttrFlags>,
+
+    // The names that were recently found missing in this dir.
+    //
+    // This field is only meaningful for dir inodes
+    // (see `SysFsOptions::negative_lookup_cache`).
+    negative_cache: Mutex<NegativeLookupCache>,
+    // The inodes of the child nodes that were recently looked up in this dir.
+    //
+    // This field is only meaningful for dir inodes of branching nodes.
+    child_cache: Mutex<ChildInodeCache>,
+
+    // The watch on the changes of the attribute, created upon the first `poll`.
+    //
+    // This field is only meaningful for attribute inodes.
+    attr_watch: Once<Arc<AttrWatch>>,
 
+    // The file system to which this inode belongs,
+    // which may be mounted at a subtree of the `SysTree`.
+    fs: Weak<SysFs>,
     parent: Weak<SysFsInode>,
     this: Weak<SysFsInode>,
 }
@@ -24,12 +57,30 @@ impl Inode for SysfsInode {
         self.metadata.type_
     }
 
-    fn resize(&self, _new_size: usize) -> Result<()> {
-        Err(..)
+    fn resize(&self, new_size: usize) -> Result<()> {
+        // Opening a writable attribute with `O_TRUNC` resizes it to zero,
+        // which is accepted as a no-op since sysfs never truncates values:
+        // a write to a textual attribute replaces the whole value,
+        // a streamed value is replaced as a whole once committed,
+        // and a write to a binary attribute only overwrites the bytes at its offset.
+        let is_attr = matches!(self.inner_node, InnerNode::Attr(..));
+        if is_attr && new_size == 0 {
+            return Ok(());
+        }
+        Err(Error::new(Errno::EPERM))
     }
 
     fn metadata(&self) -> Metadata {
-        self.metadata
+        let mut metadata = self.metadata;
+        if metadata.type_ == InodeType::Dir {
+            // The children may have changed since the inode was created
+            metadata.nlink = self.current_dir_nlink();
+        }
+        if let InnerNode::Attr(attr, sysnode) = &self.inner_node {
+            // So may the size of the value
+            Self::set_metadata_size(&mut metadata, Self::attr_file_size(attr, sysnode.as_ref()));
+        }
+        metadata
     }
 
     fn ino(&self) -> u64 {
@@ -37,6 +88,7 @@ impl Inode for SysfsInode {
     }
 
     fn mode(&self) -> Result<InodeMode> {
+        self.sync_attr_mode();
         Ok(*self.mode.read())
     }
 
@@ -69,12 +121,32 @@ impl Inode for SysfsInode {
     }
 
     fn fs(&self) -> Arc<dyn FileSystem> {
-        crate::singleton().clone()
+        self.sysfs()
     }
 
     fn set_mode(&self, mode: InodeMode) -> Result<()> {
+        if self.sysfs().options().immutable_modes() {
+            return Err(Error::new(Errno::EPERM));
+        }
+
         // TODO: check permissions
         self.mode.write().mode = mode;
+
+        if let InnerNode::Attr(attr, sysnode) = &self.inner_node {
+            if self.sysfs().options().chmod_events() {
+                let details = vec![
+                    SysEventKv {
+                        key: "ATTR".into(),
+                        value: attr.name().clone(),
+                    },
+                    SysEventKv {
+                        key: "MODE".into(),
+                        value: format!("{:o}", mode.bits()).into(),
+                    },
+                ];
+                self.sysfs().systree().publish_event(sysnode.as_ref(), SysEventAction::Change, details);
+            }
+        }
         Ok(())
     }
 
@@ -102,31 +174,82 @@ impl Inode for SysfsInode {
         self.read_direct_at(offset, buf)
     }
 
-    fn read_direct_at(&self, _offset: usize, buf: &mut VmWriter) -> Result<usize> {
-        // TODO: it is unclear whether we should simply igore the offset
-        // or report errors if it is non-zero.
-
+    fn read_direct_at(&self, offset: usize, buf: &mut VmWriter) -> Result<usize> {
+        if let InnerNode::SyntheticAttr(synthetic_attr, sysnode) = &self.inner_node {
+            return synthetic_attr.read(sysnode.as_ref(), self.sysfs().systree(), buf);
+        }
         let InnerNode::Attr(attr, leaf) = &self.inner_node else {
             return Err(Error::new(Errno::EINVAL));
         };
 
-        // TODO: check read permission
+        // The permission is checked against the flags of the attribute,
+        // from which the mode of the inode is derived (see `flags_to_inode_mode`).
+        if !self.attr_flags.read().contains(SysAttrFlags::CAN_READ) {
+            return Err(Error::new(Errno::EACCES));
+        }
 
-        leaf.read_attr(attr.name(), buf)
+        // The change is marked as seen before reading the attribute,
+        // so a concurrent change during the read is not lost.
+        if let Some(attr_watch) = self.attr_watch.get() {
+            attr_watch.mark_seen();
+        }
+
+        // A binary attribute (e.g., a firmware blob) is read at the given offset,
+        // whereas the offset is ignored for a textual attribute.
+        // TODO: it is unclear whether we should simply igore the offset
+        // or report errors if it is non-zero for textual attributes.
+        let is_binary = self.attr_flags.read().contains(SysAttrFlags::IS_BINARY);
+        let offset = if is_binary { offset } else { 0 };
+
+        // The error of the node, if any, is mapped to an errno
+        // by `From<systree::Error> for Error`.
+        let read_len = leaf.read_attr(attr.name(), offset, buf)?;
+        self.sysfs().stats().inc_reads();
+        Ok(read_len)
     }
 
     fn write_at(&self, offset: usize, buf: &mut VmReader) -> Result<usize> {
         self.write_direct_at(offset, buf)
     }
 
-    fn write_direct_at(&self, _offset: usize, buf: &mut VmReader) -> Result<usize> {
+    fn write_direct_at(&self, offset: usize, buf: &mut VmReader) -> Result<usize> {
+        if let InnerNode::SyntheticAttr(synthetic_attr, sysnode) = &self.inner_node {
+            return synthetic_attr.write(sysnode.as_ref(), self.sysfs().systree(), buf);
+        }
         let InnerNode::Attr(attr, leaf) = &self.inner_node else {
             return Err(Error::new(Errno::EINVAL));
         };
 
-        // TODO: check write permission
+        // The permission is checked against the flags of the attribute,
+        // from which the mode of the inode is derived (see `flags_to_inode_mode`).
+        if !self.attr_flags.read().contains(SysAttrFlags::CAN_WRITE) {
+            return Err(Error::new(Errno::EACCES));
+        }
 
-        leaf.write_attr(attr.name(), buf)
+        let write_slot = match leaf.attr_write_interval(attr.name()) {
+            Some(interval) => Some(self.sysfs().accept_write(leaf.id().as_u64(), attr.id(), interval)?),
+            None => None,
+        };
+
+        // The chunks of a streamed attribute are written at their own offsets,
+        // so are the writes to a binary attribute (e.g., a register dump),
+        // whereas every write to other attributes replaces the whole value.
+        let attr_flags = *self.attr_flags.read();
+        let is_offset_honored = attr_flags.intersects(SysAttrFlags::IS_STREAMED | SysAttrFlags::IS_BINARY);
+        let offset = if is_offset_honored { offset } else { 0 };
+
+        let write_len = match leaf.write_attr(attr.name(), offset, buf) {
+            Ok(write_len) => write_len,
+            Err(err) => {
+                // A failed write does not count against the rate limit
+                if let Some(write_slot) = write_slot {
+                    self.sysfs().release_write(write_slot);
+                }
+                return Err(err.into());
+            }
+        };
+        self.sysfs().stats().inc_writes();
+        Ok(write_len)
     }
 
     fn create(&self, name: &str, type_: InodeType, mode: InodeMode) -> Result<Arc<dyn Inode>> {
@@ -145,6 +268,18 @@ impl Inode for SysfsInode {
         None
     }
 
+    fn open(&self, access_mode: AccessMode, _status_flags: StatusFlags) -> Option<Result<Arc<dyn FileIo>>> {
+        // A value of a streamed attribute is committed once the writer closes the file,
+        // so the writer is given a file of its own to track the end of the stream.
+        let InnerNode::Attr(..) = &self.inner_node else {
+            return None;
+        };
+        if !access_mode.is_writable() || !self.attr_flags.read().contains(SysAttrFlags::IS_STREAMED) {
+            return None;
+        }
+        Some(Ok(Arc::new(StreamedAttrFile::new(self.this()))))
+    }
+
     fn readdir_at(&self, mut offset: usize, visitor: &mut dyn DirentVisitor) -> Result<usize> {
         if self.type_() != InodeType::DIR {
             return Err(Error::new(Errno::ENOTDIR));
@@ -176,34 +311,17 @@ impl Inode for SysfsInode {
         // [the man page of getdents](https://man7.org/linux/man-pages/man2/getdents.2.html).
         //
         // Our implementation of sysfs interprets the `offset`
-        // as an _inode number_.
+        // as an _inode number_
+        // (except that `.` and `..` are positioned after all the other entries).
         // By inode numbers, directory entries will have a _stable_ order
         // across different calls to `readdir_at`.
+        self.check_dir_permission(InodeMode::is_readable)?;
         let min_ino = offset as Ino;
-        let mut dentry_iter = self.new_dentry_iter(min_ino);
-
-        // Dump the dentries returned by the iterator into the output visitor
-        let mut last_dentry_ino = min_ino;
-        let mut count = 0;
-        while let Some(dentry) = dentry_iter.next() {
-            let res = visitor.visit(dentry.name(), dentry.ino(), dentry.type_(), dentry.ino());
-            if res.is_err() {
-                if count == 0 {
-                    return Err(Error::new(Errno::EINVAL));
-                } else {
-                    break;
-                }
-            }
-
-            count += 1;
-            last_dentry_ino = dentry.ino();
-        }
-
-        if count == 0 {
+        let Some(last_dentry_offset) = self.dump_dentries(min_ino, visitor)? else {
             return Ok(0);
-        }
+        };
 
-        let next_call_min_ino = last_dentry_ino + 1;
+        let next_call_min_ino = last_dentry_offset + 1;
         Ok(next_call_min_ino - min_ino)
     }
 
@@ -222,7 +340,8 @@ impl Inode for SysfsInode {
             return Err(Error::new(Errno::ENOTDIR));
         }
 
-        // TODO: check permissions
+        // Looking up a name in a dir requires the permission to search the dir
+        self.check_dir_permission(InodeMode::is_executable)?;
 
         if name == "." {
             return self.this();
@@ -230,15 +349,48 @@ impl Inode for SysfsInode {
             return self.parent.upgrade().unwrap_or_else(|| self.this());
         };
 
-        match &self.inner_node {
+        // The state is loaded before the lookup
+        // so that a concurrent change during the lookup invalidates the cached result
+        let dir_state = if self.sysfs().options().negative_lookup_cache() {
+            self.dir_state()
+        } else {
+            None
+        };
+        if let Some(dir_state) = &dir_state {
+            if self.negative_cache.lock().contains(name, dir_state) {
+                return Err(Error::new(Errno::ENOENT));
+            }
+        }
+
+        let res = match &self.inner_node {
             InnerNode::Branch(branch_sysnode) => {
                 self.lookup_node_or_attr(name, branch_sysnode)
             }
             InnerNode::Leaf(leaf_sysnode) => {
                 self.lookup_attr(name, leaf_sysnode)
             }
+            InnerNode::AttrGroup(group_index, sysnode) => {
+                self.lookup_grouped_attr(name, *group_index, sysnode)
+            }
             _ => unreachable!()
+        };
+
+        // The synthetic entries are looked up only if there are no real ones
+        // with the same names.
+        let res = match res {
+            Err(err) if err.error() == Errno::ENOENT => self.lookup_synthetic(name).ok_or(err),
+            res => res,
+        };
+
+        // The `subsystem` symlink also depends on the registered subsystems,
+        // whose changes are not tracked by the state of the dir
+        let is_cacheable_name = name != SUBSYSTEM_LINK_NAME;
+        if let Some(dir_state) = dir_state {
+            if is_cacheable_name && res.as_ref().is_err_and(|err| err.error() == Errno::ENOENT) {
+                self.negative_cache.lock().insert(name, dir_state);
+            }
         }
+        res
     }
 
     fn rename(&self, _old_name: &str, _target: &Arc<dyn Inode>, _new_name: &str) -> Result<()> {
@@ -246,11 +398,14 @@ impl Inode for SysfsInode {
     }
 
     fn read_link(&self) -> Result<String> {
-        let InnerNode::Symlink(symlink_node) = &self.inner_node else {
-            return Err(Error::new(Errno::EINVAL));
-        };
-
-        Ok(symlink_node.target_path().to_string())
+        let target_path = self.link_target()?;
+        // The absolute targets are rewritten if sysfs is mounted at a subtree
+        if let InnerNode::Symlink(sysnode) = &self.inner_node {
+            if let Some(rewritten_path) = self.sysfs().rewrite_link_target(sysnode.as_ref(), target_path) {
+                return Ok(rewritten_path);
+            }
+        }
+        Ok(target_path.to_string())
     }
 
     fn write_link(&self, target: &str) -> Result<()> {
@@ -273,8 +428,45 @@ impl Inode for SysfsInode {
         Err(Error::new(Errno::EOPNOTSUPP))
     }
 
-    fn poll(&self, mask: IoEvents, _poller: Option<&mut PollHandle>) -> IoEvents {
-        let events = IoEvents::IN | IoEvents::OUT;
+    // Extended attributes are not supported by sysfs.
+    // Getting one reports that the attribute does not exist,
+    // while the other operations report that they are not supported
+    // (`ENOTSUP` is the same as `EOPNOTSUPP` on Linux).
+
+    fn set_xattr(
+        &self,
+        _name: XattrName,
+        _value_reader: &mut VmReader,
+        _flags: XattrSetFlags,
+    ) -> Result<()> {
+        Err(Error::new(Errno::EOPNOTSUPP))
+    }
+
+    fn get_xattr(&self, _name: XattrName, _value_writer: &mut VmWriter) -> Result<usize> {
+        Err(Error::new(Errno::ENODATA))
+    }
+
+    fn list_xattr(&self, _namespace: XattrNamespace, _list_writer: &mut VmWriter) -> Result<usize> {
+        Err(Error::new(Errno::EOPNOTSUPP))
+    }
+
+    fn remove_xattr(&self, _name: XattrName) -> Result<()> {
+        Err(Error::new(Errno::EOPNOTSUPP))
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        let mut events = IoEvents::IN | IoEvents::OUT;
+        // An attribute may be temporarily unreadable (e.g., the hardware is offline)
+        if let InnerNode::Attr(attr, sysnode) = &self.inner_node {
+            if !sysnode.attr_is_readable_now(attr.name()) {
+                events.remove(IoEvents::IN);
+            }
+        }
+        if mask.contains(IoEvents::PRI) {
+            if let Some(attr_watch) = self.attr_watch() {
+                events |= attr_watch.poll(IoEvents::PRI, poller);
+            }
+        }
         events & mask
     }
 
@@ -284,21 +476,262 @@ impl Inode for SysfsInode {
 }
 
 impl SysFsInode {
-    pub(crate) fn new_root() -> Self {
-        let root_inner_node = {
-            let sysnode = systree::singleton().root().clone();
-            InnerNode::Branch(sysnode)
-        };
+    pub(crate) fn new_root(sysnode: Arc<dyn SysBranchNode>, fs: Weak<SysFs>) -> Arc<SysFsInode> {
+        let root_inner_node = InnerNode::Branch(sysnode);
         let none_parent = Weak::new();
-        Self::do_new_dir(root_inner_node, none_parent)
+        Self::do_new_dir(root_inner_node, fs, none_parent)
     }
 
     pub fn this(&self) -> Arc<SysFsInode> {
         self.this.upgrade().unwrap()
     }
 
+    /// Returns the file system to which this inode belongs.
+    pub fn sysfs(&self) -> Arc<SysFs> {
+        self.fs.upgrade().unwrap()
+    }
+
+    /// Reads the dentries in this dir, starting from a cursor.
+    ///
+    /// This method is an alternative to the `readdir_at` method.
+    /// Instead of an offset whose meaning depends on the encoding of inode numbers,
+    /// the position in the dir is represented by an opaque `DirCursor`.
+    /// Start with `DirCursor::START` and pass the returned cursor to the next call
+    /// until `None` is returned, which indicates that the end of the dir is reached.
+    ///
+    /// The guarantees on concurrent changes are the same as those of `readdir_at`:
+    /// some of the concurrently-added entries may be missed,
+    /// but no entry is reported twice.
+    pub fn readdir_with_cursor(
+        &self,
+        cursor: DirCursor,
+        visitor: &mut dyn DirentVisitor,
+    ) -> Result<Option<DirCursor>> {
+        if self.type_() != InodeType::DIR {
+            return Err(Error::new(Errno::ENOTDIR));
+        }
+        self.check_dir_permission(InodeMode::is_readable)?;
+
+        let next_cursor = self
+            .dump_dentries(cursor.min_ino, visitor)?
+            .map(|last_dentry_offset| DirCursor {
+                min_ino: last_dentry_offset + 1,
+            });
+        Ok(next_cursor)
+    }
+
+    /// Dumps the dentries whose positions are no less than `min_ino`
+    /// into the visitor, returning the position of the last dumped dentry.
+    ///
+    /// If no dentries are dumped because the dir has no more dentries,
+    /// `None` is returned.
+    /// If the visitor fails on the very first dentry
+    /// (e.g., the buffer is too small to fit a single dentry),
+    /// the error of the visitor is returned,
+    /// so the caller knows to retry with a larger buffer.
+    /// If the visitor fails on a later dentry,
+    /// the dentries dumped so far are reported
+    /// and the failed one is dumped again by the next call.
+    fn dump_dentries(&self, min_ino: Ino, visitor: &mut dyn DirentVisitor) -> Result<Option<Ino>> {
+        let mut dentry_iter = self.new_dentry_iter(min_ino);
+
+        // Dump the dentries returned by the iterator into the output visitor
+        let mut last_dentry_offset = None;
+        while let Some(dentry) = dentry_iter.next() {
+            let res = visitor.visit(&dentry.name, dentry.ino, dentry.type_, dentry.offset as usize);
+            if let Err(err) = res {
+                if last_dentry_offset.is_none() {
+                    return Err(err);
+                } else {
+                    break;
+                }
+            }
+
+            last_dentry_offset = Some(dentry.offset);
+        }
+        Ok(last_dentry_offset)
+    }
+
+    /// Checks whether the mode of a dir inode grants a permission,
+    /// failing with `EACCES` otherwise.
+    ///
+    /// The permissions of dirs are enforced only if
+    /// `SysFsOptions::dir_permissions` is enabled.
+    fn check_dir_permission(&self, is_permitted: fn(&InodeMode) -> bool) -> Result<()> {
+        if !self.sysfs().options().dir_permissions() {
+            return Ok(());
+        }
+
+        // TODO: check the permission against the credentials of the caller.
+        // Until the credentials are available,
+        // the caller is treated as the owner of the dir.
+        if !is_permitted(&*self.mode.read()) {
+            return Err(Error::new(Errno::EACCES));
+        }
+        Ok(())
+    }
+
+    /// Returns the watch on the changes of the attribute of an attribute inode,
+    /// creating it if it does not exist.
+    fn attr_watch(&self) -> Option<&Arc<AttrWatch>> {
+        let InnerNode::Attr(attr, sysnode) = &self.inner_node else {
+            return None;
+        };
+        let attr_watch = self.attr_watch.call_once(|| {
+            let systree = self.sysfs().systree();
+            let attr_watch = Arc::new(AttrWatch::new(attr.name().clone(), sysnode.clone(), systree));
+            // Only the changes of the node are of interest,
+            // which are matched against the node itself rather than its path
+            // since the node may be moved (see `AttrWatch::on_events`)
+            let selector = SysEventSelector::Action(SysEventAction::Change);
+            let handle = systree
+                .register_observer(Arc::downgrade(&attr_watch) as _, selector);
+            attr_watch.handle.call_once(|| handle);
+            attr_watch
+        });
+        Some(attr_watch)
+    }
+
+    /// Returns the state of a dir inode
+    /// on which the results of the lookups in the dir depend.
+    ///
+    /// `None` is returned if the state cannot be tracked,
+    /// i.e., if the children of the branching node of the dir must not be cached
+    /// (see `SysBranchNode::generation`).
+    fn dir_state(&self) -> Option<DirState> {
+        let (generation, attr_set) = match &self.inner_node {
+            InnerNode::Branch(sysnode) => {
+                let generation = sysnode.generation();
+                if generation == 0 {
+                    return None;
+                }
+                (generation, sysnode.node_attrs())
+            }
+            InnerNode::Leaf(sysnode) => (0, sysnode.node_attrs()),
+            InnerNode::AttrGroup(_, sysnode) => (0, sysnode.node_attrs()),
+            _ => return None,
+        };
+        Some(DirState {
+            generation,
+            attr_set,
+            options_epoch: self.sysfs().options().epoch(),
+        })
+    }
+
+    /// Derives the mode of an attribute inode again
+    /// if the flags of the attribute have been changed at runtime.
+    fn sync_attr_mode(&self) {
+        let InnerNode::Attr(attr, sysnode) = &self.inner_node else {
+            return;
+        };
+        let Some(new_flags) = sysnode.attr_flags(attr.name()) else {
+            return;
+        };
+        if *self.attr_flags.read() == new_flags {
+            return;
+        }
+
+        // The flags are queried and compared again under the write lock,
+        // so that concurrent syncs derive the mode only once
+        // and never overwrite newer flags with older ones.
+        let mut attr_flags = self.attr_flags.write();
+        let Some(new_flags) = sysnode.attr_flags(attr.name()) else {
+            return;
+        };
+        if *attr_flags == new_flags {
+            return;
+        }
+        *self.mode.write() = Self::flags_to_inode_mode(new_flags);
+        *attr_flags = new_flags;
+    }
+
+    /// Returns the target path of a symlink inode.
+    ///
+    /// Unlike `read_link`, this method borrows the path stored in
+    /// the symlink node, thus requiring no allocation.
+    pub fn link_target(&self) -> Result<&str> {
+        match &self.inner_node {
+            InnerNode::Symlink(symlink_node) => Ok(symlink_node.target_path()),
+            InnerNode::SubsystemLink(_, target_path) => Ok(target_path),
+            _ => Err(Error::new(Errno::EINVAL)),
+        }
+    }
+
+    /// Returns the node of a dir inode.
+    fn dir_sysnode(&self) -> Option<Arc<dyn SysNode>> {
+        match &self.inner_node {
+            InnerNode::Branch(sysnode) => Some(sysnode.clone()),
+            InnerNode::Leaf(sysnode) => Some(sysnode.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the target path of the synthetic `subsystem` symlink in this dir,
+    /// if the symlink should be exposed.
+    ///
+    /// The target is the subsystem whose members include the node of this dir
+    /// (see `SysTree::subsystem_of`),
+    /// in the form of a path relative to this dir (e.g., `../..`).
+    fn subsystem_link_target(&self) -> Option<String> {
+        if !self.sysfs().options().subsystem_links() {
+            return None;
+        }
+
+        let sysnode = self.dir_sysnode()?;
+        let path = sysnode.path();
+        // Nodes out of any registered subsystems have no such symlinks
+        self.sysfs().systree().subsystem_of(&path)?;
+        let depth = path.split('/').filter(|name| !name.is_empty()).count();
+        Some(vec![".."; depth - 1].join("/"))
+    }
+
+    fn new_subsystem_link(&self) -> Option<Arc<SysFsInode>> {
+        let target_path = self.subsystem_link_target()?;
+        let sysnode = self.dir_sysnode()?;
+        let inner_node = InnerNode::SubsystemLink(sysnode, target_path);
+        Some(self.do_new_symlink(inner_node))
+    }
+
+    /// Looks up a synthetic entry in this dir.
+    fn lookup_synthetic(&self, name: &str) -> Option<Arc<SysFsInode>> {
+        if name == SUBSYSTEM_LINK_NAME {
+            return self.new_subsystem_link();
+        }
+
+        let synthetic_attr = SyntheticAttr::from_name(name)?;
+        if !synthetic_attr.is_enabled(self.sysfs().options()) {
+            return None;
+        }
+        let sysnode = self.dir_sysnode()?;
+        Some(self.new_synthetic_attr_file(synthetic_attr, sysnode))
+    }
+
+    /// Returns whether this dir has a real entry,
+    /// i.e., an attribute or a child node, with the given name.
+    ///
+    /// A real entry hides the synthetic one with the same name.
+    fn has_real_entry(&self, name: &str) -> bool {
+        let Some(sysnode) = self.dir_sysnode() else {
+            return false;
+        };
+        sysnode.node_attrs().contains(name)
+            || shown_attr_groups(&*sysnode).iter().any(|group| group.name() == name)
+            || sysnode
+                .as_branch()
+                .is_some_and(|branch| branch.child(name).is_some_and(|child| !child.is_internal()))
+    }
+
     fn lookup_node_or_attr(&self, name: &str, sysnode: &Arc<dyn SysBranchNode>) -> Result<Arc<SysInode>> {
-        if let Some(child_sysnode) = sysnode.child(name) {
+        // The generation is loaded before the lookup of the child
+        // so that a concurrent change invalidates the cached inode.
+        let generation = sysnode.generation();
+        // The internal nodes are not exposed to the user space
+        let child_sysnode = sysnode.child(name).filter(|child| !child.is_internal());
+        if let Some(child_sysnode) = child_sysnode {
+            if let Some(child_inode) = self.child_cache.lock().get(child_sysnode.id(), generation) {
+                return Ok(child_inode);
+            }
+
             let child_type = child_sysnode.type_(); 
             let child_inode = match child_type {
                 SysNodeType::Branch => {
@@ -314,18 +747,42 @@ impl SysFsInode {
                     self.new_symlink(child_symlink)
                 }
             };
+            self.child_cache
+                .lock()
+                .insert(*child_sysnode.id(), &child_inode, generation);
             return Ok(child_inode);
         }
 
-        let Some(attr) = sysnode.node_attrs.get(name) else {
+        let sysnode: Arc<dyn SysNode> = sysnode.clone();
+        self.lookup_attr(name, &sysnode)
+    }
+
+    fn lookup_attr(&self, name: &str, sysnode: &Arc<dyn SysNode>) -> Result<Arc<SysInode>> {
+        let attr_groups = shown_attr_groups(&**sysnode);
+        if let Some(group_index) = attr_groups.iter().position(|group| group.name() == name) {
+            return Ok(self.new_attr_group_dir(group_index, sysnode.clone()));
+        }
+        // The grouped attributes are only shown in the dirs of their groups
+        if attr_groups.iter().any(|group| group.contains(name)) {
+            return Err(Error::new(Errno::ENOENT));
+        }
+
+        let attr_set = sysnode.node_attrs();
+        let Some(attr) = attr_set.get(name) else {
             return Err(Error::new(Errno::ENOENT));
         };
         let new_attr_file = self.new_attr_file(attr, sysnode.clone());
         Ok(new_attr_file)
     }
 
-    fn lookup_attr(&self, name: &str, sysnode: &Arc<dyn SysNode>) -> Result<Arc<SysInode>> {
-        let Some(attr) = sysnode.node_attrs.get(name) else {
+    fn lookup_grouped_attr(&self, name: &str, group_index: usize, sysnode: &Arc<dyn SysNode>) -> Result<Arc<SysInode>> {
+        let group = &sysnode.attr_groups()[group_index];
+        if !group.contains(name) {
+            return Err(Error::new(Errno::ENOENT));
+        }
+
+        let attr_set = sysnode.node_attrs();
+        let Some(attr) = attr_set.get(name) else {
             return Err(Error::new(Errno::ENOENT));
         };
         let new_attr_file = self.new_attr_file(attr, sysnode.clone());
@@ -335,27 +792,46 @@ impl SysFsInode {
     fn new_branch_dir(&self, sysnode: Arc<dyn SysBranchNode>) -> Arc<SysFsInode> {
         let inner_node = InnerNode::Branch(sysnode);
         let parent = self.this.clone();
-        Self::do_new_dir(inner_node, parent)
+        Self::do_new_dir(inner_node, self.fs.clone(), parent)
     }
 
     fn new_leaf_dir(&self, sysnode: Arc<dyn SysNode>) -> Arc<SysFsInode> {
         let inner_node = InnerNode::Leaf(sysnode);
         let parent = self.this.clone();
-        Self::do_new_dir(inner_node, parent)
+        Self::do_new_dir(inner_node, self.fs.clone(), parent)
+    }
+
+    fn new_attr_group_dir(&self, group_index: usize, sysnode: Arc<dyn SysNode>) -> Arc<SysFsInode> {
+        let inner_node = InnerNode::AttrGroup(group_index, sysnode);
+        let parent = self.this.clone();
+        Self::do_new_dir(inner_node, self.fs.clone(), parent)
     }
 
-    fn do_new_dir(inner_node: InnerNode, parent: Weak<SysFsInode>) -> Arc<SysFsInode> {
+    fn do_new_dir(inner_node: InnerNode, fs: Weak<SysFs>, parent: Weak<SysFsInode>) -> Arc<SysFsInode> {
+        // The generation is loaded before counting the child dirs
+        // so that a concurrent change invalidates the count.
+        let generation = Self::dir_generation(&inner_node);
+        let num_child_dirs = Self::num_child_dirs(&inner_node);
         let metadata = {
             let ino = ino::from_inner_node(&inner_node);
             let inode_type = InodeType::Dir;
-            Self::new_metadata(ino, inode_type)
+            let mut metadata = Self::new_metadata(ino, inode_type, 0);
+            metadata.nlink = Self::dir_nlink(&inner_node, num_child_dirs);
+            metadata
         };
+        let num_child_dirs = Mutex::new((generation, num_child_dirs));
         let mode = InodeMode::from_bits_truncate(0o555); // Everyone is allowed to read and list the dir
         Arc::new_cyclic(|this| {
             SysFsInode {
                 inner_node,
                 metadata,
                 mode,
+                num_child_dirs,
+                attr_flags: RwLock::new(SysAttrFlags::empty()),
+                negative_cache: Mutex::new(NegativeLookupCache::new()),
+                child_cache: Mutex::new(ChildInodeCache::new()),
+                attr_watch: Once::new(),
+                fs,
                 parent,
                 this,
             }
@@ -363,19 +839,60 @@ impl SysFsInode {
     }
 
     fn new_attr_file(&self, attr: &SysAttr, sysnode: Arc<dyn SysNode>) -> Arc<SysInode> {
+        // The flags may have been changed at runtime
+        let attr_flags = sysnode.attr_flags(attr.name()).unwrap_or(attr.flags());
+        let size = Self::attr_file_size(attr, sysnode.as_ref());
         let inner_node = InnerNode::Attr(attr.clone(), sysnode);
         let metadata = {
             let ino = ino::from_inner_node(&inner_node);
             let inode_type = InodeType::File;
-            Self::new_metadata(ino, inode_type)
+            Self::new_metadata(ino, inode_type, size)
         };
-        let mode = Self::flags_to_inode_mode(attr.flags());
+        let mode = Self::flags_to_inode_mode(attr_flags);
         let parent = self.this.clone();
         Arc::new_cyclic(|this| {
             SysFsInode {
                 inner_node,
                 metadata,
                 mode,
+                num_child_dirs: Mutex::new((0, 0)),
+                attr_flags: RwLock::new(attr_flags),
+                negative_cache: Mutex::new(NegativeLookupCache::new()),
+                child_cache: Mutex::new(ChildInodeCache::new()),
+                attr_watch: Once::new(),
+                fs: self.fs.clone(),
+                parent,
+                this,
+            }
+        })
+    }
+
+    fn new_synthetic_attr_file(&self, synthetic_attr: SyntheticAttr, sysnode: Arc<dyn SysNode>) -> Arc<SysFsInode> {
+        let inner_node = InnerNode::SyntheticAttr(synthetic_attr, sysnode);
+        let metadata = {
+            let ino = ino::from_inner_node(&inner_node);
+            let inode_type = InodeType::File;
+            Self::new_metadata(ino, inode_type, 0)
+        };
+        // Everyone is allowed to read the file,
+        // but only the owner is allowed to write it if it is writable.
+        let (mode, attr_flags) = if synthetic_attr.is_writable() {
+            (InodeMode::from_bits_truncate(0o644), SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE)
+        } else {
+            (InodeMode::from_bits_truncate(0o444), SysAttrFlags::CAN_READ)
+        };
+        let parent = self.this.clone();
+        Arc::new_cyclic(|this| {
+            SysFsInode {
+                inner_node,
+                metadata,
+                mode,
+                num_child_dirs: Mutex::new((0, 0)),
+                attr_flags: RwLock::new(attr_flags),
+                negative_cache: Mutex::new(NegativeLookupCache::new()),
+                child_cache: Mutex::new(ChildInodeCache::new()),
+                attr_watch: Once::new(),
+                fs: self.fs.clone(),
                 parent,
                 this,
             }
@@ -384,10 +901,14 @@ impl SysFsInode {
 
     fn new_symlink(&self, sysnode: Arc<dyn SysSymlink>) -> Arc<SysFsInode> {
         let inner_node = InnerNode::Symlink(sysnode);
+        self.do_new_symlink(inner_node)
+    }
+
+    fn do_new_symlink(&self, inner_node: InnerNode) -> Arc<SysFsInode> {
         let metadata = {
             let ino = ino::from_inner_node(&inner_node);
             let inode_type = InodeType::Symlink;
-            Self::new_metadata(ino, inode_type)
+            Self::new_metadata(ino, inode_type, 0)
         };
         let mode = InodeMode::from_bits_truncate(0o0444); // Everyone is allowed to read the link 
         let parent = self.this.clone();
@@ -396,6 +917,12 @@ impl SysFsInode {
                 inner_node,
                 metadata,
                 mode,
+                num_child_dirs: Mutex::new((0, 0)),
+                attr_flags: RwLock::new(SysAttrFlags::empty()),
+                negative_cache: Mutex::new(NegativeLookupCache::new()),
+                child_cache: Mutex::new(ChildInodeCache::new()),
+                attr_watch: Once::new(),
+                fs: self.fs.clone(),
                 parent,
                 this,
             }
@@ -407,21 +934,27 @@ impl SysFsInode {
         // Creates an iterator that returns dentries in the following order:
         //
         // 1. Dentries of the attributes;
-        // 2. Dentries of the child nodes;
-        // 3. The dentry of this inode;
-        // 4. The dentry of the parent inode.
+        // 2. Dentries of the attribute groups;
+        // 3. Dentries of the synthetic entries;
+        // 4. Dentries of the child nodes;
+        // 5. The dentry of this inode;
+        // 6. The dentry of the parent inode.
         //
         // and satisfies the bound that
         // their inode numbers are greater or equal to `min_ino`.
+        let synthetic_dentry_iter = self.new_synthetic_dentries(min_ino).into_iter();
         return match self.inner_node {
             InnerNode::Branch(sysnode) => {
                 let attr_dentry_iter = AttrDentryIter::new(
-                    sysnode.attr_set(), self.ino(), min_ino);
+                    ungrouped_attrs(&*sysnode), self.ino(), min_ino);
+                let group_dentry_iter = GroupDentryIter::new(
+                    shown_attr_groups(&*sysnode), self.ino(), min_ino);
                 let node_dentry_iter = NodeDentryIter::new({
                     let mut children = Vec::new();
-                    sysnode.visit_children_with(min_ino, &mut |child| {
-                        if child.id() >= min_ino {
-                            children.push_back(child.clone());
+                    sysnode.visit_children_with(0, &mut |child| {
+                        // The internal nodes are not exposed to the user space
+                        if ino::from_sysnode_id(child.id()) >= min_ino && !child.is_internal() {
+                            children.push(child.clone());
                         }
                         Some(())
                     });
@@ -430,14 +963,39 @@ impl SysFsInode {
                 let special_dentry_iter = ThisAndParentDentryIter::new(
                     self, min_ino);
                 attr_dentry_iter
+                    .chain(group_dentry_iter)
+                    .chain(synthetic_dentry_iter)
                     .chain(node_dentry_iter)
                     .chain(special_dentry_iter) 
             }
             InnerNode::Leaf(sysnode) => {
-                let attr_dentry_iter = AttrDentryIter::new(sysnode.attr_set(), min_ino);
+                let attr_dentry_iter = AttrDentryIter::new(ungrouped_attrs(&*sysnode), self.ino(), min_ino);
+                let group_dentry_iter = GroupDentryIter::new(shown_attr_groups(&*sysnode), self.ino(), min_ino);
                 let node_dentry_iter = NodeDentryIter::new(Vec::new());
                 let special_dentry_iter = ThisAndParentDentryIter::new(self, min_ino);
                 attr_dentry_iter
+                    .chain(group_dentry_iter)
+                    .chain(synthetic_dentry_iter)
+                    .chain(node_dentry_iter)
+                    .chain(special_dentry_iter) 
+            }
+            InnerNode::AttrGroup(group_index, sysnode) => {
+                // The inode numbers of the grouped attributes are derived from
+                // the dir of the node, not the dir of the group.
+                let node_dir_ino = ino::from_sysnode_id(sysnode.id());
+                let group = &sysnode.attr_groups()[group_index];
+                let grouped_attrs = sysnode
+                    .node_attrs()
+                    .iter()
+                    .filter(|attr| group.contains(attr.name()))
+                    .collect();
+                let attr_dentry_iter = AttrDentryIter::new(grouped_attrs, node_dir_ino, min_ino);
+                let group_dentry_iter = GroupDentryIter::new(&[], self.ino(), min_ino);
+                let node_dentry_iter = NodeDentryIter::new(Vec::new());
+                let special_dentry_iter = ThisAndParentDentryIter::new(self, min_ino);
+                attr_dentry_iter
+                    .chain(group_dentry_iter)
+                    .chain(synthetic_dentry_iter)
                     .chain(node_dentry_iter)
                     .chain(special_dentry_iter) 
             }
@@ -448,15 +1006,17 @@ impl SysFsInode {
 
         // Helper iterator types
 
-        struct AttrDentryIter<'a> {
-            attr_iter: SysAttrIter<'a>,
+        // The attributes are collected upon the creation of the iterator,
+        // since the attribute set of a node may be replaced at runtime.
+        struct AttrDentryIter {
+            attr_iter: alloc::vec::IntoIter<SysAttr>,
             dir_ino: Ino,
             min_ino: Ino,
         }
-        impl<'a> AttrDentryIter<'a> {
-            pub fn new(attr_set: &'a SysAttrSet, dir_ino: Ino, min_ino: Ino) -> Self {
+        impl AttrDentryIter {
+            pub fn new(attrs: Vec<SysAttr>, dir_ino: Ino, min_ino: Ino) -> Self {
                 Self {
-                    attr_iter,
+                    attr_iter: attrs.into_iter(),
                     dir_ino,
                     min_ino,
                 }
@@ -476,6 +1036,7 @@ impl SysFsInode {
 
                     let next = Dentry {
                         ino,
+                        offset: ino,
                         name: self.attr.name().to_owned(),
                         type_: InodeType::File,
                     };
@@ -484,6 +1045,49 @@ impl SysFsInode {
             }
         }
 
+        // The dentries of the attribute groups have the inode numbers
+        // in the subrange reserved for the groups (see `ino::GROUP_ID_BASE`).
+        struct GroupDentryIter {
+            group_iter: core::iter::Enumerate<alloc::vec::IntoIter<SysStr>>,
+            dir_ino: Ino,
+            min_ino: Ino,
+        }
+        impl GroupDentryIter {
+            pub fn new(attr_groups: &[SysAttrGroup], dir_ino: Ino, min_ino: Ino) -> Self {
+                let group_names: Vec<SysStr> = attr_groups
+                    .iter()
+                    .map(|group| group.name().clone())
+                    .collect();
+                Self {
+                    group_iter: group_names.into_iter().enumerate(),
+                    dir_ino,
+                    min_ino,
+                }
+            }
+        }
+        impl Iterator for GroupDentryIter {
+            type Item = Dentry;
+
+            fn next(&mut self) -> Option<Dentry> {
+                loop {
+                    let (group_index, group_name) = self.group_iter.next()?;
+
+                    let ino = ino::from_dir_ino_and_attr_id(self.dir_ino, ino::group_id(group_index));
+                    if ino < self.min_ino {
+                        continue;
+                    }
+
+                    let next = Dentry {
+                        ino,
+                        offset: ino,
+                        name: group_name,
+                        type_: InodeType::DIR,
+                    };
+                    return Some(next);
+                }
+            }
+        }
+
         struct NodeDentryIter {
             node_iter: alloc::vec::IntoIter<Arc<dyn SysObj>>,
         }
@@ -503,6 +1107,7 @@ impl SysFsInode {
                 let ino = ino::from_sysnode_id(next_node.id());
                 let next_dentry= Dentry {
                     ino,
+                    offset: ino,
                     name: next_node.name().to_owned(),
                     type_: match next_node.type_() {
                         SysNodeType::Branch | SysNodeType::Leaf => InodeType::DIR,
@@ -540,6 +1145,7 @@ impl SysFsInode {
                 if self.min_ino <= Self::THIS_DENTRY_INO {
                     let next_dentry = Dentry {
                         ino: self.this_dir.ino(),
+                        offset: Self::THIS_DENTRY_INO,
                         name: ".".into(),
                         type_: InodeType::DIR,
                     };
@@ -555,6 +1161,7 @@ impl SysFsInode {
                                 .map_or(this_dir_ino, |parent| parent.ino());
                             parent_dir_ino
                         },
+                        offset: Self::PARENT_DENTRY_INO,
                         name: "..".into(),
                         type_: InodeType::DIR,
                     };
@@ -567,6 +1174,99 @@ impl SysFsInode {
         }
     }
 
+    /// Creates the dentries of the synthetic entries in this dir
+    /// whose inode numbers are no less than `min_ino`,
+    /// in the ascending order of the inode numbers.
+    fn new_synthetic_dentries(&self, min_ino: Ino) -> Vec<Dentry> {
+        let mut dentries = Vec::new();
+        // Only the dirs of nodes contain synthetic entries
+        if self.dir_sysnode().is_none() {
+            return dentries;
+        }
+
+        let sysfs = self.sysfs();
+        for synthetic_attr in SyntheticAttr::ALL {
+            if !synthetic_attr.is_enabled(sysfs.options()) {
+                continue;
+            }
+            let ino = ino::from_dir_ino_and_attr_id(self.ino(), synthetic_attr.id());
+            dentries.push(Dentry {
+                ino,
+                offset: ino,
+                name: synthetic_attr.name().into(),
+                type_: InodeType::File,
+            });
+        }
+
+        if self.subsystem_link_target().is_some() {
+            let ino = ino::from_dir_ino_and_attr_id(self.ino(), ino::SUBSYSTEM_LINK_ID);
+            dentries.push(Dentry {
+                ino,
+                offset: ino,
+                name: SUBSYSTEM_LINK_NAME.into(),
+                type_: InodeType::SymLink,
+            });
+        }
+
+        dentries.retain(|dentry| dentry.ino >= min_ino && !self.has_real_entry(&dentry.name));
+        dentries.sort_by_key(|dentry| dentry.ino);
+        dentries
+    }
+
+    /// Returns the current `nlink` of a dir inode.
+    ///
+    /// The child dirs of nodes are counted again
+    /// only if the children have changed (see `SysBranchNode::generation`),
+    /// whereas the dirs of attribute groups are always counted.
+    fn current_dir_nlink(&self) -> usize {
+        let generation = Self::dir_generation(&self.inner_node);
+        let mut num_child_dirs = self.num_child_dirs.lock();
+        if generation == 0 || num_child_dirs.0 != generation {
+            *num_child_dirs = (generation, Self::num_child_dirs(&self.inner_node));
+        }
+        Self::dir_nlink(&self.inner_node, num_child_dirs.1)
+    }
+
+    fn dir_generation(inner_node: &InnerNode) -> u64 {
+        match inner_node {
+            InnerNode::Branch(sysnode) => sysnode.generation(),
+            _ => 0,
+        }
+    }
+
+    /// Calculates the number of hard links of a dir,
+    /// given the number of the child dirs of nodes (see `num_child_dirs`).
+    ///
+    /// As in most file systems, the number is two (for `.` and the dentry in the parent)
+    /// plus the number of child dirs (for their `..`).
+    fn dir_nlink(inner_node: &InnerNode, num_child_dirs: usize) -> usize {
+        // The dirs of attribute groups are child dirs, too
+        let num_group_dirs = match inner_node {
+            InnerNode::Branch(sysnode) => shown_attr_groups(&**sysnode).len(),
+            InnerNode::Leaf(sysnode) => shown_attr_groups(&**sysnode).len(),
+            _ => 0,
+        };
+        2 + num_group_dirs + num_child_dirs
+    }
+
+    /// Counts the child dirs of the node of a dir,
+    /// i.e., the children that are branching or leaf nodes and are not internal.
+    fn num_child_dirs(inner_node: &InnerNode) -> usize {
+        let InnerNode::Branch(sysnode) = inner_node else {
+            return 0;
+        };
+
+        let mut num_child_dirs = 0;
+        sysnode.visit_children_with(0, &mut |child| {
+            let is_dir = matches!(child.type_(), SysNodeType::Branch | SysNodeType::Leaf);
+            if is_dir && !child.is_internal() {
+                num_child_dirs += 1;
+            }
+            Some(())
+        });
+        num_child_dirs
+    }
+
     fn flags_to_inode_mode(attr_flags: SysAttrFlags) -> InodeMode {
         let mut inode_mode = InodeMode::empty();
         if attr_flags.contains(SysAttrFlags::CAN_READ) {
@@ -578,19 +1278,35 @@ impl SysFsInode {
         inode_mode
     }
 
-    fn new_metadata(ino: u64, type_: InodeType) -> Metadata {
+    fn new_metadata(ino: u64, type_: InodeType, size: usize) -> Metadata {
         // Experiments on Linux show that the timestamps of inodes 
         // are determined at the time when the inode is first visisted
         // and won't be changed afterwards.
         let now = crate::time::clocks::RealTimeCoarseClock::get().read_time();
-        Metadata {
+        let mut metadata = Metadata {
             ino,
             type_,
+            blk_size: BLOCK_SIZE,
             atime: now,
             mtime: now,
             ctime: now,
             ..Default::default()
-        }
+        };
+        Self::set_metadata_size(&mut metadata, size);
+        metadata
+    }
+
+    /// Sets the size of an inode, along with the number of blocks it takes.
+    fn set_metadata_size(metadata: &mut Metadata, size: usize) {
+        metadata.size = size;
+        metadata.blocks = size.div_ceil(BLOCK_SIZE);
+    }
+
+    /// Returns the size of the file of an attribute (see `SysNode::attr_size`).
+    ///
+    /// As on Linux, the size of a computed value is estimated as one page.
+    fn attr_file_size(attr: &SysAttr, sysnode: &dyn SysNode) -> usize {
+        sysnode.attr_size(attr.name()).unwrap_or(0)
     }
 
 }
@@ -608,11 +1324,455 @@ enum InnerNode {
     Leaf(Arc<dyn SysNode>),
     Attr(SysAttr, Arc<dyn SysNode>),
     Symlink(Arc<dyn SysSymlink>),
+    // The synthetic `subsystem` symlink in the dir of a node,
+    // with the target path of the symlink.
+    SubsystemLink(Arc<dyn SysNode>, String),
+    // A synthetic attribute in the dir of a node.
+    SyntheticAttr(SyntheticAttr, Arc<dyn SysNode>),
+    // The dir of an attribute group of a node,
+    // with the index of the group in `SysNode::attr_groups`.
+    AttrGroup(usize, Arc<dyn SysNode>),
+}
+
+/// Returns the attribute groups of a node that are shown in sysfs.
+///
+/// The groups beyond `ino::MAX_GROUPS` are ignored
+/// since there are no inode numbers left for them.
+fn shown_attr_groups<N: SysNode + ?Sized>(sysnode: &N) -> &[SysAttrGroup] {
+    let attr_groups = sysnode.attr_groups();
+    &attr_groups[..attr_groups.len().min(ino::MAX_GROUPS)]
+}
+
+/// Returns the attributes of a node that are shown directly in the dir of the node,
+/// i.e., those not contained in any attribute groups.
+fn ungrouped_attrs<N: SysNode + ?Sized>(sysnode: &N) -> Vec<SysAttr> {
+    let attr_groups = shown_attr_groups(sysnode);
+    sysnode
+        .node_attrs()
+        .iter()
+        .filter(|attr| !attr_groups.iter().any(|group| group.contains(attr.name())))
+        .collect()
+}
+
+/// The synthetic attributes that sysfs adds to the dir of a node,
+/// which are not contained in the attribute set of the node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SyntheticAttr {
+    // The path of the node (see `SysFsOptions::debug_attrs`).
+    DebugPath,
+    // The name of the node (see `SysFsOptions::debug_attrs`).
+    DebugName,
+    // The `uevent` file as in Linux, which shows the environment of the node
+    // and re-triggers an event of the node when an action is written to it.
+    Uevent,
+}
+
+impl SyntheticAttr {
+    const ALL: [Self; 3] = [Self::DebugPath, Self::DebugName, Self::Uevent];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|attr| attr.name() == name)
+    }
+
+    fn name(&self) -> &'static str {
+        // The debugging names are prefixed to make collisions with real entries unlikely,
+        // whereas `uevent` keeps its conventional name.
+        // Either way, a real entry with the same name hides the synthetic one
+        // (see `SysFsInode::has_real_entry`).
+        match self {
+            Self::DebugPath => "__sysfs_path",
+            Self::DebugName => "__sysfs_name",
+            Self::Uevent => "uevent",
+        }
+    }
+
+    fn id(&self) -> SysAttrId {
+        match self {
+            Self::DebugPath => ino::DEBUG_PATH_ID,
+            Self::DebugName => ino::DEBUG_NAME_ID,
+            Self::Uevent => ino::UEVENT_ID,
+        }
+    }
+
+    fn is_enabled(&self, options: &SysFsOptions) -> bool {
+        match self {
+            Self::DebugPath | Self::DebugName => options.debug_attrs(),
+            Self::Uevent => true,
+        }
+    }
+
+    fn is_writable(&self) -> bool {
+        matches!(self, Self::Uevent)
+    }
+
+    fn read(&self, sysnode: &dyn SysNode, systree: &SysTree, writer: &mut VmWriter) -> Result<usize> {
+        let value = match self {
+            Self::DebugPath => format!("{}\n", sysnode.path()),
+            Self::DebugName => format!("{}\n", sysnode.name()),
+            Self::Uevent => uevent_env(sysnode, systree),
+        };
+        let mut reader = VmReader::from(value.as_bytes());
+        writer
+            .write_fallible(&mut reader)
+            .map_err(|(err, _)| Error::from(err))
+    }
+
+    fn write(&self, sysnode: &dyn SysNode, systree: &SysTree, reader: &mut VmReader) -> Result<usize> {
+        if !self.is_writable() {
+            return Err(Error::new(Errno::EACCES));
+        }
+
+        // A valid action is short, so there is no need to read more than a few bytes
+        const MAX_LEN: usize = 16;
+        if reader.remain() > MAX_LEN {
+            return Err(Error::new(Errno::EINVAL));
+        }
+
+        let mut buf = [0u8; MAX_LEN];
+        let mut writer = VmWriter::from(&mut buf[..]);
+        let write_len = reader
+            .read_fallible(&mut writer)
+            .map_err(|(err, _)| Error::from(err))?;
+
+        let value = core::str::from_utf8(&buf[..write_len])
+            .map_err(|_| Error::new(Errno::EINVAL))?;
+        let action = match value.trim() {
+            "add" => SysEventAction::Add,
+            "change" => SysEventAction::Change,
+            "remove" => SysEventAction::Remove,
+            _ => return Err(Error::new(Errno::EINVAL)),
+        };
+        systree.publish_event(sysnode, action, Vec::new());
+        Ok(write_len)
+    }
+}
+
+/// Returns the environment of a node shown in its `uevent` file,
+/// i.e., the entries of the uevents of the node as `KEY=VALUE` lines,
+/// except for those that vary from event to event (e.g., `ACTION`).
+fn uevent_env(sysnode: &dyn SysNode, systree: &SysTree) -> String {
+    let path = sysnode.path();
+    let subsystem = systree.subsystem_of(&path);
+    let event = SysEvent::new(SysEventAction::Add, path.into(), Vec::new()).with_subsystem(subsystem);
+    let buf = event.to_uevent_bytes();
+    let mut env = String::new();
+    for entry in buf.split(|byte| *byte == b'\0') {
+        let Ok(entry) = core::str::from_utf8(entry) else {
+            continue;
+        };
+        if entry.is_empty() || entry.starts_with("ACTION=") || entry.starts_with("SEQNUM=") {
+            continue;
+        }
+        env.push_str(entry);
+        env.push('\n');
+    }
+    env
+}
+
+/// The name of the synthetic `subsystem` symlink.
+const SUBSYSTEM_LINK_NAME: &str = "subsystem";
+
+/// An opaque cursor that represents a position in a dir of sysfs
+/// (see `SysFsInode::readdir_with_cursor`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DirCursor {
+    // Internally, the position is the minimum inode number
+    // of the dentries to be read.
+    min_ino: Ino,
+}
+
+impl DirCursor {
+    /// The cursor at the start of a dir.
+    pub const START: Self = Self { min_ino: 0 };
+}
+
+/// A file opened for writing a streamed attribute (see `SysAttrFlags::IS_STREAMED`).
+///
+/// The chunks written through the file are passed to the node
+/// at the offsets where the previous chunks end.
+/// Once the file is closed (i.e., dropped),
+/// the written value is committed with `SysNode::commit_attr`.
+struct StreamedAttrFile {
+    inode: Arc<SysFsInode>,
+    // The total number of bytes written through this file,
+    // or `None` if a write has failed, in which case nothing is committed.
+    written_len: Mutex<Option<usize>>,
+}
+
+impl StreamedAttrFile {
+    fn new(inode: Arc<SysFsInode>) -> Self {
+        Self {
+            inode,
+            written_len: Mutex::new(Some(0)),
+        }
+    }
+}
+
+impl Pollable for StreamedAttrFile {
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.inode.poll(mask, poller)
+    }
+}
+
+impl FileIo for StreamedAttrFile {
+    fn read(&self, writer: &mut VmWriter) -> Result<usize> {
+        // Only the committed value is read, so the offset is irrelevant
+        self.inode.read_direct_at(0, writer)
+    }
+
+    fn write(&self, reader: &mut VmReader) -> Result<usize> {
+        let mut written_len = self.written_len.lock();
+        let Some(offset) = *written_len else {
+            return Err(Error::new(Errno::EINVAL));
+        };
+        match self.inode.write_direct_at(offset, reader) {
+            Ok(write_len) => {
+                *written_len = Some(offset + write_len);
+                Ok(write_len)
+            }
+            Err(err) => {
+                *written_len = None;
+                Err(err)
+            }
+        }
+    }
+}
+
+impl Drop for StreamedAttrFile {
+    fn drop(&mut self) {
+        let Some(written_len) = *self.written_len.get_mut() else {
+            return;
+        };
+        let InnerNode::Attr(attr, sysnode) = &self.inode.inner_node else {
+            return;
+        };
+        // There is no one to report the error to once the file is closed.
+        // The failed commit leaves the old value intact.
+        let _ = sysnode.commit_attr(attr.name(), written_len);
+    }
+}
+
+/// A watch on the changes of an attribute, which backs the `poll` of an attribute inode.
+///
+/// A change of an attribute is signaled by a `Change` event of its node
+/// with the name of the attribute as the value of the `ATTR` key.
+/// Every change advances the change epoch of the attribute.
+///
+/// The semantics of polling `IoEvents::PRI` mimic those of Linux's sysfs:
+/// the first poll is ready immediately;
+/// after the attribute is read,
+/// the next poll is not ready until the attribute is changed again.
+/// So a user is expected to read the attribute
+/// every time the poll is ready (e.g., a GPIO value).
+///
+/// The watch is unregistered from the `SysTree` once it is dropped
+/// along with its inode.
+struct AttrWatch {
+    attr_name: SysStr,
+    sysnode: Arc<dyn SysNode>,
+    // The `SysTree` with which the watch is registered
+    systree: &'static SysTree,
+    // The handle of the watch as an observer of the `SysTree`
+    handle: Once<ObserverHandle>,
+    // The epoch of the latest change.
+    epoch: AtomicU64,
+    // The epoch that was current when the attribute was last read.
+    seen_epoch: AtomicU64,
+    pollee: Pollee,
+}
+
+impl AttrWatch {
+    fn new(attr_name: SysStr, sysnode: Arc<dyn SysNode>, systree: &'static SysTree) -> Self {
+        Self {
+            attr_name,
+            sysnode,
+            systree,
+            handle: Once::new(),
+            // The epoch starts ahead of the seen one,
+            // so the first poll is ready.
+            epoch: AtomicU64::new(1),
+            seen_epoch: AtomicU64::new(0),
+            pollee: Pollee::new(),
+        }
+    }
+
+    fn poll(&self, mask: IoEvents, poller: Option<&mut PollHandle>) -> IoEvents {
+        self.pollee.poll_with(mask, poller, || self.check_io_events())
+    }
+
+    fn check_io_events(&self) -> IoEvents {
+        let seen_epoch = self.seen_epoch.load(Ordering::Acquire);
+        if seen_epoch < self.epoch.load(Ordering::Acquire) {
+            IoEvents::PRI
+        } else {
+            IoEvents::empty()
+        }
+    }
+
+    fn mark_seen(&self) {
+        let epoch = self.epoch.load(Ordering::Acquire);
+        self.seen_epoch.fetch_max(epoch, Ordering::Release);
+    }
+}
+
+impl Observer<SysEvent> for AttrWatch {
+    fn on_events(&self, event: &SysEvent) {
+        let is_this_attr = event
+            .details()
+            .iter()
+            .any(|kv| kv.key == "ATTR" && kv.value == self.attr_name);
+        if !is_this_attr {
+            return;
+        }
+        // The events published at a path have no sources,
+        // in which case the current path of the node is compared
+        let is_this_node = match event.source() {
+            Some(source) => source.id() == self.sysnode.id(),
+            None => event.path() == self.sysnode.path(),
+        };
+        if !is_this_node {
+            return;
+        }
+
+        self.epoch.fetch_add(1, Ordering::AcqRel);
+        self.pollee.notify(IoEvents::PRI);
+    }
+}
+
+impl Drop for AttrWatch {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.get() {
+            self.systree.unregister_observer_by_handle(*handle);
+        }
+    }
+}
+
+/// A cache of the inodes of the child nodes in a dir,
+/// so that repeated lookups of a child return the same inode.
+///
+/// The cache is valid only for the generation of the branching node of the dir
+/// at which the inodes were inserted (see `SysBranchNode::generation`).
+/// Once the generation advances, the cache is cleared.
+/// Nothing is cached for a branching node whose generation is always zero.
+///
+/// The inodes are held weakly, so the cache keeps neither the inodes
+/// nor their nodes alive: the same inode is only needed
+/// as long as someone else holds it.
+/// The entries of the dropped inodes are pruned once the cache grows large.
+struct ChildInodeCache {
+    inodes: BTreeMap<SysNodeId, Weak<SysFsInode>>,
+    generation: u64,
+}
+
+impl ChildInodeCache {
+    // The number of the entries beyond which the dropped inodes are pruned
+    const PRUNE_THRESHOLD: usize = 64;
+
+    const fn new() -> Self {
+        Self {
+            inodes: BTreeMap::new(),
+            generation: 0,
+        }
+    }
+
+    fn get(&self, id: &SysNodeId, generation: u64) -> Option<Arc<SysFsInode>> {
+        if generation == 0 || self.generation != generation {
+            return None;
+        }
+        self.inodes.get(id).and_then(Weak::upgrade)
+    }
+
+    fn insert(&mut self, id: SysNodeId, inode: &Arc<SysFsInode>, generation: u64) {
+        // The children of a node with a zero generation must not be cached
+        if generation == 0 {
+            return;
+        }
+        if self.generation != generation {
+            self.inodes.clear();
+            self.generation = generation;
+        }
+        if self.inodes.len() >= Self::PRUNE_THRESHOLD {
+            self.inodes.retain(|_, inode| inode.strong_count() > 0);
+        }
+        self.inodes.insert(id, Arc::downgrade(inode));
+    }
+}
+
+/// The state of a dir on which the results of the lookups in the dir depend
+/// (see `SysFsInode::dir_state`).
+struct DirState {
+    // The generation of the branching node of the dir,
+    // which advances once the children change (see `SysBranchNode::generation`).
+    // It is always zero for the dirs of other nodes.
+    generation: u64,
+    // The attribute set of the node of the dir,
+    // which is replaced as a whole once the attributes change
+    // (see `SysNode::replace_node_attrs` and `SysNode::add_attr`).
+    attr_set: Arc<SysAttrSet>,
+    // The epoch of the options that expose synthetic entries
+    // (see `SysFsOptions::epoch`).
+    options_epoch: u64,
+}
+
+impl DirState {
+    fn is_same(&self, other: &Self) -> bool {
+        self.generation == other.generation
+            && Arc::ptr_eq(&self.attr_set, &other.attr_set)
+            && self.options_epoch == other.options_epoch
+    }
+}
+
+/// A small cache of the names that were recently found missing in a dir.
+///
+/// The cache is valid only for the state of the dir
+/// at which the names were inserted (see `SysFsInode::dir_state`).
+/// Once the state changes, the cache is cleared.
+struct NegativeLookupCache {
+    names: VecDeque<String>,
+    state: Option<DirState>,
+}
+
+impl NegativeLookupCache {
+    const CAPACITY: usize = 16;
+
+    const fn new() -> Self {
+        Self {
+            names: VecDeque::new(),
+            state: None,
+        }
+    }
+
+    fn contains(&self, name: &str, state: &DirState) -> bool {
+        let Some(cached_state) = &self.state else {
+            return false;
+        };
+        cached_state.is_same(state) && self.names.iter().any(|cached| cached == name)
+    }
+
+    fn insert(&mut self, name: &str, state: DirState) {
+        if !self.state.as_ref().is_some_and(|cached_state| cached_state.is_same(&state)) {
+            self.names.clear();
+            self.state = Some(state);
+        }
+        if self.names.iter().any(|cached| cached == name) {
+            return;
+        }
+
+        if self.names.len() >= Self::CAPACITY {
+            self.names.pop_front();
+        }
+        self.names.push_back(name.to_string());
+    }
 }
 
 /// A directory entry of sysfs.
 struct Dentry {
     pub ino: Ino,
+    // The position of the dentry in the dir, from which a later `readdir` resumes.
+    //
+    // The position is the inode number, except for `.` and `..`,
+    // whose positions are after those of all the other dentries.
+    pub offset: Ino,
     pub name: SysStr,
     pub type_: InodeType,
 }
@@ -620,15 +1780,42 @@ struct Dentry {
 mod ino {
     //! Calculating the inode numbers for sysfs inodes _deterministically_.
 
-    // The least significant 8 bits are used to encode the attribute ID.
-    const ATTR_INO_SHIFT: u8 = 8;
+    // The least significant bits are used to encode the attribute ID.
+    const ATTR_INO_SHIFT: u32 = SysAttrSet::ID_BITS;
     const_assert!(SysAttrSet::CAPACITY == (1_usize << ATTR_INO_SHIFT));
 
+    // Every node ID must survive the shift,
+    // so that the inode numbers of two distinct nodes,
+    // each in the range of `[dir_ino, dir_ino + CAPACITY)`, never overlap.
+    // Moreover, the topmost range must be left unused
+    // since its largest values are the offsets of the `.` and `..` dentries
+    // (see `SysFsInode::readdir_at`).
+    const_assert!(SysNodeId::MAX < Ino::MAX >> ATTR_INO_SHIFT);
+
     pub fn from_sysnode_id(node_id: &SysNodeId) -> Ino {
+        debug_assert!(node_id.as_u64() <= SysNodeId::MAX);
         node_id.as_u64() << ATTR_INO_SHIFT
     }
 
-    pub fn from_dir_ino_and_attr_id(dir_ino: Ino, attr_id: u8) -> Ino {
+    // The largest attribute IDs are never allocated to any attributes
+    // (see `SysAttrSet::NUM_RESERVED_IDS`), so they are reserved for
+    // the synthetic entries.
+    pub const SUBSYSTEM_LINK_ID: SysAttrId = (SysAttrSet::CAPACITY - 1) as SysAttrId;
+    pub const DEBUG_PATH_ID: SysAttrId = (SysAttrSet::CAPACITY - 2) as SysAttrId;
+    pub const DEBUG_NAME_ID: SysAttrId = (SysAttrSet::CAPACITY - 3) as SysAttrId;
+    pub const UEVENT_ID: SysAttrId = (SysAttrSet::CAPACITY - 4) as SysAttrId;
+
+    // The rest of the reserved attribute IDs are for the dirs of attribute groups.
+    pub const GROUP_ID_BASE: SysAttrId = SysAttrSet::MAX_ATTRS as SysAttrId;
+    pub const MAX_GROUPS: usize = (UEVENT_ID - GROUP_ID_BASE) as usize;
+    const_assert!(UEVENT_ID > GROUP_ID_BASE);
+
+    pub fn group_id(group_index: usize) -> SysAttrId {
+        debug_assert!(group_index < MAX_GROUPS);
+        GROUP_ID_BASE + group_index as SysAttrId
+    }
+
+    pub fn from_dir_ino_and_attr_id(dir_ino: Ino, attr_id: SysAttrId) -> Ino {
         dir_ino + (attr_id as Ino)
     }
 
@@ -637,10 +1824,22 @@ mod ino {
             InnerNode::Branch(sysnode) => from_sysnode_id(sysnode.id()),
             InnerNode::Leaf(sysnode) => from_sysnode_id(sysnode.id()),
             InnerNode::Symlink(sysnode) => from_sysnode_id(sysnode.id()),
+            InnerNode::SubsystemLink(sysnode, _) => {
+                let dir_ino = from_sysnode_id(sysnode.id());
+                from_dir_ino_and_attr_id(dir_ino, SUBSYSTEM_LINK_ID)
+            }
+            InnerNode::SyntheticAttr(synthetic_attr, sysnode) => {
+                let dir_ino = from_sysnode_id(sysnode.id());
+                from_dir_ino_and_attr_id(dir_ino, synthetic_attr.id())
+            }
+            InnerNode::AttrGroup(group_index, sysnode) => {
+                let dir_ino = from_sysnode_id(sysnode.id());
+                from_dir_ino_and_attr_id(dir_ino, group_id(*group_index))
+            }
             InnerNode::Attr(name, sysnode) => {
                 let dir_ino = from_sysnode_id(sysnode.id());
                 let attr_id = sysnode
-                    .attr_set()
+                    .node_attrs()
                     .iter()
                     .find(|attr| attr.name() == name)
                     .map(|attr| attr.id())
@@ -650,3 +1849,915 @@ mod ino {
         };
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::{
+        prelude::ktest,
+        task::{Task, TaskOptions},
+    };
+    use systree::{
+        test_util::{EventRecorder, FixtureBranch, FixtureLeaf, FixtureSymlink, TreeBuilder},
+        utils::{SysLeafNode, SysStoredValue},
+        SysAttrGroup, SysAttrId, SysAttrSet, SysAttrSetBuilder, SysEventAction, SysEventKv,
+        SysEventSelector, SysNodeId, SysNodeType,
+    };
+
+    use super::*;
+    use crate::fs::test::{lookup, new_fs, read_to_string, write_str};
+
+    /// Adds a leaf node to the branching node of the given path in the `SysTree`.
+    fn add_leaf(fs: &SysFs, parent_path: &str, leaf: Arc<SysLeafNode>) {
+        let parent = fs.systree().lookup(parent_path).unwrap().arc_as_branch().unwrap();
+        leaf.set_parent(Arc::downgrade(&parent));
+        fs.systree().add_child(parent.as_ref(), leaf).unwrap();
+    }
+
+    /// A visitor that records the names of at most `capacity` dentries,
+    /// as a buffer of `getdents` does.
+    struct DentryRecorder {
+        names: Vec<String>,
+        inos: Vec<u64>,
+        capacity: usize,
+    }
+
+    impl DentryRecorder {
+        fn new(capacity: usize) -> Self {
+            Self {
+                names: Vec::new(),
+                inos: Vec::new(),
+                capacity,
+            }
+        }
+    }
+
+    impl DirentVisitor for DentryRecorder {
+        fn visit(&mut self, name: &str, ino: u64, _type_: InodeType, _offset: usize) -> Result<()> {
+            if self.names.len() >= self.capacity {
+                return Err(Error::new(Errno::EINVAL));
+            }
+            self.names.push(name.to_string());
+            self.inos.push(ino);
+            Ok(())
+        }
+    }
+
+    /// Publishes a `Change` event of an attribute of the node of the given path.
+    fn publish_attr_change(fs: &SysFs, path: &str, attr_name: &str) {
+        let node = fs.systree().lookup(path).unwrap().arc_as_node().unwrap();
+        let details = vec![SysEventKv {
+            key: "ATTR".into(),
+            value: attr_name.to_string().into(),
+        }];
+        fs.systree()
+            .publish_event(node.as_ref(), SysEventAction::Change, details);
+    }
+
+    #[ktest]
+    fn runtime_attr_is_listed_with_stable_ino() {
+        let builder = TreeBuilder::new().add("/devices/eth0", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let eth0 = lookup(&fs, "/devices/eth0").unwrap();
+        // Cache the dentries before the attribute is added
+        let mut dentries = DentryRecorder::new(8);
+        eth0.readdir_at(0, &mut dentries).unwrap();
+        assert!(!dentries.names.iter().any(|name| name == "carrier"));
+        assert_eq!(eth0.lookup("carrier").unwrap_err().error(), Errno::ENOENT);
+
+        let recorder = EventRecorder::new();
+        fs.systree()
+            .register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+        let sysnode = fs.systree().lookup_node("/devices/eth0").unwrap();
+        let carrier = SysAttr::new(0, "carrier".into(), SysAttrFlags::CAN_READ);
+        fs.systree().add_attr(sysnode.as_ref(), carrier).unwrap();
+        let events = recorder.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action(), SysEventAction::Change);
+        assert_eq!(events[0].path(), "/devices/eth0");
+
+        let list_inos = || {
+            let mut dentries = DentryRecorder::new(8);
+            eth0.readdir_at(0, &mut dentries).unwrap();
+            dentries.names.into_iter().zip(dentries.inos).collect::<Vec<_>>()
+        };
+        let dentries = list_inos();
+        let ino_of = |name: &str| dentries.iter().find(|(n, _)| n == name).unwrap().1;
+        let carrier_ino = ino_of("carrier");
+        assert_ne!(carrier_ino, ino_of("mtu"));
+        assert_eq!(eth0.lookup("carrier").unwrap().ino(), carrier_ino);
+        // The ino is stable across listings and lookups
+        assert_eq!(list_inos(), dentries);
+        assert_eq!(eth0.lookup("carrier").unwrap().ino(), carrier_ino);
+
+        let carrier = SysAttr::new(0, "carrier".into(), SysAttrFlags::CAN_READ);
+        let res = fs.systree().add_attr(sysnode.as_ref(), carrier);
+        assert_eq!(res, Err(systree::Error::AlreadyExists));
+    }
+
+    #[ktest]
+    fn readdir_with_too_small_buffers() {
+        let builder = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &[])
+            .add("/devices/eth1", SysNodeType::Leaf, &[]);
+        let fs = new_fs(builder, "/");
+        let devices = lookup(&fs, "/devices").unwrap();
+        let mut all_dentries = DentryRecorder::new(16);
+        devices.readdir_at(0, &mut all_dentries).unwrap();
+        assert_eq!(all_dentries.names, ["eth0", "eth1", ".", ".."]);
+
+        // A buffer too small for the first entry is an error for the caller to enlarge it
+        let mut dentries = DentryRecorder::new(0);
+        let err = devices.readdir_at(0, &mut dentries).unwrap_err();
+        assert_eq!(err.error(), Errno::EINVAL);
+
+        // A buffer that fits one entry but not two resumes right after that entry
+        let mut offset = 0;
+        let mut names = Vec::new();
+        loop {
+            let mut dentries = DentryRecorder::new(1);
+            let increment = devices.readdir_at(offset, &mut dentries).unwrap();
+            if increment == 0 {
+                assert!(dentries.names.is_empty());
+                break;
+            }
+            assert_eq!(dentries.names.len(), 1);
+            names.extend(dentries.names);
+            offset += increment;
+        }
+        assert_eq!(names, all_dentries.names);
+    }
+
+    #[ktest]
+    fn attr_group_dirs_are_listed_with_stable_inos() {
+        let fs = new_fs(TreeBuilder::new().add("/devices", SysNodeType::Branch, &[]), "/");
+        let branch = fs.systree().lookup("/devices").unwrap().arc_as_branch().unwrap();
+        let statistics = SysAttrGroup::new("statistics".into(), vec!["rx_bytes".into(), "tx_bytes".into()]);
+        let eth0 = FixtureLeaf::new_with_groups(
+            "eth0".into(),
+            &["mtu", "address", "rx_bytes", "tx_bytes"],
+            vec![statistics],
+            Arc::downgrade(&branch),
+        );
+        fs.systree().add_child(branch.as_ref(), eth0).unwrap();
+        let eth0 = lookup(&fs, "/devices/eth0").unwrap();
+        let eth0_inode = eth0.downcast_ref::<SysFsInode>().unwrap();
+
+        let list_paginated = || {
+            let mut dentries = Vec::new();
+            let mut cursor = Some(DirCursor::START);
+            while let Some(current_cursor) = cursor {
+                let mut recorder = DentryRecorder::new(2);
+                cursor = eth0_inode
+                    .readdir_with_cursor(current_cursor, &mut recorder)
+                    .unwrap();
+                dentries.extend(recorder.names.into_iter().zip(recorder.inos));
+            }
+            dentries
+        };
+        let dentries = list_paginated();
+        let names: Vec<&str> = dentries.iter().map(|(name, _)| name.as_str()).collect();
+        for name in ["mtu", "address", "statistics", ".", ".."] {
+            assert_eq!(names.iter().filter(|n| **n == name).count(), 1);
+        }
+        // The grouped attributes are shown in the dir of the group only
+        assert!(!names.contains(&"rx_bytes"));
+        assert!(!names.contains(&"tx_bytes"));
+
+        // The inos of the group dir and the attributes do not collide
+        let group_dir = eth0.lookup("statistics").unwrap();
+        let rx_bytes = group_dir.lookup("rx_bytes").unwrap();
+        let mut inos: Vec<u64> = dentries
+            .iter()
+            .filter(|(name, _)| name != "." && name != "..")
+            .map(|(_, ino)| *ino)
+            .chain([eth0.ino(), rx_bytes.ino(), group_dir.lookup("tx_bytes").unwrap().ino()])
+            .collect();
+        let num_inos = inos.len();
+        inos.sort();
+        inos.dedup();
+        assert_eq!(inos.len(), num_inos);
+
+        // The inos are stable across the listings and lookups
+        let (_, group_ino) = dentries.iter().find(|(name, _)| name == "statistics").unwrap();
+        assert_eq!(*group_ino, group_dir.ino());
+        assert_eq!(list_paginated(), dentries);
+        assert_eq!(eth0.lookup("statistics").unwrap().ino(), group_dir.ino());
+        assert_eq!(eth0.metadata().nlink, 3);
+    }
+
+    #[ktest]
+    fn xattr_operations_are_not_supported() {
+        let builder = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &["mtu"])
+            .add_symlink("/class/net/eth0", "/devices/eth0");
+        let fs = new_fs(builder, "/");
+
+        for path in ["/devices", "/devices/eth0", "/devices/eth0/mtu", "/class/net/eth0"] {
+            let inode = lookup(&fs, path).unwrap();
+            let name = || XattrName::try_from_full_name("user.comment").unwrap();
+
+            let mut buf = vec![0u8; 64];
+            let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+            let res = inode.get_xattr(name(), &mut writer);
+            assert_eq!(res.unwrap_err().error(), Errno::ENODATA);
+
+            let mut reader = VmReader::from(b"hello".as_slice()).to_fallible();
+            let res = inode.set_xattr(name(), &mut reader, XattrSetFlags::empty());
+            assert_eq!(res.unwrap_err().error(), Errno::EOPNOTSUPP);
+
+            let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+            let res = inode.list_xattr(XattrNamespace::User, &mut writer);
+            assert_eq!(res.unwrap_err().error(), Errno::EOPNOTSUPP);
+
+            let res = inode.remove_xattr(name());
+            assert_eq!(res.unwrap_err().error(), Errno::EOPNOTSUPP);
+        }
+    }
+
+    #[ktest]
+    fn writes_to_append_only_attr_accumulate() {
+        let fs = new_fs(TreeBuilder::new().add("/devices", SysNodeType::Branch, &[]), "/");
+        let mut builder = SysAttrSetBuilder::new();
+        let flags = SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE | SysAttrFlags::IS_APPEND_ONLY;
+        builder.add("log".into(), flags);
+        let leaf = SysLeafNode::new("audit".into(), builder.build());
+        leaf.set_handler("log", Arc::new(SysStoredValue::with_max_len(b"", 32)))
+            .unwrap();
+        add_leaf(&fs, "/devices", leaf);
+
+        let log = lookup(&fs, "/devices/audit/log").unwrap();
+        write_str(log.as_ref(), "login root\n").unwrap();
+        write_str(log.as_ref(), "logout root\n").unwrap();
+        assert_eq!(read_to_string(log.as_ref()).unwrap(), "login root\nlogout root\n");
+
+        // An append beyond the maximum size is rejected as a whole
+        let res = write_str(log.as_ref(), "login admin\n");
+        assert_eq!(res.unwrap_err().error(), Errno::EFBIG);
+        assert_eq!(read_to_string(log.as_ref()).unwrap(), "login root\nlogout root\n");
+    }
+
+    #[ktest]
+    fn oversized_write_to_stored_value_fails_with_efbig() {
+        let fs = new_fs(TreeBuilder::new().add("/devices", SysNodeType::Branch, &[]), "/");
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add("alias".into(), SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE);
+        let leaf = SysLeafNode::new("eth0".into(), builder.build());
+        let alias = SysStoredValue::with_max_len(b"", 8);
+        leaf.set_handler("alias", Arc::new(alias)).unwrap();
+        add_leaf(&fs, "/devices", leaf);
+
+        let alias = lookup(&fs, "/devices/eth0/alias").unwrap();
+        assert_eq!(write_str(alias.as_ref(), "uplink-0").unwrap(), 8);
+        let res = write_str(alias.as_ref(), "uplink-01");
+        assert_eq!(res.unwrap_err().error(), Errno::EFBIG);
+        assert_eq!(read_to_string(alias.as_ref()).unwrap(), "uplink-0");
+    }
+
+    #[ktest]
+    fn internal_nodes_are_invisible() {
+        let builder = TreeBuilder::new().add("/devices/eth0", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let devices = lookup(&fs, "/devices").unwrap();
+        let old_nlink = devices.metadata().nlink;
+
+        let branch = fs.systree().lookup("/devices").unwrap().arc_as_branch().unwrap();
+        let bookkeeping = FixtureLeaf::new_internal("bookkeeping".into(), &[], Arc::downgrade(&branch));
+        fs.systree().add_child(branch.as_ref(), bookkeeping).unwrap();
+
+        assert_eq!(devices.lookup("bookkeeping").unwrap_err().error(), Errno::ENOENT);
+        let mut dentries = DentryRecorder::new(8);
+        devices.readdir_at(0, &mut dentries).unwrap();
+        assert!(dentries.names.iter().any(|name| name == "eth0"));
+        assert!(!dentries.names.iter().any(|name| name == "bookkeeping"));
+        assert_eq!(devices.metadata().nlink, old_nlink);
+
+        // The node is still a part of the tree
+        let sysnode = fs.systree().lookup("/devices/bookkeeping").unwrap();
+        assert_eq!(sysnode.path(), "/devices/bookkeeping");
+    }
+
+    #[ktest]
+    fn removed_node_is_not_found_via_cached_parent() {
+        let builder = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &["mtu"])
+            .add("/devices/eth1", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let devices = lookup(&fs, "/devices").unwrap();
+        let eth0 = devices.lookup("eth0").unwrap();
+        devices.lookup("eth1").unwrap();
+
+        let branch = fs.systree().lookup("/devices").unwrap().arc_as_branch().unwrap();
+        fs.systree().remove_child(branch.as_ref(), "eth0").unwrap();
+        assert_eq!(devices.lookup("eth0").unwrap_err().error(), Errno::ENOENT);
+        devices.lookup("eth1").unwrap();
+        let mut dentries = DentryRecorder::new(8);
+        devices.readdir_at(0, &mut dentries).unwrap();
+        assert!(!dentries.names.iter().any(|name| name == "eth0"));
+
+        // The inode held before the removal is not found again once the node is back
+        let new_eth0 = FixtureLeaf::new("eth0".into(), &["mtu"], Arc::downgrade(&branch));
+        fs.systree().add_child(branch.as_ref(), new_eth0).unwrap();
+        let found_eth0 = devices.lookup("eth0").unwrap();
+        assert!(!Arc::ptr_eq(&eth0, &found_eth0));
+        assert_ne!(eth0.ino(), found_eth0.ino());
+    }
+
+    #[ktest]
+    fn dir_nlink_follows_child_dirs() {
+        let builder = TreeBuilder::new().add("/devices/pci0", SysNodeType::Branch, &[]);
+        let fs = new_fs(builder, "/");
+        let devices = lookup(&fs, "/devices").unwrap();
+        assert_eq!(devices.metadata().nlink, 3);
+
+        let branch = fs.systree().lookup("/devices").unwrap().arc_as_branch().unwrap();
+        let usb0 = FixtureBranch::new("usb0".into(), &[], Arc::downgrade(&branch));
+        fs.systree().add_child(branch.as_ref(), usb0).unwrap();
+        assert_eq!(devices.metadata().nlink, 4);
+        // A symlink is not a dir
+        let link = FixtureSymlink::new("lo".into(), "pci0".to_string(), Arc::downgrade(&branch));
+        fs.systree().add_child(branch.as_ref(), link).unwrap();
+        assert_eq!(devices.metadata().nlink, 4);
+
+        fs.systree().remove_child(branch.as_ref(), "pci0").unwrap();
+        assert_eq!(devices.metadata().nlink, 3);
+    }
+
+    #[ktest]
+    fn repeated_lookups_return_same_inode() {
+        let builder = TreeBuilder::new()
+            .add("/devices/pci0", SysNodeType::Branch, &[])
+            .add("/devices/eth0", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let devices = lookup(&fs, "/devices").unwrap();
+
+        for name in ["pci0", "eth0"] {
+            let first = devices.lookup(name).unwrap();
+            let second = devices.lookup(name).unwrap();
+            assert!(Arc::ptr_eq(&first, &second));
+        }
+
+        // The cache is invalidated once the children change
+        let eth0 = devices.lookup("eth0").unwrap();
+        let branch = fs.systree().lookup("/devices").unwrap().arc_as_branch().unwrap();
+        let lo = FixtureLeaf::new("lo".into(), &[], Arc::downgrade(&branch));
+        fs.systree().add_child(branch.as_ref(), lo).unwrap();
+        let new_eth0 = devices.lookup("eth0").unwrap();
+        assert!(!Arc::ptr_eq(&eth0, &new_eth0));
+        assert_eq!(eth0.ino(), new_eth0.ino());
+        assert!(Arc::ptr_eq(&new_eth0, &devices.lookup("eth0").unwrap()));
+
+        // The cache does not keep the inodes alive
+        let weak_eth0 = Arc::downgrade(&new_eth0);
+        drop(eth0);
+        drop(new_eth0);
+        assert!(weak_eth0.upgrade().is_none());
+    }
+
+    #[ktest]
+    fn replaced_attr_set_is_listed_in_same_dir() {
+        let fs = new_fs(TreeBuilder::new().add("/devices", SysNodeType::Branch, &[]), "/");
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add("mtu".into(), SysAttrFlags::CAN_READ);
+        add_leaf(&fs, "/devices", SysLeafNode::new("eth0".into(), builder.build()));
+        let recorder = EventRecorder::new();
+        fs.systree()
+            .register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+
+        let eth0 = lookup(&fs, "/devices/eth0").unwrap();
+        let list_names = |dir: &Arc<dyn Inode>| {
+            let mut dentries = DentryRecorder::new(64);
+            dir.readdir_at(0, &mut dentries).unwrap();
+            dentries.names
+        };
+        let names = list_names(&eth0);
+        assert!(names.iter().any(|name| name == "mtu"));
+
+        let mut builder = SysAttrSetBuilder::new();
+        builder
+            .add("speed".into(), SysAttrFlags::CAN_READ)
+            .add("duplex".into(), SysAttrFlags::CAN_READ);
+        let sysnode = fs.systree().lookup_node("/devices/eth0").unwrap();
+        fs.systree()
+            .replace_node_attrs(sysnode.as_ref(), builder.build())
+            .unwrap();
+
+        // The next listing of the same inode reflects the new attribute set
+        let names = list_names(&eth0);
+        assert!(!names.iter().any(|name| name == "mtu"));
+        assert!(names.iter().any(|name| name == "speed"));
+        assert!(names.iter().any(|name| name == "duplex"));
+        assert_eq!(eth0.lookup("mtu").unwrap_err().error(), Errno::ENOENT);
+        eth0.lookup("speed").unwrap();
+
+        let new_eth0 = lookup(&fs, "/devices/eth0").unwrap();
+        assert_eq!(new_eth0.ino(), eth0.ino());
+        let events = recorder.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action(), SysEventAction::Change);
+        assert_eq!(events[0].path(), "/devices/eth0");
+    }
+
+    #[ktest]
+    fn metadata_reports_consistent_blocks() {
+        let builder = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &["mtu"])
+            .add_symlink("/class/net/eth0", "/devices/eth0");
+        let fs = new_fs(builder, "/");
+        for path in ["/", "/devices/eth0", "/class/net/eth0"] {
+            let metadata = lookup(&fs, path).unwrap().metadata();
+            assert_eq!(metadata.blk_size, BLOCK_SIZE);
+            // Dirs and symlinks have no sizes, which take no blocks
+            assert_eq!(metadata.size, 0);
+            assert_eq!(metadata.blocks, 0);
+        }
+
+        // The sizes of stored values are exact and follow the writes
+        let mut attrs = SysAttrSetBuilder::new();
+        attrs.add("alias".into(), SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE);
+        attrs.add("stat".into(), SysAttrFlags::CAN_READ);
+        let leaf = SysLeafNode::new("eth1".into(), attrs.build());
+        let alias = SysStoredValue::new(&[b'a'; 2 * BLOCK_SIZE + 1]);
+        leaf.set_handler("alias", Arc::new(alias)).unwrap();
+        let stat = |_offset: usize, _writer: &mut VmWriter<Fallible>| -> systree::Result<usize> { Ok(0) };
+        leaf.set_handler("stat", Arc::new(stat)).unwrap();
+        add_leaf(&fs, "/devices", leaf);
+
+        let alias = lookup(&fs, "/devices/eth1/alias").unwrap();
+        let metadata = alias.metadata();
+        assert_eq!(metadata.blk_size, BLOCK_SIZE);
+        assert_eq!(metadata.size, 2 * BLOCK_SIZE + 1);
+        assert_eq!(metadata.blocks, 3);
+        write_str(alias.as_ref(), "uplink").unwrap();
+        assert_eq!(alias.metadata().size, 6);
+        assert_eq!(alias.metadata().blocks, 1);
+
+        // Those of computed values are estimated as one page
+        let metadata = lookup(&fs, "/devices/eth1/stat").unwrap().metadata();
+        assert_eq!(metadata.size, PAGE_SIZE);
+        assert_eq!(metadata.blocks, PAGE_SIZE / BLOCK_SIZE);
+    }
+
+    #[ktest]
+    fn dir_permissions_are_enforced_when_enabled() {
+        let builder = TreeBuilder::new().add("/devices/eth0", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let devices = lookup(&fs, "/devices").unwrap();
+        devices.set_mode(InodeMode::from_bits_truncate(0o000)).unwrap();
+
+        // The permissions are not enforced by default
+        assert!(!fs.options().dir_permissions());
+        devices.lookup("eth0").unwrap();
+        devices.readdir_at(0, &mut DentryRecorder::new(8)).unwrap();
+
+        fs.options().set_dir_permissions(true);
+        assert_eq!(devices.lookup("eth0").unwrap_err().error(), Errno::EACCES);
+        let res = devices.readdir_at(0, &mut DentryRecorder::new(8));
+        assert_eq!(res.unwrap_err().error(), Errno::EACCES);
+
+        // Searching a dir does not require reading it, and vice versa
+        devices.set_mode(InodeMode::from_bits_truncate(0o100)).unwrap();
+        devices.lookup("eth0").unwrap();
+        let res = devices.readdir_at(0, &mut DentryRecorder::new(8));
+        assert_eq!(res.unwrap_err().error(), Errno::EACCES);
+        devices.set_mode(InodeMode::from_bits_truncate(0o400)).unwrap();
+        assert_eq!(devices.lookup("eth0").unwrap_err().error(), Errno::EACCES);
+        devices.readdir_at(0, &mut DentryRecorder::new(8)).unwrap();
+    }
+
+    #[ktest]
+    fn streamed_attr_is_committed_on_close() {
+        let fs = new_fs(TreeBuilder::new().add("/firmware", SysNodeType::Branch, &[]), "/");
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add(
+            "config".into(),
+            SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE | SysAttrFlags::IS_STREAMED,
+        );
+        let leaf = SysLeafNode::new("dev0".into(), builder.build());
+        leaf.set_handler("config", Arc::new(SysStreamedValue::new(b"old")))
+            .unwrap();
+        add_leaf(&fs, "/firmware", leaf);
+        let config = lookup(&fs, "/firmware/dev0/config").unwrap();
+        let read_config = || {
+            let mut buf = vec![0u8; 16 * 1024];
+            let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+            let read_len = config.read_at(0, &mut writer).unwrap();
+            buf.truncate(read_len);
+            buf
+        };
+
+        let new_bytes: Vec<u8> = (0..10 * 1024).map(|i| (i % 251) as u8).collect();
+        let file = config
+            .open(AccessMode::O_WRONLY, StatusFlags::empty())
+            .unwrap()
+            .unwrap();
+        // The chunks are not page-aligned at the end, nor are they committed early
+        for chunk in new_bytes.chunks(4096) {
+            let mut reader = VmReader::from(chunk).to_fallible();
+            assert_eq!(file.write(&mut reader).unwrap(), chunk.len());
+            assert_eq!(read_config(), b"old");
+        }
+        drop(file);
+        assert_eq!(read_config(), new_bytes);
+
+        // A value whose length is a multiple of the page size is committed as well
+        let file = config
+            .open(AccessMode::O_WRONLY, StatusFlags::empty())
+            .unwrap()
+            .unwrap();
+        let mut reader = VmReader::from(&new_bytes[..8192]).to_fallible();
+        file.write(&mut reader).unwrap();
+        drop(file);
+        assert_eq!(read_config(), &new_bytes[..8192]);
+    }
+
+    #[ktest]
+    fn attrs_are_only_resized_to_zero() {
+        let builder = TreeBuilder::new().add("/devices/eth0", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let mtu = lookup(&fs, "/devices/eth0/mtu").unwrap();
+        write_str(mtu.as_ref(), "1500").unwrap();
+
+        // As is done by opening the attribute with `O_TRUNC`
+        mtu.resize(0).unwrap();
+        assert_eq!(read_to_string(mtu.as_ref()).unwrap(), "1500");
+        write_str(mtu.as_ref(), "9000").unwrap();
+        assert_eq!(read_to_string(mtu.as_ref()).unwrap(), "9000");
+
+        assert_eq!(mtu.resize(100).unwrap_err().error(), Errno::EPERM);
+        let eth0 = lookup(&fs, "/devices/eth0").unwrap();
+        assert_eq!(eth0.resize(0).unwrap_err().error(), Errno::EPERM);
+    }
+
+    #[ktest]
+    fn poll_is_ready_once_per_change() {
+        let builder = TreeBuilder::new().add("/gpio/gpio0", SysNodeType::Leaf, &["value", "edge"]);
+        let fs = new_fs(builder, "/");
+        let value = lookup(&fs, "/gpio/gpio0/value").unwrap();
+
+        // The first poll is ready immediately
+        assert_eq!(value.poll(IoEvents::PRI, None), IoEvents::PRI);
+        read_to_string(value.as_ref()).unwrap();
+        // Not until the next change
+        assert_eq!(value.poll(IoEvents::PRI, None), IoEvents::empty());
+        // Nor is it ready on the changes of the other attributes
+        publish_attr_change(&fs, "/gpio/gpio0", "edge");
+        assert_eq!(value.poll(IoEvents::PRI, None), IoEvents::empty());
+
+        // A change from another task makes the waiting poller ready
+        let changer = {
+            let fs = fs.clone();
+            TaskOptions::new(move || publish_attr_change(&fs, "/gpio/gpio0", "value"))
+                .data(())
+                .spawn()
+                .unwrap()
+        };
+        while value.poll(IoEvents::PRI, None).is_empty() {
+            Task::yield_now();
+        }
+        drop(changer);
+        read_to_string(value.as_ref()).unwrap();
+        assert_eq!(value.poll(IoEvents::PRI, None), IoEvents::empty());
+    }
+
+    #[ktest]
+    fn poll_follows_moved_node() {
+        let builder = TreeBuilder::new()
+            .add("/gpio/gpio0", SysNodeType::Leaf, &["value"])
+            .add("/gpio/chip1", SysNodeType::Branch, &[]);
+        let fs = new_fs(builder, "/");
+        let value = lookup(&fs, "/gpio/gpio0/value").unwrap();
+        read_to_string(value.as_ref()).unwrap();
+        assert_eq!(value.poll(IoEvents::PRI, None), IoEvents::empty());
+
+        // The watch is not tied to the path at which it was created
+        fs.systree().move_node("/gpio/gpio0", "/gpio/chip1/gpio0").unwrap();
+        publish_attr_change(&fs, "/gpio/chip1/gpio0", "value");
+        assert_eq!(value.poll(IoEvents::PRI, None), IoEvents::PRI);
+        read_to_string(value.as_ref()).unwrap();
+
+        fs.systree().move_node("/gpio/chip1/gpio0", "/gpio/chip1/line0").unwrap();
+        publish_attr_change(&fs, "/gpio/chip1/line0", "value");
+        assert_eq!(value.poll(IoEvents::PRI, None), IoEvents::PRI);
+    }
+
+    #[ktest]
+    fn readdir_with_cursor_paginates_under_insertions() {
+        let builder = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &[])
+            .add("/devices/eth1", SysNodeType::Leaf, &[])
+            .add("/devices/eth2", SysNodeType::Leaf, &[])
+            .add("/devices/eth3", SysNodeType::Leaf, &[]);
+        let fs = new_fs(builder, "/");
+        let devices = lookup(&fs, "/devices").unwrap();
+        let devices_inode = devices.downcast_ref::<SysFsInode>().unwrap();
+        let branch = fs.systree().lookup("/devices").unwrap().arc_as_branch().unwrap();
+
+        let mut names = Vec::new();
+        let mut cursor = Some(DirCursor::START);
+        let mut num_pages = 0;
+        while let Some(current_cursor) = cursor {
+            let mut recorder = DentryRecorder::new(2);
+            cursor = devices_inode
+                .readdir_with_cursor(current_cursor, &mut recorder)
+                .unwrap();
+            names.extend(recorder.names);
+            num_pages += 1;
+
+            if num_pages == 1 {
+                // Insert a child between the pages
+                let eth9 = FixtureLeaf::new("eth9".into(), &[], Arc::downgrade(&branch));
+                fs.systree().add_child(branch.as_ref(), eth9).unwrap();
+            }
+        }
+
+        // No entry is reported twice, and no existing one is missed
+        let mut unique_names = names.clone();
+        unique_names.sort();
+        unique_names.dedup();
+        assert_eq!(unique_names.len(), names.len());
+        for name in [".", "..", "eth0", "eth1", "eth2", "eth3"] {
+            assert!(names.iter().any(|n| n == name));
+        }
+        assert!(num_pages >= 3);
+    }
+
+    #[ktest]
+    fn missing_names_are_cached_until_dir_changes() {
+        let builder = TreeBuilder::new().add("/devices/eth0", SysNodeType::Leaf, &[]);
+        let fs = new_fs(builder, "/");
+        fs.options().set_negative_lookup_cache(true);
+        let devices = lookup(&fs, "/devices").unwrap();
+        let devices_inode = devices.downcast_ref::<SysFsInode>().unwrap();
+        let is_cached = |name: &str| {
+            let dir_state = devices_inode.dir_state().unwrap();
+            devices_inode.negative_cache.lock().contains(name, &dir_state)
+        };
+
+        assert_eq!(devices.lookup("eth1").unwrap_err().error(), Errno::ENOENT);
+        assert!(is_cached("eth1"));
+        // The repeated lookup is answered by the cache
+        assert_eq!(devices.lookup("eth1").unwrap_err().error(), Errno::ENOENT);
+        assert!(is_cached("eth1"));
+        // Existing names are never cached
+        devices.lookup("eth0").unwrap();
+        assert!(!is_cached("eth0"));
+
+        // Adding the name invalidates the cache
+        let branch = fs.systree().lookup("/devices").unwrap().arc_as_branch().unwrap();
+        let eth1 = FixtureLeaf::new("eth1".into(), &[], Arc::downgrade(&branch));
+        fs.systree().add_child(branch.as_ref(), eth1).unwrap();
+        assert!(!is_cached("eth1"));
+        devices.lookup("eth1").unwrap();
+    }
+
+    #[ktest]
+    fn chmod_is_rejected_with_immutable_modes() {
+        let builder = TreeBuilder::new().add("/devices/eth0", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let mtu = lookup(&fs, "/devices/eth0/mtu").unwrap();
+        let old_mode = mtu.mode().unwrap();
+
+        // The modes are mutable by default
+        assert!(!fs.options().immutable_modes());
+        mtu.set_mode(InodeMode::S_IRUSR).unwrap();
+        assert_eq!(mtu.mode().unwrap(), InodeMode::S_IRUSR);
+
+        fs.options().set_immutable_modes(true);
+        let res = mtu.set_mode(old_mode);
+        assert_eq!(res.unwrap_err().error(), Errno::EPERM);
+        assert_eq!(mtu.mode().unwrap(), InodeMode::S_IRUSR);
+    }
+
+    #[ktest]
+    fn chmod_of_attr_publishes_change_event() {
+        let builder = TreeBuilder::new().add("/devices/eth0", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let mtu = lookup(&fs, "/devices/eth0/mtu").unwrap();
+        let recorder = EventRecorder::new();
+        fs.systree().register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+
+        // No events are published by default
+        mtu.set_mode(InodeMode::S_IRUSR | InodeMode::S_IWUSR).unwrap();
+        assert!(recorder.events().is_empty());
+
+        fs.options().set_chmod_events(true);
+        mtu.set_mode(InodeMode::S_IRUSR).unwrap();
+        let events = recorder.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action(), SysEventAction::Change);
+        assert_eq!(events[0].path(), "/devices/eth0");
+        let details: Vec<(&str, &str)> = events[0]
+            .details()
+            .iter()
+            .map(|kv| (kv.key.as_ref(), kv.value.as_ref()))
+            .collect();
+        assert_eq!(details, [("ATTR", "mtu"), ("MODE", "400")]);
+
+        // Chmod-ing a dir publishes no events
+        let eth0 = lookup(&fs, "/devices/eth0").unwrap();
+        eth0.set_mode(InodeMode::from_bits_truncate(0o755)).unwrap();
+        assert_eq!(recorder.events().len(), 1);
+    }
+
+    #[ktest]
+    fn debug_attrs_show_paths_and_names() {
+        let builder = TreeBuilder::new()
+            .add("/devices/pci0/eth0", SysNodeType::Leaf, &["mtu"])
+            .add("/devices/pci0/eth1", SysNodeType::Leaf, &["__sysfs_name"]);
+        let fs = new_fs(builder, "/");
+        fs.options().set_debug_attrs(true);
+
+        let eth0 = fs.systree().lookup("/devices/pci0/eth0").unwrap();
+        let path_attr = lookup(&fs, "/devices/pci0/eth0/__sysfs_path").unwrap();
+        assert_eq!(read_to_string(path_attr.as_ref()).unwrap(), format!("{}\n", eth0.path()));
+        let name_attr = lookup(&fs, "/devices/pci0/eth0/__sysfs_name").unwrap();
+        assert_eq!(read_to_string(name_attr.as_ref()).unwrap(), "eth0\n");
+        // The synthetic attributes are read-only
+        assert_eq!(write_str(path_attr.as_ref(), "/foo").unwrap_err().error(), Errno::EACCES);
+
+        // A real attribute with the same name takes precedence
+        let name_attr = lookup(&fs, "/devices/pci0/eth1/__sysfs_name").unwrap();
+        write_str(name_attr.as_ref(), "real\n").unwrap();
+        assert_eq!(read_to_string(name_attr.as_ref()).unwrap(), "real\n");
+
+        fs.options().set_debug_attrs(false);
+        let res = lookup(&fs, "/devices/pci0/eth0/__sysfs_path");
+        assert_eq!(res.unwrap_err().error(), Errno::ENOENT);
+    }
+
+    #[ktest]
+    fn attr_inos_are_within_ranges_of_nodes() {
+        let (node_id, next_node_id) = (SysNodeId::new(), SysNodeId::new());
+        let dir_ino = ino::from_sysnode_id(&node_id);
+        let next_dir_ino = ino::from_sysnode_id(&next_node_id);
+
+        let largest_attr_id = (SysAttrSet::CAPACITY - 1) as SysAttrId;
+        assert_eq!(largest_attr_id, ino::SUBSYSTEM_LINK_ID);
+        let largest_ino = ino::from_dir_ino_and_attr_id(dir_ino, largest_attr_id);
+        assert!(largest_ino < next_dir_ino);
+        // The node ID and the attribute ID can be decoded from the inode number
+        assert_eq!(largest_ino >> SysAttrSet::ID_BITS, node_id.as_u64());
+        assert_eq!(largest_ino & (SysAttrSet::CAPACITY as Ino - 1), largest_attr_id as Ino);
+    }
+
+    #[ktest]
+    fn devices_of_subsystems_have_subsystem_links() {
+        let builder = TreeBuilder::new()
+            .add("/net/eth0/queues", SysNodeType::Branch, &[])
+            .add("/devices/eth0", SysNodeType::Leaf, &[]);
+        let fs = new_fs(builder, "/");
+        fs.systree()
+            .register_subsystem("net".into(), Arc::new(SysAttrSet::new_empty()))
+            .unwrap();
+
+        // The symlinks are exposed only if the option is enabled
+        assert_eq!(lookup(&fs, "/net/eth0/subsystem").unwrap_err().error(), Errno::ENOENT);
+        fs.options().set_subsystem_links(true);
+
+        let link = lookup(&fs, "/net/eth0/subsystem").unwrap();
+        assert_eq!(link.type_(), InodeType::SymLink);
+        assert_eq!(link.read_link().unwrap(), "..");
+        let link = lookup(&fs, "/net/eth0/queues/subsystem").unwrap();
+        assert_eq!(link.read_link().unwrap(), "../..");
+
+        // Resolving the symlink reaches the dir of the subsystem
+        let net = lookup(&fs, "/net").unwrap();
+        let resolved = lookup(&fs, "/net/eth0/queues/../..").unwrap();
+        assert_eq!(resolved.ino(), net.ino());
+
+        // Neither the subsystem itself nor the nodes out of subsystems have the symlinks
+        assert_eq!(lookup(&fs, "/net/subsystem").unwrap_err().error(), Errno::ENOENT);
+        assert_eq!(lookup(&fs, "/devices/eth0/subsystem").unwrap_err().error(), Errno::ENOENT);
+    }
+
+    #[ktest]
+    fn dir_nlink_counts_child_dirs() {
+        let builder = TreeBuilder::new()
+            .add("/class/empty", SysNodeType::Branch, &[])
+            .add("/devices/pci0", SysNodeType::Branch, &[])
+            .add("/devices/eth0", SysNodeType::Leaf, &["mtu"])
+            .add_symlink("/devices/lo", "eth0");
+        let fs = new_fs(builder, "/");
+
+        let empty = lookup(&fs, "/class/empty").unwrap();
+        assert_eq!(empty.metadata().nlink, 2);
+        // The symlink and the attribute are not dirs
+        let devices = lookup(&fs, "/devices").unwrap();
+        assert_eq!(devices.metadata().nlink, 4);
+        let eth0 = lookup(&fs, "/devices/eth0").unwrap();
+        assert_eq!(eth0.metadata().nlink, 2);
+    }
+
+    #[ktest]
+    fn link_target_borrows_owned_target() {
+        let builder = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &["mtu"])
+            .add_symlink("/class/net/eth0", "../../devices/eth0");
+        let fs = new_fs(builder, "/");
+
+        let link = lookup(&fs, "/class/net/eth0").unwrap();
+        let link_inode = link.downcast_ref::<SysFsInode>().unwrap();
+        assert_eq!(link_inode.link_target().unwrap(), "../../devices/eth0");
+        assert_eq!(link_inode.link_target().unwrap(), link.read_link().unwrap());
+
+        // Only symlinks have targets
+        let mtu = lookup(&fs, "/devices/eth0/mtu").unwrap();
+        let mtu_inode = mtu.downcast_ref::<SysFsInode>().unwrap();
+        assert_eq!(mtu_inode.link_target().unwrap_err().error(), Errno::EINVAL);
+    }
+
+    #[ktest]
+    fn attr_flag_changes_update_inode_mode() {
+        let builder = TreeBuilder::new().add("/devices", SysNodeType::Branch, &[]);
+        let fs = new_fs(builder, "/");
+        let mut attr_builder = SysAttrSetBuilder::new();
+        attr_builder.add("power".into(), SysAttrFlags::CAN_READ);
+        let leaf = SysLeafNode::new("dev0".into(), attr_builder.build());
+        leaf.set_handler("power", Arc::new(SysStoredValue::new(b"on\n"))).unwrap();
+        add_leaf(&fs, "/devices", leaf.clone());
+
+        let power = lookup(&fs, "/devices/dev0/power").unwrap();
+        assert_eq!(power.mode().unwrap(), InodeMode::S_IRUSR);
+        assert_eq!(write_str(power.as_ref(), "off\n").unwrap_err().error(), Errno::EACCES);
+
+        let recorder = EventRecorder::new();
+        fs.systree().register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+        let new_flags = SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE;
+        fs.systree().set_attr_flags(leaf.as_ref(), "power", new_flags).unwrap();
+
+        let events = recorder.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action(), SysEventAction::Change);
+        assert_eq!(events[0].path(), "/devices/dev0");
+        let details: Vec<(&str, &str)> = events[0]
+            .details()
+            .iter()
+            .map(|kv| (kv.key.as_ref(), kv.value.as_ref()))
+            .collect();
+        let flags_value = format!("{:#x}", new_flags.bits());
+        assert_eq!(details, [("ATTR", "power"), ("ATTR_FLAGS", flags_value.as_str())]);
+
+        // The inode derives its mode from the new flags
+        assert_eq!(power.mode().unwrap(), InodeMode::S_IRUSR | InodeMode::S_IWUSR);
+        write_str(power.as_ref(), "off\n").unwrap();
+        assert_eq!(read_to_string(power.as_ref()).unwrap(), "off\n");
+    }
+
+    #[ktest]
+    fn read_errors_of_nodes_map_to_errnos() {
+        let builder = TreeBuilder::new().add("/devices", SysNodeType::Branch, &[]);
+        let fs = new_fs(builder, "/");
+
+        let mut attr_builder = SysAttrSetBuilder::new();
+        attr_builder.add("status".into(), SysAttrFlags::CAN_READ);
+        let leaf = SysLeafNode::new("dev0".into(), attr_builder.build());
+        let error = Arc::new(Mutex::new(systree::Error::Io));
+        let status = {
+            let error = error.clone();
+            move |_offset: usize, _writer: &mut VmWriter<Fallible>| -> systree::Result<usize> {
+                Err(*error.lock())
+            }
+        };
+        leaf.set_handler("status", Arc::new(status)).unwrap();
+        add_leaf(&fs, "/devices", leaf);
+
+        let status = lookup(&fs, "/devices/dev0/status").unwrap();
+        let expected = [
+            (systree::Error::NotFound, Errno::ENOENT),
+            (systree::Error::PermissionDenied, Errno::EACCES),
+            (systree::Error::InvalidArgument, Errno::EINVAL),
+            (systree::Error::NotSupported, Errno::EOPNOTSUPP),
+            (systree::Error::TryAgain, Errno::EAGAIN),
+            (systree::Error::TimedOut, Errno::ETIMEDOUT),
+            (systree::Error::BadAddress, Errno::EFAULT),
+            (systree::Error::Io, Errno::EIO),
+        ];
+        for (err, errno) in expected {
+            *error.lock() = err;
+            assert_eq!(read_to_string(status.as_ref()).unwrap_err().error(), errno);
+        }
+        // A failed read is not counted
+        assert_eq!(fs.stats().reads(), 0);
+    }
+
+    #[ktest]
+    fn stats_count_successful_reads_and_writes() {
+        let builder = TreeBuilder::new().add("/devices/eth0", SysNodeType::Leaf, &["mtu"]);
+        let fs = new_fs(builder, "/");
+        let mtu = lookup(&fs, "/devices/eth0/mtu").unwrap();
+
+        for i in 0..3 {
+            write_str(mtu.as_ref(), &format!("{}", 1500 + i)).unwrap();
+        }
+        for _ in 0..5 {
+            read_to_string(mtu.as_ref()).unwrap();
+        }
+        assert_eq!(fs.stats().writes(), 3);
+        assert_eq!(fs.stats().reads(), 5);
+
+        // Reading a dir is not counted
+        let eth0 = lookup(&fs, "/devices/eth0").unwrap();
+        assert!(read_to_string(eth0.as_ref()).is_err());
+        assert_eq!(fs.stats().reads(), 5);
+    }
+}
@@ -1,15 +1,75 @@
 mod inode;
 mod fs;
 
-pub use self::inode::SysFsInode;
-pub use self::fs::SysFs;
+pub use self::inode::{DirCursor, SysFsInode};
+pub use self::fs::{SysFs, SysFsOptions, SysFsStats};
 
 static SINGLETON: Once<Arc<SysFs>> = Once::new();
 
+/// Gets the singleton of sysfs.
+///
+/// # Panics
+///
+/// This function panics if sysfs has not been initialized with `init`.
 pub fn singleton() -> &'static Arc<SysFs> {
-    SINGLETON.get()
+    SINGLETON.get().expect("sysfs is not initialized")
 }
 
+/// Initializes the singleton of sysfs.
+///
+/// Calling this function again after sysfs has been initialized
+/// has no effect.
 pub fn init() {
-    todo!("init the FS singleton")
-}
\ No newline at end of file
+    SINGLETON.call_once(SysFs::new);
+}
+
+impl From<systree::Error> for Error {
+    fn from(err: systree::Error) -> Self {
+        // The errors reported by the nodes in a `SysTree` are mapped
+        // to the errnos that are closest in meaning.
+        // Any failure that cannot be classified by a node
+        // is expected to be reported as `systree::Error::Io`,
+        // which is mapped to `EIO`.
+        let errno = match err {
+            systree::Error::NotFound => Errno::ENOENT,
+            systree::Error::AlreadyExists => Errno::EEXIST,
+            systree::Error::PermissionDenied => Errno::EACCES,
+            systree::Error::InvalidArgument => Errno::EINVAL,
+            systree::Error::NotSupported => Errno::EOPNOTSUPP,
+            systree::Error::TryAgain => Errno::EAGAIN,
+            systree::Error::TimedOut => Errno::ETIMEDOUT,
+            systree::Error::BadAddress => Errno::EFAULT,
+            systree::Error::TooLarge => Errno::EFBIG,
+            systree::Error::NoSpace => Errno::ENOSPC,
+            systree::Error::Io => Errno::EIO,
+        };
+        Error::new(errno)
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+
+    #[ktest]
+    fn systree_errors_map_to_errnos() {
+        let expected = [
+            (systree::Error::NotFound, Errno::ENOENT),
+            (systree::Error::AlreadyExists, Errno::EEXIST),
+            (systree::Error::PermissionDenied, Errno::EACCES),
+            (systree::Error::InvalidArgument, Errno::EINVAL),
+            (systree::Error::NotSupported, Errno::EOPNOTSUPP),
+            (systree::Error::TryAgain, Errno::EAGAIN),
+            (systree::Error::TimedOut, Errno::ETIMEDOUT),
+            (systree::Error::BadAddress, Errno::EFAULT),
+            (systree::Error::TooLarge, Errno::EFBIG),
+            (systree::Error::NoSpace, Errno::ENOSPC),
+            (systree::Error::Io, Errno::EIO),
+        ];
+        for (err, errno) in expected {
+            assert_eq!(Error::from(err).error(), errno);
+        }
+    }
+}
@@ -3,11 +3,28 @@ mod super::SysStr;
 /// An immutable set of attributes associated with a node in `SysTree`.
 pub struct SysAttrSet {
     this_set: Option<Box<[SysAttr]>>,
-    parent_set: Option<Arc<SysAttr>>,
+    parent_set: Option<Arc<SysAttrSet>>,
 }
 
 impl SysAttrSet {
-    pub const CAPACITY: usize = 256;
+    /// The number of bits of an attribute ID.
+    ///
+    /// Both the capacity of an attribute set and the encoding of
+    /// the inode numbers of attributes in sysfs are derived from this constant,
+    /// so the width of attribute IDs can be changed in one place.
+    pub const ID_BITS: u32 = 8;
+
+    /// The maximum number of attribute IDs of an attribute set.
+    pub const CAPACITY: usize = 1 << Self::ID_BITS;
+
+    /// The number of the largest attribute IDs that are never allocated,
+    /// which are reserved for the synthetic entries of sysfs
+    /// (e.g., the `subsystem` symlink) and the dirs of attribute groups
+    /// (see `SysAttrGroup`).
+    pub const NUM_RESERVED_IDS: usize = 16;
+
+    /// The maximum number of attributes of an attribute set.
+    pub const MAX_ATTRS: usize = Self::CAPACITY - Self::NUM_RESERVED_IDS;
 
     pub const fn new_empty() -> Self {
         Self {
@@ -39,13 +56,13 @@ impl SysAttrSet {
     }
 }
 
-pub struct SysAttrSetBuidler {
-    total_attrs: u8,
-    this_set: Vec<SysStr>,
+pub struct SysAttrSetBuilder {
+    total_attrs: SysAttrId,
+    this_set: Vec<SysAttr>,
     parent_set: Option<Arc<SysAttrSet>>,
 }
 
-impl SysAttrSetBuidler {
+impl SysAttrSetBuilder {
     pub fn new() -> Self {
         Self {
             total_attrs: 0,
@@ -56,37 +73,62 @@ impl SysAttrSetBuidler {
 
     pub fn with_parent(parent: Arc<SysAttrSet>) -> Self {
         Self {
-            total_attrs: parent.len() as u8,
+            total_attrs: parent.len() as SysAttrId,
             this_set: Vec::new(),
             parent_set: Some(parent),
         }    
     }
 
+    /// Adds an attribute.
+    ///
+    /// An attribute set contains at most `SysAttrSet::MAX_ATTRS` attributes.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the name of the attribute is invalid
+    /// or if the attribute set is full (see the `try_add` method).
+    /// Use the `try_add` method to add attributes of untrusted names.
     pub fn add(&mut self, name: SysStr, flags: SysAttrFlags) -> &mut Self {
-        debug_assert!(self.total_attrs < u8::MAX);
+        self.try_add(name, flags).expect("the attribute is invalid or the set is full")
+    }
+
+    /// Adds an attribute, failing if the name of the attribute is invalid.
+    ///
+    /// The name of an attribute becomes a filename in sysfs,
+    /// so it must be valid as the name of a node (see `utils::validate_name`).
+    /// Otherwise, `Error::InvalidArgument` is returned.
+    ///
+    /// An attribute set contains at most `SysAttrSet::MAX_ATTRS` attributes.
+    /// Once the set is full, adding a new attribute fails with `Error::NoSpace`.
+    pub fn try_add(&mut self, name: SysStr, flags: SysAttrFlags) -> Result<&mut Self> {
+        crate::utils::validate_name(&name)?;
 
         // Ignore the attribute if it is already contained in parent_set
-        if let Some(parent_set) = self.parent_set {
-            if parent_set.contains(name) {
-                return;
+        if let Some(parent_set) = &self.parent_set {
+            if parent_set.contains(&name) {
+                return Ok(self);
             }
         };
 
         // Ignore the attribute if it is already contained in this_set
-        let already_added = this_set.iter().find(|old_attr| {
-            old_attr.name() == name
-        }).is_some();
+        let already_added = self.this_set.iter().any(|old_attr| {
+            *old_attr.name() == name
+        });
         if already_added {
-            return;
+            return Ok(self);
         }
 
+        if self.total_attrs as usize >= SysAttrSet::MAX_ATTRS {
+            return Err(Error::NoSpace);
+        }
         let new_attr = SysAttr {
             id: self.total_attrs,
             name,
             flags,
         };
-        self.this_set.push_back(new_attr);
+        self.this_set.push(new_attr);
         self.total_attrs += 1;
+        Ok(self)
     }
 
     pub fn build(mut self) -> SysAttrSet {
@@ -99,16 +141,24 @@ impl SysAttrSetBuidler {
     }
 }
 
+/// The ID of an attribute, which is unique within the attribute set of a node.
+///
+/// The type is wide enough for any ID of `SysAttrSet::ID_BITS` bits,
+/// so raising `SysAttrSet::ID_BITS` only requires widening this type, if at all.
+pub type SysAttrId = u16;
+
+const_assert!(SysAttrSet::ID_BITS <= SysAttrId::BITS);
+
 /// An attribute of a node in a `SysTree`.
 #[derive(Copy, Clone, Debug)]
 pub struct SysAttr {
-    id: u8,
+    id: SysAttrId,
     name: SysStr,
     flags: SysAttrFlags,
 }
 
 impl SysAttr {
-    pub fn new(id: u8, name: SysStr, flags: SysAttrFlags) -> Self {
+    pub fn new(id: SysAttrId, name: SysStr, flags: SysAttrFlags) -> Self {
         Self {
             id,
             name,
@@ -116,7 +166,7 @@ impl SysAttr {
         }
     }
 
-    pub fn id(&self) -> u8 {
+    pub fn id(&self) -> SysAttrId {
         self.id
     }
 
@@ -129,6 +179,37 @@ impl SysAttr {
     }
 }
 
+/// A named group of the attributes of a node,
+/// which is shown as a subdir of the node's dir in sysfs
+/// (e.g., `power/` in the dir of a device).
+///
+/// The attributes in a group are still contained in
+/// the attribute set of the node; a group only affects where they are shown.
+#[derive(Clone, Debug)]
+pub struct SysAttrGroup {
+    name: SysStr,
+    attr_names: Vec<SysStr>,
+}
+
+impl SysAttrGroup {
+    pub fn new(name: SysStr, attr_names: Vec<SysStr>) -> Self {
+        Self { name, attr_names }
+    }
+
+    pub fn name(&self) -> &SysStr {
+        &self.name
+    }
+
+    pub fn attr_names(&self) -> &[SysStr] {
+        &self.attr_names
+    }
+
+    /// Returns whether the group contains an attribute of the given name.
+    pub fn contains(&self, attr_name: &str) -> bool {
+        self.attr_names.iter().any(|name| name == attr_name)
+    }
+}
+
 bitflags! {
     /// The flags of an attribute of a node in a `SysTree`.
     pub struct SysAttrFlags: u8 {
@@ -139,6 +220,18 @@ bitflags! {
         /// Indicates whether an attribute is a binary one
         /// (rather than a textual one).
         const IS_BINARY: u8     = 1 << 4;
+        /// Indicates whether all accesses to an attribute are serialized
+        /// through a mutex dedicated to the attribute.
+        const IS_SERIALIZED: u8 = 1 << 5;
+        /// Indicates whether the value of an attribute may be written
+        /// in multiple chunks, each at its own offset
+        /// (see `crate::utils::SysStreamedValue`).
+        const IS_STREAMED: u8   = 1 << 6;
+        /// Indicates whether the writes to an attribute are appended to its value
+        /// regardless of their offsets (e.g., an audit log),
+        /// so reads return the accumulated content
+        /// (see `crate::utils::SysAttrHandler::append`).
+        const IS_APPEND_ONLY: u8 = 1 << 7;
     }
 }
 
@@ -147,3 +240,39 @@ impl Default for SysAttrFlags {
         Self::CAN_READ
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+    use crate::Error;
+
+    #[ktest]
+    fn builder_rejects_invalid_attr_names() {
+        let mut builder = SysAttrSetBuilder::new();
+        for name in ["class/net", "/mtu", "", "mtu\0", "\0", ".", ".."] {
+            let res = builder.try_add(SysStr::Borrowed(name), SysAttrFlags::CAN_READ);
+            assert_eq!(res.err(), Some(Error::InvalidArgument));
+        }
+        builder
+            .try_add("mtu".into(), SysAttrFlags::CAN_READ)
+            .unwrap()
+            .try_add("mtu.old".into(), SysAttrFlags::CAN_READ)
+            .unwrap();
+    }
+
+    #[ktest]
+    fn builder_rejects_attrs_beyond_capacity() {
+        let mut builder = SysAttrSetBuilder::new();
+        for i in 0..SysAttrSet::MAX_ATTRS {
+            builder
+                .try_add(format!("attr{}", i).into(), SysAttrFlags::CAN_READ)
+                .unwrap();
+        }
+        // Adding an existing attribute is still a no-op
+        builder.try_add("attr0".into(), SysAttrFlags::CAN_READ).unwrap();
+        let res = builder.try_add("one_more".into(), SysAttrFlags::CAN_READ);
+        assert_eq!(res.err(), Some(Error::NoSpace));
+    }
+}
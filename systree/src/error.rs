@@ -0,0 +1,38 @@
+/// The error type of the operations on a `SysTree`.
+///
+/// The implementations of `SysNode` report their failures with this type,
+/// which is mapped to an errno by the "view" part (e.g., sysfs)
+/// when the error crosses the boundary to the user space.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A node or an attribute is not found.
+    NotFound,
+    /// A node or an attribute with the same name already exists.
+    AlreadyExists,
+    /// The operation is not permitted on a node or an attribute.
+    PermissionDenied,
+    /// An argument of the operation is invalid.
+    InvalidArgument,
+    /// The operation is not supported by a node or an attribute.
+    NotSupported,
+    /// The operation cannot be done for now, but may succeed if retried later
+    /// (e.g., the hardware is not ready).
+    TryAgain,
+    /// The operation does not complete before the timeout.
+    TimedOut,
+    /// A buffer provided for the operation is not accessible.
+    BadAddress,
+    /// A value exceeds the maximum size that can be stored.
+    TooLarge,
+    /// There is no room left for a new item
+    /// (e.g., an attribute set already has the maximum number of attributes).
+    NoSpace,
+    /// The operation fails due to the underlying hardware or controller.
+    ///
+    /// This is the error that should be reported
+    /// if a failure does not fit in any of the other kinds.
+    Io,
+}
+
+/// The result type of the operations on a `SysTree`.
+pub type Result<T> = core::result::Result<T, Error>;
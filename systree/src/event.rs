@@ -3,14 +3,61 @@
 /// 
 /// 
 pub struct SysEventHub {
-    subject: Subject<SysEvent, SysEventSelector>,
+    // The registered observers along with their selectors
+    // and the descriptions of the selectors, which are kept for diagnostics.
+    registry: Mutex<Vec<RegisteredObserver>>,
+    next_handle: AtomicU64,
+    // The sequence number of the last published event
+    last_seqnum: AtomicU64,
+    // The default attribute sets of the registered subsystems,
+    // indexed by the names of the subsystems (see `SysTree::register_subsystem`).
+    subsystem_attrs: RwMutex<BTreeMap<SysStr, Arc<SysAttrSet>>>,
+}
+
+struct RegisteredObserver {
+    handle: ObserverHandle,
+    observer: Weak<dyn Observer<SysEvent>>,
+    // The strong reference to a one-shot observer,
+    // which is owned by the hub until it receives its event.
+    one_shot: Option<Arc<dyn Observer<SysEvent>>>,
+    selector: SysEventSelector,
+    description: String,
 }
 
 impl SysEventHub {
     pub const fn new() -> Self {
         Self {
-            subject: Subject::new(),
+            registry: Mutex::new(Vec::new()),
+            next_handle: AtomicU64::new(0),
+            last_seqnum: AtomicU64::new(0),
+            subsystem_attrs: RwMutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub(crate) fn register_subsystem(&self, name: SysStr, default_attrs: Arc<SysAttrSet>) -> Result<()> {
+        let mut subsystem_attrs = self.subsystem_attrs.write();
+        if subsystem_attrs.contains_key(&name) {
+            return Err(Error::AlreadyExists);
         }
+        subsystem_attrs.insert(name, default_attrs);
+        Ok(())
+    }
+
+    pub(crate) fn subsystem_attrs(&self, name: &str) -> Option<Arc<SysAttrSet>> {
+        self.subsystem_attrs.read().get(name).cloned()
+    }
+
+    /// Returns the name of the subsystem whose members include
+    /// the node of the given path (see `SysTree::subsystem_of`).
+    pub(crate) fn subsystem_of(&self, path: &str) -> Option<SysStr> {
+        let mut names = path.split('/').filter(|name| !name.is_empty());
+        let top_level_name = names.next()?;
+        // The top-level node itself is not a member
+        names.next()?;
+
+        let subsystem_attrs = self.subsystem_attrs.read();
+        let (name, _) = subsystem_attrs.get_key_value(top_level_name)?;
+        Some(name.clone())
     }
 
     pub fn publish_event(&self,
@@ -18,43 +65,221 @@ impl SysEventHub {
         action: SysEventAction,
         details: Vec<SysEventKv>
     ) {
-        let Some(path) = obj.path() else {
+        let path = obj.path();
+        if !path.starts_with('/') {
             // The object is not attached to the systree, yet.
             // We do not allow unattached object to publish events.
             return;
+        }
+
+        let subsystem = self.subsystem_of(&path);
+        let event = SysEvent::new(action, path.into(), details)
+            .with_source(source_of(obj))
+            .with_subsystem(subsystem)
+            .with_seqnum(self.next_seqnum());
+        self.notify_observers(&event);
+    }
+
+    /// Publishes an event that occurs on the given path.
+    ///
+    /// This method is useful when the path of the object can no longer be
+    /// obtained from the object itself (e.g., the object has been removed).
+    pub(crate) fn publish_event_at(&self,
+        path: SysStr,
+        action: SysEventAction,
+        details: Vec<SysEventKv>
+    ) {
+        let subsystem = self.subsystem_of(&path);
+        let event = SysEvent::new(action, path, details)
+            .with_subsystem(subsystem)
+            .with_seqnum(self.next_seqnum());
+        self.notify_observers(&event);
+    }
+
+    /// Allocates the sequence number for an event to be published.
+    ///
+    /// Like those of Linux uevents, sequence numbers start at one
+    /// and increase by one per published event.
+    fn next_seqnum(&self) -> u64 {
+        self.last_seqnum.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Delivers an event to the observers whose selectors match it.
+    ///
+    /// The delivery to each observer is isolated from the others:
+    /// the matching observers are collected under the lock of the registry,
+    /// but the event is delivered after the lock has been released.
+    /// So an observer that dies, or that re-enters the hub
+    /// (e.g., to unregister itself or to publish another event),
+    /// cannot prevent the other observers from receiving the event.
+    /// The dead observers are pruned along the way,
+    /// and so are the one-shot observers that receive the event.
+    ///
+    /// Note that a panicking observer cannot be isolated,
+    /// since panics are fatal in the kernel.
+    fn notify_observers(&self, event: &SysEvent) {
+        let observers: Vec<Arc<dyn Observer<SysEvent>>> = {
+            let mut registry = self.registry.lock();
+            let mut observers = Vec::new();
+            registry.retain(|registered| {
+                let Some(observer) = registered.observer.upgrade() else {
+                    return false;
+                };
+                if !registered.selector.filter(event) {
+                    return true;
+                }
+                observers.push(observer);
+                registered.one_shot.is_none()
+            });
+            observers
         };
 
-        let event = SysEvent::new(action, path, details);
-        self.subject.notify_observers(&event);
+        for observer in observers {
+            observer.on_events(event);
+        }
     }
 
     pub fn register_observer(&self,
         observer: Weak<dyn Observer<SysEvent>>,
         filter: SysEventSelector
-    ) -> Option<> {
-        self.subject.register_observer(observer, filter).unwrap()
+    ) -> ObserverHandle {
+        let handle = ObserverHandle(self.next_handle.fetch_add(1, Ordering::Relaxed));
+        let description = filter.describe();
+        self.registry.lock().push(RegisteredObserver {
+            handle,
+            observer,
+            one_shot: None,
+            selector: filter,
+            description,
+        });
+        handle
+    }
+
+    /// Registers a one-shot observer,
+    /// which receives at most one event, i.e., the first one selected by the filter,
+    /// and is then unregistered automatically.
+    ///
+    /// Unlike the `register_observer` method, the hub owns the observer
+    /// until the observer receives its event.
+    pub fn register_one_shot_observer(&self,
+        observer: Arc<dyn Observer<SysEvent>>,
+        filter: SysEventSelector
+    ) -> ObserverHandle {
+        let handle = ObserverHandle(self.next_handle.fetch_add(1, Ordering::Relaxed));
+        let description = format!("{} (once)", filter.describe());
+        self.registry.lock().push(RegisteredObserver {
+            handle,
+            observer: Arc::downgrade(&observer),
+            one_shot: Some(observer),
+            selector: filter,
+            description,
+        });
+        handle
     }
 
     pub fn unregister_observer(&self, observer: Weak<dyn Observer<SysEvent>>)
         -> Option<Weak<dyn Observer<SysEvent>>>
     {
-        self.subject.unregister_observer(observer)
+        let mut registry = self.registry.lock();
+        let index = registry
+            .iter()
+            .position(|registered| Weak::ptr_eq(&registered.observer, &observer))?;
+        Some(registry.remove(index).observer)
+    }
+
+    /// Unregisters the observer identified by a handle,
+    /// returning whether the observer was registered.
+    ///
+    /// Unlike the `unregister_observer` method,
+    /// this method also works for the one-shot observers,
+    /// whose `Weak`s are not known to the callers.
+    /// A one-shot observer that is unregistered before receiving its event
+    /// is dropped by the hub without being invoked.
+    pub fn unregister_observer_by_handle(&self, handle: ObserverHandle) -> bool {
+        let removed = {
+            let mut registry = self.registry.lock();
+            let Some(index) = registry.iter().position(|registered| registered.handle == handle) else {
+                return false;
+            };
+            registry.remove(index)
+        };
+        // The observer, if owned by the hub, is dropped after the lock has been released
+        drop(removed);
+        true
+    }
+
+    /// Lists the handles of the registered observers
+    /// along with the descriptions of their selectors.
+    ///
+    /// The observers that have been dropped are not listed.
+    pub fn list_observers(&self) -> Vec<(ObserverHandle, String)> {
+        let mut registry = self.registry.lock();
+        registry.retain(|registered| registered.observer.strong_count() > 0);
+        registry
+            .iter()
+            .map(|registered| (registered.handle, registered.description.clone()))
+            .collect()
     }
 }
 
+/// A handle that identifies an observer registered in a `SysTree`.
+///
+/// The handle can be used to unregister the observer
+/// (see `SysTree::unregister_observer_by_handle`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObserverHandle(u64);
+
 /// A selector (i.e., a filter) for events that occur in the `SysTree`.
 pub enum SysEventSelector {
     // Select all events.
     All,
     // Select only events of a specific action.
     Action(SysEventAction),
+    // Select only events that occur at the given path or below it.
+    Subtree(String),
+    // Select only events that are selected by all of the given selectors.
+    //
+    // An empty list selects all events.
+    AllOf(Vec<SysEventSelector>),
+    // Select only events that are selected by any of the given selectors.
+    //
+    // An empty list selects no events.
+    AnyOf(Vec<SysEventSelector>),
+}
+
+impl SysEventSelector {
+    /// Returns a human-readable description of the selector,
+    /// which is intended for diagnostics.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::All => "all events".to_string(),
+            Self::Action(action) => format!("events of action `{}`", action.as_str()),
+            Self::Subtree(path) => format!("events under `{}`", path),
+            Self::AllOf(selectors) => Self::describe_list(selectors, " and ", "all events"),
+            Self::AnyOf(selectors) => Self::describe_list(selectors, " or ", "no events"),
+        }
+    }
+
+    fn describe_list(selectors: &[SysEventSelector], sep: &str, empty: &str) -> String {
+        if selectors.is_empty() {
+            return empty.to_string();
+        }
+        let descriptions: Vec<String> = selectors
+            .iter()
+            .map(|selector| format!("({})", selector.describe()))
+            .collect();
+        descriptions.join(sep)
+    }
 }
 
 impl EventsFilter<SysEvent> for SysEventSelector {
     fn filter(&self, event: &SysEvent) -> bool {
         match self {
             Self::All => true,
-            Self::Action(action) => action == event.action(),
+            Self::Action(action) => *action == event.action(),
+            Self::Subtree(path) => is_path_in_subtree(event.path(), path),
+            Self::AllOf(selectors) => selectors.iter().all(|selector| selector.filter(event)),
+            Self::AnyOf(selectors) => selectors.iter().any(|selector| selector.filter(event)),
         }
     }
 }
@@ -65,6 +290,15 @@ impl EventsFilter<SysEvent> for SysEventSelector {
 /// * Which _action_ triggers the event (`self.action()`);
 /// * On which _path_ the event occurs (`self.path()`);
 /// * More _details_ about the event, encoded as key-value pairs (`self.details`).
+///
+/// Two events are equal if and only if they consist of the same three components,
+/// so consumers that buffer events can deduplicate them.
+///
+/// An event may also refer to the node from which it originates (`self.source()`),
+/// so that observers can inspect the node without looking it up by the path.
+/// The source node is not taken into account by equality,
+/// nor are the subsystem (`self.subsystem()`) and the sequence number (`self.seqnum()`)
+/// assigned by the `SysTree` when the event is published.
 #[derive(Clone, Debug)]
 pub struct SysEvent {
     // Mandatory info
@@ -72,20 +306,66 @@ pub struct SysEvent {
     // Which action happens
     action: SysEventAction,
     // Where the event originates from
-    path: String,
+    //
+    // The path is a `SysStr` so that static or interned paths
+    // (see `crate::intern`) need no allocation per event.
+    path: SysStr,
     // Optional details
     details: Vec<SysEventKv>,
+    // The node from which the event originates, if known
+    source: Option<Weak<dyn SysObj>>,
+    // The subsystem in which the event occurs, if any
+    subsystem: Option<SysStr>,
+    // The sequence number assigned when the event is published, if any
+    seqnum: Option<u64>,
 }
 
 impl SysEvent {
-    pub fn new(action: SysEventAction, path: String, details: Vec<SysEventKv>) -> Self {
+    pub fn new(action: SysEventAction, path: SysStr, details: Vec<SysEventKv>) -> Self {
         Self {
             action,
             path,
             details,
+            source: None,
+            subsystem: None,
+            seqnum: None,
         }
     }
 
+    /// Sets the node from which the event originates.
+    pub fn with_source(mut self, source: Option<Weak<dyn SysObj>>) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Sets the subsystem in which the event occurs.
+    pub fn with_subsystem(mut self, subsystem: Option<SysStr>) -> Self {
+        self.subsystem = subsystem;
+        self
+    }
+
+    /// Returns the name of the subsystem in which the event occurs
+    /// (see `SysTree::subsystem_of`).
+    ///
+    /// `None` is returned if the path of the event is not in any subsystem,
+    /// or if the event has not been published through a `SysTree`.
+    pub fn subsystem(&self) -> Option<&str> {
+        self.subsystem.as_deref()
+    }
+
+    /// Sets the sequence number of the event.
+    pub fn with_seqnum(mut self, seqnum: u64) -> Self {
+        self.seqnum = Some(seqnum);
+        self
+    }
+
+    /// Returns the sequence number of the event.
+    ///
+    /// `None` is returned if the event has not been published through a `SysTree`.
+    pub fn seqnum(&self) -> Option<u64> {
+        self.seqnum
+    }
+
     pub fn action(&self) -> SysEventAction {
         self.action
     }
@@ -97,17 +377,122 @@ impl SysEvent {
     pub fn details(&self) -> &[SysEventKv] {
         &self.details
     }
+
+    /// Serializes the event into the wire format of Linux uevents,
+    /// i.e., `KEY=VALUE` entries terminated by `\0`.
+    ///
+    /// The entries start with the mandatory prefix of
+    /// `ACTION`, `DEVPATH`, and `SUBSYSTEM` (if the event occurs in a subsystem),
+    /// followed by `SEQNUM` (if the event has been published)
+    /// and the details of the event in the given order.
+    ///
+    /// A detail whose key contains `=` or `\0` or whose value contains `\0`
+    /// cannot be represented in the format, so it is left out
+    /// rather than corrupting the entries that follow.
+    pub fn to_uevent_buffer(&self, order: SysUeventOrder) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut push_entry = |key: &str, value: &str| {
+            if !is_uevent_entry_valid(key, value) {
+                return;
+            }
+            buf.extend_from_slice(key.as_bytes());
+            buf.push(b'=');
+            buf.extend_from_slice(value.as_bytes());
+            buf.push(b'\0');
+        };
+
+        push_entry("ACTION", self.action.as_str());
+        push_entry("DEVPATH", &self.path);
+        if let Some(subsystem) = &self.subsystem {
+            push_entry("SUBSYSTEM", subsystem);
+        }
+        if let Some(seqnum) = self.seqnum {
+            push_entry("SEQNUM", &seqnum.to_string());
+        }
+
+        let mut details: Vec<&SysEventKv> = self.details.iter().collect();
+        if order == SysUeventOrder::SortedByKey {
+            // The sort is stable, so the details with the same key keep their order
+            details.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+        for kv in details {
+            push_entry(&kv.key, &kv.value);
+        }
+        buf
+    }
+
+    /// Serializes the event into the wire format of Linux uevents,
+    /// with the details in the order of insertion.
+    ///
+    /// See `to_uevent_buffer` for the details of the format.
+    pub fn to_uevent_bytes(&self) -> Vec<u8> {
+        self.to_uevent_buffer(SysUeventOrder::Insertion)
+    }
+
+    /// Returns the node from which the event originates.
+    ///
+    /// `None` is returned if the source node is unknown
+    /// (e.g., for the event of a removed node) or has been dropped.
+    pub fn source(&self) -> Option<Arc<dyn SysObj>> {
+        self.source.as_ref()?.upgrade()
+    }
+}
+
+impl PartialEq for SysEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.action == other.action && self.path == other.path && self.details == other.details
+    }
+}
+
+impl Eq for SysEvent {}
+
+impl Hash for SysEvent {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.action.hash(state);
+        self.path.hash(state);
+        self.details.hash(state);
+    }
+}
+
+/// The order of the details of an event in its uevent serialization
+/// (see `SysEvent::to_uevent_buffer`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SysUeventOrder {
+    /// The details are in the order of insertion.
+    #[default]
+    Insertion,
+    /// The details are sorted by their keys,
+    /// which makes the serialization reproducible
+    /// for consumers that compare the payloads.
+    SortedByKey,
+}
+
+/// Returns whether a key-value pair can be encoded as
+/// a `KEY=VALUE` entry of a uevent without ambiguity.
+fn is_uevent_entry_valid(key: &str, value: &str) -> bool {
+    !key.is_empty() && !key.contains(['=', '\0']) && !value.contains('\0')
+}
+
+/// Returns a weak reference to an object attached to a `SysTree`,
+/// which is obtained from the parent of the object.
+fn source_of(obj: &dyn SysObj) -> Option<Weak<dyn SysObj>> {
+    let parent = obj.parent().upgrade()?;
+    let source = parent.child(&obj.name())?;
+    if source.id() != obj.id() {
+        return None;
+    }
+    Some(Arc::downgrade(&source))
 }
 
 /// A key-value pair of strings, which encodes information about an `SysEvent`.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct SysEventKv {
     pub key: SysStr,
     pub value: SysStr,
 }
 
 /// The action of an `SysEvent`.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SysEventAction {
     /// Add a new node in the `SysTree`.
     Add,
@@ -115,4 +500,277 @@ pub enum SysEventAction {
     Remove,
     /// Change a node in the `SysTree`.
     Change,
-}
\ No newline at end of file
+    /// Bring a node in the `SysTree` online (e.g., a hot-plugged CPU).
+    Online,
+    /// Take a node in the `SysTree` offline (e.g., a hot-unplugged CPU).
+    Offline,
+    /// Move or rename a node in the `SysTree`,
+    /// with the old path of the node in the `OLD_DEVPATH` detail.
+    Move,
+}
+
+impl SysEventAction {
+    /// Returns the name of the action,
+    /// which is the value of the `ACTION` key of a Linux uevent.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+            Self::Change => "change",
+            Self::Online => "online",
+            Self::Offline => "offline",
+            Self::Move => "move",
+        }
+    }
+}
+
+/// Returns whether a path is that of the root of a subtree
+/// or that of one of its descendants.
+///
+/// For example, `/class/net/eth0` is in the subtree of `/class/net`,
+/// while `/class/network` is not.
+pub(crate) fn is_path_in_subtree(path: &str, subtree_path: &str) -> bool {
+    let subtree_path = subtree_path.trim_end_matches('/');
+    match path.strip_prefix(subtree_path) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::AtomicUsize;
+
+    use ostd::prelude::ktest;
+
+    use super::*;
+    use crate::{
+        test_util::{EventRecorder, TreeBuilder},
+        utils::{SysLeafNode, SysStoredValue},
+        SysAttrFlags, SysAttrSetBuilder, SysNodeType, SysTree,
+    };
+
+    fn new_change_event(value: &'static str) -> SysEvent {
+        let details = vec![SysEventKv {
+            key: "ATTR".into(),
+            value: value.into(),
+        }];
+        SysEvent::new(SysEventAction::Change, "/devices/eth0".into(), details)
+    }
+
+    /// An observer that unregisters itself upon the first event.
+    struct SelfUnregistering {
+        tree: &'static SysTree,
+        handle: Mutex<Option<ObserverHandle>>,
+        num_events: AtomicUsize,
+    }
+
+    impl Observer<SysEvent> for SelfUnregistering {
+        fn on_events(&self, _event: &SysEvent) {
+            self.num_events.fetch_add(1, Ordering::Relaxed);
+            let handle = self.handle.lock().unwrap();
+            self.tree.unregister_observer_by_handle(handle);
+        }
+    }
+
+    #[ktest]
+    fn dead_and_reentrant_observers_do_not_block_others() {
+        let tree: &'static SysTree = Box::leak(Box::new(
+            TreeBuilder::new()
+                .add("/devices/eth0", SysNodeType::Leaf, &[])
+                .build()
+                .unwrap(),
+        ));
+        let dead_recorder = EventRecorder::new();
+        tree.register_observer(Arc::downgrade(&dead_recorder) as _, SysEventSelector::All);
+        drop(dead_recorder);
+        let reentrant = Arc::new(SelfUnregistering {
+            tree,
+            handle: Mutex::new(None),
+            num_events: AtomicUsize::new(0),
+        });
+        let handle = tree.register_observer(Arc::downgrade(&reentrant) as _, SysEventSelector::All);
+        *reentrant.handle.lock() = Some(handle);
+        let live_recorder = EventRecorder::new();
+        tree.register_observer(Arc::downgrade(&live_recorder) as _, SysEventSelector::All);
+
+        let eth0 = tree.lookup_node("/devices/eth0").unwrap();
+        tree.publish_event(eth0.as_ref(), SysEventAction::Change, Vec::new());
+        assert_eq!(live_recorder.paths(), ["/devices/eth0"]);
+        assert_eq!(reentrant.num_events.load(Ordering::Relaxed), 1);
+        // Both the dead and the unregistered observers are gone
+        assert_eq!(tree.list_observers().len(), 1);
+
+        tree.publish_event(eth0.as_ref(), SysEventAction::Change, Vec::new());
+        assert_eq!(live_recorder.events().len(), 2);
+        assert_eq!(reentrant.num_events.load(Ordering::Relaxed), 1);
+    }
+
+    fn kv(key: &'static str, value: &'static str) -> SysEventKv {
+        SysEventKv {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    #[ktest]
+    fn uevent_details_are_sorted_by_key_on_demand() {
+        let details = vec![kv("MINOR", "0"), kv("DEVNAME", "sda"), kv("MAJOR", "8"), kv("DEVNAME", "sdb")];
+        let event = SysEvent::new(SysEventAction::Add, "/block/sda".into(), details)
+            .with_subsystem(Some("block".into()));
+
+        let prefix = "ACTION=add\0DEVPATH=/block/sda\0SUBSYSTEM=block\0";
+        let expected = format!("{}MINOR=0\0DEVNAME=sda\0MAJOR=8\0DEVNAME=sdb\0", prefix);
+        assert_eq!(event.to_uevent_bytes(), expected.as_bytes());
+        assert_eq!(event.to_uevent_buffer(SysUeventOrder::Insertion), expected.as_bytes());
+        // The mandatory prefix stays in place, and the equal keys keep their order
+        let expected = format!("{}DEVNAME=sda\0DEVNAME=sdb\0MAJOR=8\0MINOR=0\0", prefix);
+        assert_eq!(event.to_uevent_buffer(SysUeventOrder::SortedByKey), expected.as_bytes());
+    }
+
+    #[ktest]
+    fn subsystems_of_published_events_are_registered_ones() {
+        let tree = TreeBuilder::new()
+            .add("/devices/pci0/eth0", SysNodeType::Leaf, &[])
+            .add("/net/eth0", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        tree.register_subsystem("net".into(), Arc::new(SysAttrSetBuilder::new().build()))
+            .unwrap();
+        let recorder = EventRecorder::new();
+        tree.register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+
+        for path in ["/devices/pci0/eth0", "/net/eth0", "/net"] {
+            let node = tree.lookup_node(path).unwrap();
+            tree.publish_event(node.as_ref(), SysEventAction::Change, Vec::new());
+        }
+        let subsystems: Vec<Option<String>> = recorder
+            .events()
+            .iter()
+            .map(|event| event.subsystem().map(|subsystem| subsystem.to_string()))
+            .collect();
+        // Unlike the top-level dirs (e.g., `/devices`), only registered subsystems count,
+        // whose own nodes are not their members
+        assert_eq!(subsystems, [None, Some("net".to_string()), None]);
+    }
+
+    /// An observer that reads the `mtu` attribute of the source node of every event.
+    struct MtuReader {
+        mtus: Mutex<Vec<String>>,
+    }
+
+    impl Observer<SysEvent> for MtuReader {
+        fn on_events(&self, event: &SysEvent) {
+            let source = event.source().and_then(|source| source.arc_as_node());
+            let mtu = source.and_then(|node| node.show_attr("mtu").ok());
+            self.mtus.lock().push(mtu.unwrap_or_default());
+        }
+    }
+
+    #[ktest]
+    fn observer_reads_attr_of_event_source() {
+        let tree = TreeBuilder::new()
+            .add("/devices", SysNodeType::Branch, &[])
+            .build()
+            .unwrap();
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add("mtu".into(), SysAttrFlags::CAN_READ);
+        let eth0 = SysLeafNode::new("eth0".into(), builder.build());
+        eth0.set_handler("mtu", Arc::new(SysStoredValue::new(b"1500")))
+            .unwrap();
+        let devices = tree.lookup("/devices").unwrap().arc_as_branch().unwrap();
+        eth0.set_parent(Arc::downgrade(&devices));
+        tree.add_child(devices.as_ref(), eth0.clone()).unwrap();
+
+        let reader = Arc::new(MtuReader {
+            mtus: Mutex::new(Vec::new()),
+        });
+        tree.register_observer(Arc::downgrade(&reader) as _, SysEventSelector::All);
+        tree.publish_event(eth0.as_ref(), SysEventAction::Change, Vec::new());
+        assert_eq!(*reader.mtus.lock(), ["1500"]);
+
+        // An event constructed without a node has no source
+        let event = SysEvent::new(SysEventAction::Change, "/devices/eth0".into(), Vec::new());
+        assert!(event.source().is_none());
+    }
+
+    #[ktest]
+    fn event_borrows_static_path() {
+        static PATH: &str = "/devices/system/cpu/cpu0";
+        let event = SysEvent::new(SysEventAction::Change, PATH.into(), Vec::new());
+        // No copy of the path is allocated
+        assert!(matches!(event.path, SysStr::Borrowed(_)));
+        assert_eq!(event.path().as_ptr(), PATH.as_ptr());
+        let cloned_event = event.clone();
+        assert_eq!(cloned_event.path().as_ptr(), PATH.as_ptr());
+        assert_eq!(
+            event.to_uevent_bytes(),
+            b"ACTION=change\0DEVPATH=/devices/system/cpu/cpu0\0"
+        );
+
+        let owned_path: SysStr = PATH.to_string().into();
+        let event = SysEvent::new(SysEventAction::Change, owned_path, Vec::new());
+        assert!(matches!(event.path, SysStr::Owned(_)));
+        assert_eq!(event.path(), PATH);
+    }
+
+    #[ktest]
+    fn events_are_equal_regardless_of_seqnums() {
+        let a = new_change_event("mtu").with_seqnum(1);
+        let b = new_change_event("mtu")
+            .with_seqnum(2)
+            .with_subsystem(Some("net".into()));
+        assert_eq!(a, b);
+
+        // So consumers that buffer events can deduplicate them
+        let mut dedup = Vec::new();
+        for event in [a.clone(), b.clone(), new_change_event("carrier")] {
+            if !dedup.contains(&event) {
+                dedup.push(event);
+            }
+        }
+        assert_eq!(dedup.len(), 2);
+
+        assert_ne!(a, new_change_event("carrier"));
+        let removal = SysEvent::new(SysEventAction::Remove, "/devices/eth0".into(), a.details().to_vec());
+        assert_ne!(a, removal);
+    }
+
+    #[ktest]
+    fn online_and_offline_events_are_selected_and_serialized() {
+        let tree = TreeBuilder::new()
+            .add("/devices/system/cpu/cpu1", SysNodeType::Leaf, &["online"])
+            .build()
+            .unwrap();
+        let online_recorder = EventRecorder::new();
+        let all_recorder = EventRecorder::new();
+        tree.register_observer(
+            Arc::downgrade(&online_recorder) as _,
+            SysEventSelector::Action(SysEventAction::Online),
+        );
+        tree.register_observer(Arc::downgrade(&all_recorder) as _, SysEventSelector::All);
+
+        let cpu1 = tree.lookup_node("/devices/system/cpu/cpu1").unwrap();
+        tree.publish_event(cpu1.as_ref(), SysEventAction::Offline, Vec::new());
+        tree.publish_event(cpu1.as_ref(), SysEventAction::Online, Vec::new());
+
+        let events = online_recorder.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action(), SysEventAction::Online);
+        let actions: Vec<SysEventAction> = all_recorder
+            .events()
+            .iter()
+            .map(|event| event.action())
+            .collect();
+        assert_eq!(actions, [SysEventAction::Offline, SysEventAction::Online]);
+
+        for (action, prefix) in [
+            (SysEventAction::Online, "ACTION=online\0"),
+            (SysEventAction::Offline, "ACTION=offline\0"),
+        ] {
+            let event = SysEvent::new(action, "/devices/system/cpu/cpu1".into(), Vec::new());
+            let expected = format!("{}DEVPATH=/devices/system/cpu/cpu1\0", prefix);
+            assert_eq!(event.to_uevent_bytes(), expected.as_bytes());
+        }
+    }
+}
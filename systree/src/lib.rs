@@ -16,25 +16,42 @@
 //! making the code more modular, maintainable, and easier to understand.
 
 mod attr;
+mod error;
 mod event;
 mod node;
+mod transaction;
 mod tree;
 
+pub mod intern;
+#[cfg(any(ktest, feature = "test-util"))]
+pub mod test_util;
 pub mod utils;
 
-pub use self::attr:{SysAttr, SysAttrFlags, SysAttrSet, SysAttrSetBuilder};
-pub use self::event::{SysEvent, SysEventKv, SysEventAction};
-pub use self::node::{SysNodeType, SysBranchNode, SysNode, SysSymlink, SysObj, SysNodeId};
-pub use self::tree::{SysTree};
+pub use self::attr:{SysAttr, SysAttrFlags, SysAttrGroup, SysAttrId, SysAttrSet, SysAttrSetBuilder};
+pub use self::error::{Error, Result};
+pub use self::event::{ObserverHandle, SysEvent, SysEventKv, SysEventAction, SysEventSelector, SysUeventOrder};
+pub use self::node::{common_ancestor, SysAncestors, SysNodeType, SysBranchNode, SysNode, SysSymlink, SysObj, SysNodeId, SysWalkOrder};
+pub use self::transaction::{SysTransaction, SysTxError};
+pub use self::tree::{NodeSubscription, SysTree};
 
-static SYS_TREE: SysTree = SysTree::new();
+static SYS_TREE: Once<SysTree> = Once::new();
 
 /// Gets the singleton of the `SysTree`.
+///
+/// The singleton is created on first use,
+/// so it is safe to call this function before `init`
+/// (e.g., from the init function of sysfs).
 pub fn singleton() -> &'static SysTree {
-    &SYS_TREE
+    SYS_TREE.call_once(SysTree::new)
 }
 
-// TODO: initialize the singleton in the component init function
+/// Initializes the singleton of the `SysTree`.
+///
+/// This function is expected to be called in the component init function.
+/// Calling it more than once is harmless.
+pub fn init() {
+    let _ = singleton();
+}
 
 /// An owned string or a static reference to string.
 pub type SysStr = Cow<'static, str>;
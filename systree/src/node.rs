@@ -1,4 +1,5 @@
 use core::any::Any;
+use core::ops::ControlFlow;
 use core::sync::{Arc, Weak};
 
 /// The three types of nodes in a `SysTree`.
@@ -14,6 +15,34 @@ pub enum SysNodeType {
     Symlink,
 }
 
+impl SysNodeType {
+    /// Returns the name of the type.
+    ///
+    /// The names are stable since they may be a part of external formats
+    /// (e.g., a dump of a `SysTree`).
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Branch => "branch",
+            Self::Leaf => "leaf",
+            Self::Symlink => "symlink",
+        }
+    }
+}
+
+impl FromStr for SysNodeType {
+    type Err = Error;
+
+    /// Parses a type from its name returned by `SysNodeType::as_str`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "branch" => Ok(Self::Branch),
+            "leaf" => Ok(Self::Leaf),
+            "symlink" => Ok(Self::Symlink),
+            _ => Err(Error::InvalidArgument),
+        }
+    }
+}
+
 /// A trait that represents a branching node in a `SysTree`.
 pub trait SysBranchNode: SysNode {
     /// Visits a child node with the given name using a closure.
@@ -21,6 +50,11 @@ pub trait SysBranchNode: SysNode {
     /// If the child with the given name exists, 
     /// a reference to the child will be provided to the closure.
     /// Otherwise, the closure will be given a `None`.
+    /// The closure is called exactly once.
+    ///
+    /// The child is provided as a reference to its `Arc`,
+    /// so the closure may clone the `Arc` to keep the child
+    /// (as the `child` method does).
     /// 
     /// # Efficiency
     /// 
@@ -39,7 +73,7 @@ pub trait SysBranchNode: SysNode {
     /// on this object as this might cause deadlock.
     fn visit_child_with(&self,
         name: &str, 
-        f: &mut dyn FnMut(Option<&dyn SysNode>)
+        f: &mut dyn FnMut(Option<&Arc<dyn SysObj>>)
     );
 
     /// Visits child nodes with a minimum ID using a closure.
@@ -50,6 +84,9 @@ pub trait SysBranchNode: SysNode {
     /// 
     /// The iteration terminates until there are no unvisisted children
     /// or the closure returns a `None`.
+    ///
+    /// Like the `visit_child_with` method, the children are provided
+    /// as references to their `Arc`s, which the closure may clone.
     /// 
     /// # Efficiency
     /// 
@@ -65,14 +102,90 @@ pub trait SysBranchNode: SysNode {
     /// Same as the `visit_child_with` method. 
     fn visit_children_with(&self, 
         min_id: u64,
-        f: &mut dyn FnMut(&dyn SysObj) -> Option<()>,
+        f: &mut dyn FnMut(&Arc<dyn SysObj>) -> Option<()>,
     );
 
+    /// Visits at most `max_count` child nodes with a minimum ID using a closure.
+    ///
+    /// This method behaves the same as the `visit_children_with` method,
+    /// except that the iteration also terminates
+    /// once `max_count` children have been visited,
+    /// even if the closure keeps returning `Some`.
+    /// This is useful for paginated or bounded scans.
+    ///
+    /// # Deadlock
+    ///
+    /// Same as the `visit_child_with` method.
+    fn visit_children_limited(&self,
+        min_id: u64,
+        max_count: usize,
+        f: &mut dyn FnMut(&Arc<dyn SysObj>) -> Option<()>,
+    ) {
+        if max_count == 0 {
+            return;
+        }
+
+        let mut count = 0;
+        self.visit_children_with(min_id, &mut |child| {
+            f(child)?;
+            count += 1;
+            if count >= max_count {
+                return None;
+            }
+            Some(())
+        });
+    }
+
+    /// Returns the generation of the children.
+    ///
+    /// The generation must advance whenever a child is added, removed, or renamed,
+    /// so that caches of the children can detect staleness cheaply.
+    ///
+    /// By default, the generation is always zero,
+    /// which means that the children of the node must not be cached.
+    /// Caches should treat a generation of zero as "no caching".
+    fn generation(&self) -> u64 {
+        0
+    }
+
+    /// Detaches a child with a specified name from this node.
+    ///
+    /// This method allows a node to be removed without knowing
+    /// the concrete type of its parent (see `SysTree::remove_self`).
+    ///
+    /// By default, detaching children is not supported.
+    fn detach_child(&self, _name: &str) -> Result<Arc<dyn SysObj>> {
+        Err(Error::NotSupported)
+    }
+
+    /// Attaches a child to this node.
+    ///
+    /// This method is the counterpart of the `detach_child` method,
+    /// which allows a node to be added without knowing
+    /// the concrete type of its parent (see `SysTransaction`).
+    /// The parent of the child must have been set to this node.
+    ///
+    /// By default, attaching children is not supported.
+    fn attach_child(&self, _child: Arc<dyn SysObj>) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
+    /// Renames a child of this node.
+    ///
+    /// Unlike detaching the child and attaching it again,
+    /// the child is renamed atomically (see `SysBranchNodeFields::rename_child`),
+    /// so concurrent lookups never miss the child (see `SysTree::move_node`).
+    ///
+    /// By default, renaming children is not supported.
+    fn rename_child(&self, _old_name: &str, _new_name: SysStr) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
     /// Returns a child with a specified name.
     fn child(&self, name: &str) -> Option<Arc<dyn SysObj>> {
-        let mut res;
+        let mut res = None;
         self.visit_child_with(name, &mut |child_opt| {
-            res = child_opt.to_owned()
+            res = child_opt.cloned();
         });
         res
     }
@@ -81,17 +194,66 @@ pub trait SysBranchNode: SysNode {
     fn children(&self) -> Vec<Arc<dyn SysObj>> {
         let mut children = Vec::new();
         self.visit_children_with(0, &mut |child| {
-            children.push_back(child.clone());
+            children.push(child.clone());
             Some(())
         });
         children
     }
 
+    /// Collects all children into a `Vec`, sorted by their names.
+    ///
+    /// Unlike the `children` method, which returns the children
+    /// in the order of their IDs, this method is convenient for listings.
+    ///
+    /// By default, the children are sorted alphabetically.
+    /// A node backed by `SysBranchNodeFields` should override this method
+    /// with `SysBranchNodeFields::children_sorted`,
+    /// so that the comparator of the names configured for the node is honored.
+    fn children_sorted_by_name(&self) -> Vec<Arc<dyn SysObj>> {
+        let mut children = self.children();
+        children.sort_by_cached_key(|child| child.name());
+        children
+    }
+
+    /// Walks the descendants of this node in depth first,
+    /// providing each descendant and its depth to the given closure.
+    ///
+    /// With `SysWalkOrder::Pre`, a node is visited before its descendants
+    /// (e.g., to print a listing);
+    /// with `SysWalkOrder::Post`, a node is visited after its descendants
+    /// (e.g., to tear down a subtree from the bottom up).
+    ///
+    /// The children of this node are at depth one.
+    /// Descendants deeper than `max_depth` (or `SysTree::MAX_DEPTH`,
+    /// whichever is smaller) are not visited.
+    /// Symlinks are visited, but never followed.
+    ///
+    /// The walk stops as soon as the closure returns `ControlFlow::Break`,
+    /// which is then returned by this method.
+    ///
+    /// # Deadlock
+    ///
+    /// The walk is built on the `visit_children_with` method,
+    /// so it holds the internal locks of the ancestors of the visited node.
+    /// The closure should _not_ invoke methods that modify
+    /// this node or any of its descendants.
+    fn walk(&self,
+        max_depth: usize,
+        order: SysWalkOrder,
+        visitor: &mut dyn FnMut(&dyn SysObj, usize) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        let max_depth = max_depth.min(SysTree::MAX_DEPTH);
+        if max_depth == 0 {
+            return ControlFlow::Continue(());
+        }
+        walk_children(self, 1, max_depth, order, visitor)
+    }
+
     /// Counts the number of children.
     fn count_children(&self) -> usize {
-        let mut count = 0;
-        self.visit_children_with(.., &mut |_| {
-            *count += 1;
+        let mut count = 0usize;
+        self.visit_children_with(0, &mut |_| {
+            count += 1;
             Some(())
         });
         count
@@ -106,13 +268,146 @@ pub trait SysBranchNode: SysNode {
 /// In particular, every "normal" node may have associated attributes.
 pub trait SysNode: SysObj {
     /// Returns the attribute set of a `SysNode`.
-    fn node_attrs(&self) -> &SysAttrSet;
+    ///
+    /// The attribute set of a node may be replaced as a whole at runtime
+    /// (see the `replace_node_attrs` method),
+    /// so the returned attribute set is a snapshot.
+    fn node_attrs(&self) -> Arc<SysAttrSet>;
+
+    /// Replaces the attribute set of a node atomically at runtime
+    /// (e.g., when a device is reconfigured),
+    /// while the node itself (including its ID) stays intact.
+    ///
+    /// Most callers should use `SysTree::replace_node_attrs` instead,
+    /// which publishes a `Change` event for the new attribute set.
+    ///
+    /// By default, the attribute set of a node cannot be replaced.
+    fn replace_node_attrs(&self, _new_attrs: SysAttrSet) -> Result<()> {
+        Err(Error::NotSupported)
+    }
 
-    /// Reads the value of an attribute.
-    fn read_attr(&self, name: &str, writer: &mut VmWriter) -> Result<usize>;
+    /// Adds an attribute to a node at runtime
+    /// (e.g., one that appears only after a device has been probed).
+    ///
+    /// Only the name and the flags of the given attribute are used.
+    /// The new attribute is allocated the next unused ID,
+    /// so its ID never collides with those of the existing attributes.
+    ///
+    /// Most callers should use `SysTree::add_attr` instead,
+    /// which publishes a `Change` event for the new attribute.
+    ///
+    /// By default, attributes cannot be added at runtime.
+    fn add_attr(&self, _attr: SysAttr) -> Result<()> {
+        Err(Error::NotSupported)
+    }
 
-    /// Writes the value of an attribute.
-    fn write_attr(&self, name: &str, reader: &mut VmReader) -> Result<()>;
+    /// Returns the attribute groups of a node.
+    ///
+    /// An attribute contained in a group is shown in the subdir of the group
+    /// rather than directly in the dir of the node.
+    /// The names of the groups must not collide with
+    /// those of the attributes or the child nodes.
+    ///
+    /// By default, a node has no attribute groups.
+    fn attr_groups(&self) -> &[SysAttrGroup] {
+        &[]
+    }
+
+    /// Returns the current flags of an attribute.
+    ///
+    /// The flags of an attribute may be changed at runtime
+    /// (see the `set_attr_flags` method),
+    /// so the flags returned by this method may differ from
+    /// those in the attribute set.
+    fn attr_flags(&self, name: &str) -> Option<SysAttrFlags> {
+        self.node_attrs().get(name).map(|attr| attr.flags())
+    }
+
+    /// Sets the flags of an attribute at runtime.
+    ///
+    /// Most callers should use `SysTree::set_attr_flags` instead,
+    /// which publishes a `Change` event for the new flags.
+    ///
+    /// By default, the flags of attributes cannot be changed.
+    fn set_attr_flags(&self, _name: &str, _flags: SysAttrFlags) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
+    /// Returns whether an attribute is readable at the moment.
+    ///
+    /// Beyond the static `CAN_READ` flag, a computed attribute may be
+    /// temporarily unreadable (e.g., when the hardware is offline),
+    /// in which case the implementation can override this method
+    /// to gate the readability on the dynamic state.
+    ///
+    /// By default, an attribute is readable if it has the `CAN_READ` flag.
+    fn attr_is_readable_now(&self, name: &str) -> bool {
+        self.attr_flags(name)
+            .is_some_and(|flags| flags.contains(SysAttrFlags::CAN_READ))
+    }
+
+    /// Returns the minimum interval between two accepted writes to an attribute.
+    ///
+    /// Writable attributes that poke hardware can be protected
+    /// from being abused by a tight-loop writer in the user space,
+    /// in which case the writes arriving too soon are rejected
+    /// or delayed by sysfs.
+    ///
+    /// By default, the writes to attributes are not rate limited.
+    fn attr_write_interval(&self, _name: &str) -> Option<Duration> {
+        None
+    }
+
+    /// Reads the value of an attribute, starting from the given offset.
+    ///
+    /// Returns the number of bytes read.
+    /// Reading at or beyond the end of the value returns zero.
+    ///
+    /// Sysfs passes the offset of the read only for binary attributes
+    /// (see `SysAttrFlags::IS_BINARY`), which support `pread` at arbitrary offsets.
+    /// For textual attributes, the offset is always zero.
+    fn read_attr(&self, name: &str, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize>;
+
+    /// Writes the value of an attribute, starting from the given offset.
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// Sysfs passes the offset of the write only for binary and streamed attributes
+    /// (see `SysAttrFlags::IS_BINARY` and `SysAttrFlags::IS_STREAMED`).
+    /// For other attributes, the offset is always zero.
+    fn write_attr(&self, name: &str, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize>;
+
+    /// Commits the value of a streamed attribute
+    /// that has been written in multiple chunks (see `SysAttrFlags::IS_STREAMED`).
+    ///
+    /// Sysfs calls this method once a writer of a streamed attribute closes the file,
+    /// with the total number of bytes that the writer has written.
+    /// The implementation should reject the commit with `Error::InvalidArgument`
+    /// if the length does not match the pending value
+    /// (e.g., since another writer has started a new value in between).
+    ///
+    /// By default, there is nothing to commit.
+    fn commit_attr(&self, _name: &str, _len: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns the current size of the value of an attribute in bytes.
+    ///
+    /// This method helps callers to size their buffers before reading
+    /// the value of an attribute.
+    ///
+    /// The implementations that store the values of attributes
+    /// should return the exact sizes.
+    /// For attributes whose values are computed on reads,
+    /// the size may be an estimate.
+    /// By default, the size is estimated as one page,
+    /// which is the conventional upper bound of the values of textual attributes.
+    fn attr_size(&self, name: &str) -> Result<usize> {
+        if !self.node_attrs().contains(name) {
+            return Err(Error::NotFound);
+        }
+        Ok(PAGE_SIZE)
+    }
 
     /// Shows the string value of an attribute.
     ///
@@ -120,21 +415,107 @@ pub trait SysNode: SysObj {
     /// So using this `show_attr` method is more convenient than
     /// the `read_attr` method.
     fn show_attr(&self, name: &str) -> Result<String> {
-        let mut buf: Vec<u8> = vec![0; PAGE_SIZE];
-        let mut writer = VmWriter::from(buf.as_mut_slice());
-        let read_len = self.read_attr(name, &mut writer)?;
-        let attr_val = String::from_utf(buf)?;
+        let attr_val = self.read_attr_bytes(name)?;
+        String::from_utf8(attr_val).map_err(|_| Error::InvalidArgument)
+    }
+
+    /// Reads the value of an attribute as a signed integer.
+    ///
+    /// The leading and trailing whitespaces (e.g., a newline) are ignored.
+    /// If the value is not an integer, `Error::InvalidArgument` is returned.
+    fn read_attr_i64(&self, name: &str) -> Result<i64> {
+        let attr_val = self.show_attr(name)?;
+        attr_val.trim().parse().map_err(|_| Error::InvalidArgument)
+    }
+
+    /// Reads the value of an attribute as an unsigned integer.
+    ///
+    /// The leading and trailing whitespaces (e.g., a newline) are ignored.
+    /// If the value is not an unsigned integer, `Error::InvalidArgument` is returned.
+    fn read_attr_u64(&self, name: &str) -> Result<u64> {
+        let attr_val = self.show_attr(name)?;
+        attr_val.trim().parse().map_err(|_| Error::InvalidArgument)
+    }
+
+    /// Reads the complete value of an attribute as bytes.
+    ///
+    /// This method is the bytes-level counterpart of the `show_attr` method,
+    /// which is more convenient than the `read_attr` method
+    /// for binary attributes (see `SysAttrFlags::IS_BINARY`).
+    ///
+    /// The value is read as the `stream_attr` method does.
+    fn read_attr_bytes(&self, name: &str) -> Result<Vec<u8>> {
+        let mut attr_val = Vec::new();
+        self.stream_attr(name, &mut |chunk| {
+            attr_val.extend_from_slice(chunk);
+            Ok(())
+        })?;
         Ok(attr_val)
     }
 
+    /// Streams the complete value of an attribute, chunk by chunk, into a sink.
+    ///
+    /// Unlike the `read_attr_bytes` method,
+    /// this method does not materialize the whole value,
+    /// which is wasteful for very large attributes.
+    /// If the sink returns an error, the streaming stops with the error.
+    ///
+    /// By default, the value of a binary attribute (see `SysAttrFlags::IS_BINARY`)
+    /// is read one page at a time with the `read_attr` method
+    /// until the end of the value.
+    /// The value of a textual attribute is read with a single call at offset zero,
+    /// as sysfs does, since the implementations may ignore the offsets
+    /// of textual attributes (see `read_attr`).
+    /// The implementations of computed attributes may override this method
+    /// to emit the chunks directly, avoiding any buffering.
+    fn stream_attr(&self, name: &str, sink: &mut dyn FnMut(&[u8]) -> Result<()>) -> Result<()> {
+        let is_binary = self
+            .attr_flags(name)
+            .is_some_and(|flags| flags.contains(SysAttrFlags::IS_BINARY));
+        if !is_binary {
+            let buf_len = self.attr_size(name).map_or(PAGE_SIZE, |size| size.max(PAGE_SIZE));
+            let mut buf: Vec<u8> = vec![0; buf_len];
+            let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+            let read_len = self.read_attr(name, 0, &mut writer)?;
+            if read_len > 0 {
+                sink(&buf[..read_len])?;
+            }
+            return Ok(());
+        }
+
+        let mut offset = 0;
+        let mut buf: Vec<u8> = vec![0; PAGE_SIZE];
+        loop {
+            let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+            let read_len = self.read_attr(name, offset, &mut writer)?;
+            if read_len == 0 {
+                return Ok(());
+            }
+            sink(&buf[..read_len])?;
+            offset += read_len;
+        }
+    }
+
     /// Stores the string value of an attribute.
     ///
     /// Most attributes are textual, rather binary (see `SysAttrFlags::IS_BINARY`).
     /// So using this `store_attr` method is more convenient than
     /// the `write_attr` method.
     fn store_attr(&self, name: &str, new_val: &str) -> Result<()> {
-        let mut reader = VmReader::from(new_val.as_slice());
-        self.write_attr(name, &mut reader)
+        let mut reader = VmReader::from(new_val.as_bytes()).to_fallible();
+        self.write_attr(name, 0, &mut reader)?;
+        Ok(())
+    }
+
+    /// Stores the bytes value of an attribute.
+    ///
+    /// This method is the bytes-level counterpart of the `store_attr` method,
+    /// which is more convenient than the `write_attr` method
+    /// for binary attributes (see `SysAttrFlags::IS_BINARY`).
+    fn store_attr_bytes(&self, name: &str, new_val: &[u8]) -> Result<()> {
+        let mut reader = VmReader::from(new_val).to_fallible();
+        self.write_attr(name, 0, &mut reader)?;
+        Ok(())
     }
 }
 
@@ -166,11 +547,79 @@ pub trait SysObj: Any + Send + Sync + Debug + 'static {
     /// If the node has no 
     fn parent(&self) -> Weak<dyn SysBranchNode>;
 
+    /// Sets the parent of a node.
+    ///
+    /// This method is called by `SysBranchNodeFields::add_child`
+    /// (with the branching node that the node is added to)
+    /// and `SysBranchNodeFields::remove_child` (with an empty `Weak`),
+    /// so that the parent of a node is wired up upon insertion.
+    /// The reference implementations store the parent in `SysObjFields`.
+    ///
+    /// By default, the parent of a node is fixed upon construction,
+    /// so this method does nothing.
+    fn set_parent(&self, _parent: Weak<dyn SysBranchNode>) {}
+
+    /// Moves a node to a new parent and/or renames it.
+    ///
+    /// This method is called by `SysTree::move_node`
+    /// while the node is detached from its old parent
+    /// and before it is attached to the new one,
+    /// so the implementation only needs to update the stored parent and name.
+    /// The ID of the node stays intact.
+    ///
+    /// By default, moving a node is not supported.
+    fn relocate(&self, _new_parent: Weak<dyn SysBranchNode>, _new_name: SysStr) -> Result<()> {
+        Err(Error::NotSupported)
+    }
+
     /// Returns whether a node is the root of a `SysTree`.
     fn is_root(&self) -> bool {
         return false;
     }
 
+    /// Returns whether a node is internal.
+    ///
+    /// An internal node (e.g., a node for internal bookkeeping)
+    /// is a part of the tree for controllers and the paths of events,
+    /// but it is not exposed to the user space,
+    /// i.e., it is treated as nonexistent by sysfs.
+    ///
+    /// Whether a node is internal must not change while it is in a branching node,
+    /// since the views of the children are cached until the children change
+    /// (see `SysBranchNode::generation`).
+    /// To expose or hide a node, detach it and attach it again.
+    ///
+    /// By default, a node is not internal.
+    fn is_internal(&self) -> bool {
+        false
+    }
+
+    /// Casts a node to a "normal" node, if it is one.
+    ///
+    /// Only branching and leaf nodes are "normal" nodes.
+    fn as_node(&self) -> Option<&dyn SysNode> {
+        None
+    }
+
+    /// Casts a node to a branching node, if it is one.
+    fn as_branch(&self) -> Option<&dyn SysBranchNode> {
+        None
+    }
+
+    /// Casts an `Arc` of a node to that of a "normal" node, if it is one.
+    ///
+    /// This method is the owned version of the `as_node` method.
+    fn arc_as_node(self: Arc<Self>) -> Option<Arc<dyn SysNode>> {
+        None
+    }
+
+    /// Casts an `Arc` of a node to that of a branching node, if it is one.
+    ///
+    /// This method is the owned version of the `as_branch` method.
+    fn arc_as_branch(self: Arc<Self>) -> Option<Arc<dyn SysBranchNode>> {
+        None
+    }
+
     /// Returns the path from the root to this node.
     /// 
     /// The path of a node is the names of all the ancestors concatenated
@@ -179,25 +628,193 @@ pub trait SysObj: Any + Send + Sync + Debug + 'static {
     /// If the node has been attached to a `SysTree`,
     /// then the returned path begins with `/`.
     /// Otherwise, the returned path does _not_ begin with `/`.
+    /// In particular, the path of the root is `/`,
+    /// and the path of a node without a parent is its own name.
+    /// If the parent chain is broken partway up
+    /// (i.e., an ancestor has been dropped),
+    /// the walk stops there and the returned path is relative
+    /// to the last live ancestor.
+    ///
+    /// At most `SysTree::MAX_DEPTH` ancestors are walked,
+    /// so that a corrupted parent chain (e.g., a cycle) cannot hang this method.
+    /// If the limit is reached, the returned path is truncated
+    /// and thus does _not_ begin with `/`.
     fn path(&self) -> String {
-        todo!("implement with the parent and name methods")
+        if self.is_root() {
+            return SysTree::ROOT_PATH.to_string();
+        }
+
+        let mut names = vec![self.name()];
+        let mut is_attached = false;
+        let mut next_parent = self.parent().upgrade();
+        while let Some(parent) = next_parent {
+            if parent.is_root() {
+                is_attached = true;
+                break;
+            }
+            if names.len() >= SysTree::MAX_DEPTH {
+                break;
+            }
+
+            names.push(parent.name());
+            next_parent = parent.parent().upgrade();
+        }
+
+        names.reverse();
+        let path = names.join("/");
+        if is_attached {
+            format!("/{}", path)
+        } else {
+            path
+        }
+    }
+
+    /// Returns an iterator over the ancestors of a node,
+    /// from the parent of the node up to the root.
+    ///
+    /// The node itself is not included.
+    /// If the parent chain is broken partway up
+    /// (i.e., an ancestor has been dropped),
+    /// the iteration stops at the last live ancestor.
+    ///
+    /// Like `path`, at most `SysTree::MAX_DEPTH` ancestors are yielded,
+    /// so that a corrupted parent chain (e.g., a cycle) cannot hang the iteration.
+    fn ancestors(&self) -> SysAncestors {
+        SysAncestors {
+            next: self.parent(),
+            depth: 0,
+        }
     }
 }
 
+/// An iterator over the ancestors of a node (see `SysObj::ancestors`).
+pub struct SysAncestors {
+    next: Weak<dyn SysBranchNode>,
+    // The number of the ancestors that have been yielded
+    depth: usize,
+}
+
+impl Iterator for SysAncestors {
+    type Item = Arc<dyn SysBranchNode>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.depth >= SysTree::MAX_DEPTH {
+            return None;
+        }
+
+        let ancestor = self.next.upgrade()?;
+        self.next = if ancestor.is_root() {
+            crate::utils::no_parent()
+        } else {
+            ancestor.parent()
+        };
+        self.depth += 1;
+        Some(ancestor)
+    }
+}
+
+/// The order in which `SysBranchNode::walk` visits the nodes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SysWalkOrder {
+    /// A node is visited before its descendants.
+    Pre,
+    /// A node is visited after its descendants.
+    Post,
+}
+
+/// Walks the children of a branching node at the given depth
+/// and their descendants (see `SysBranchNode::walk`).
+fn walk_children<B: SysBranchNode + ?Sized>(
+    branch: &B,
+    depth: usize,
+    max_depth: usize,
+    order: SysWalkOrder,
+    visitor: &mut dyn FnMut(&dyn SysObj, usize) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut flow = ControlFlow::Continue(());
+    branch.visit_children_with(0, &mut |child| {
+        if order == SysWalkOrder::Pre {
+            flow = visitor(child.as_ref(), depth);
+        }
+        if flow.is_continue() && depth < max_depth {
+            // Symlinks are not branching nodes, so they are never descended into
+            if let Some(child_branch) = child.as_branch() {
+                flow = walk_children(child_branch, depth + 1, max_depth, order, visitor);
+            }
+        }
+        if flow.is_continue() && order == SysWalkOrder::Post {
+            flow = visitor(child.as_ref(), depth);
+        }
+        if flow.is_break() {
+            return None;
+        }
+        Some(())
+    });
+    flow
+}
+
+/// Returns the lowest common ancestor of two nodes.
+///
+/// The ancestors of a node include the node itself if it is a branching node.
+/// So if one node is an ancestor of the other, the former is returned.
+///
+/// If the two nodes are not in the same tree, `None` is returned.
+pub fn common_ancestor(a: &Arc<dyn SysObj>, b: &Arc<dyn SysObj>) -> Option<Arc<dyn SysBranchNode>> {
+    let a_ancestor_ids: Vec<SysNodeId> = self_and_ancestors(a)
+        .map(|a_ancestor| *a_ancestor.id())
+        .collect();
+    self_and_ancestors(b).find(|b_ancestor| a_ancestor_ids.contains(b_ancestor.id()))
+}
+
+/// Returns an iterator over a node itself (if it is a branching node)
+/// and its ancestors, from the bottom to the top.
+fn self_and_ancestors(node: &Arc<dyn SysObj>) -> impl Iterator<Item = Arc<dyn SysBranchNode>> {
+    node.clone().arc_as_branch().into_iter().chain(node.ancestors())
+}
+
 /// The unique ID of a `SysNode`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SysNodeId(u64);
 
 impl SysNodeId {
+    /// The maximum value of an ID.
+    ///
+    /// The IDs leave the higher `SysAttrSet::ID_BITS` bits unused
+    /// so that the users can encode the ID of an attribute with the ID of its node
+    /// (e.g., sysfs derives the inode numbers of a node and its attributes
+    /// by shifting the ID of the node left by `SysAttrSet::ID_BITS` bits).
+    /// The largest value of the remaining bits is not a valid ID either,
+    /// so that the users have spare values beyond the encoded IDs.
+    pub const MAX: u64 = (u64::MAX >> SysAttrSet::ID_BITS) - 1;
+
     /// Creates a new ID.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the IDs are exhausted (see `try_new`).
     pub fn new() -> Self {
+        Self::try_new().expect("the IDs of nodes are exhausted")
+    }
+
+    /// Creates a new ID, returning `None` if the IDs are exhausted.
+    ///
+    /// The IDs are never reused, even after their nodes are dropped,
+    /// so that a stale ID kept by the users (e.g., in the inode number of sysfs)
+    /// never refers to another node.
+    /// Even if a million nodes were created per second,
+    /// it would take more than two thousand years to exhaust the IDs.
+    pub fn try_new() -> Option<Self> {
         static NEXT_ID: AtomicU64 = AtomicU64::new(0);
 
-        let next_id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
-        // Guard against integer overflow
-        assert!(next_id <= u64::MAX / 2);
+        // Unlike `fetch_add`, the ID never advances beyond the maximum,
+        // so the exhaustion persists instead of wrapping around.
+        let next_id = NEXT_ID
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next_id| {
+                (next_id <= Self::MAX).then_some(next_id + 1)
+            })
+            .ok()?;
 
-        Self(next_id)
+        Some(Self(next_id))
     }
 
     /// Gets the value of the ID.
@@ -205,3 +822,441 @@ impl SysNodeId {
         self.0
     }
 }
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use ostd::prelude::ktest;
+
+    use super::*;
+    use crate::{
+        test_util::{FixtureBranch, FixtureLeaf, TreeBuilder},
+        utils::{no_parent, SysAttrHandler, SysLeafNode, SysStoredValue},
+        Error, Result, SysAttrFlags, SysAttrSetBuilder,
+    };
+
+    /// A handler of an attribute that can be written but not read.
+    struct WriteOnlyValue;
+
+    impl SysAttrHandler for WriteOnlyValue {
+        fn read(&self, _offset: usize, _writer: &mut VmWriter<Fallible>) -> Result<usize> {
+            Err(Error::PermissionDenied)
+        }
+
+        fn write(&self, _offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+            Ok(reader.remain())
+        }
+    }
+
+    fn pattern_byte(offset: usize) -> u8 {
+        (offset % 251) as u8
+    }
+
+    fn new_leaf(attrs: &[(&'static str, SysAttrFlags)]) -> Arc<SysLeafNode> {
+        let mut builder = SysAttrSetBuilder::new();
+        for (name, flags) in attrs {
+            builder.add(SysStr::Borrowed(name), *flags);
+        }
+        SysLeafNode::new("dev0".into(), builder.build())
+    }
+
+    #[ktest]
+    fn read_attr_bytes_of_small_value() {
+        let leaf = new_leaf(&[("name", SysAttrFlags::CAN_READ)]);
+        let value = Arc::new(SysStoredValue::new(b"eth0\n"));
+        leaf.set_handler("name", value).unwrap();
+
+        assert_eq!(leaf.read_attr_bytes("name").unwrap(), b"eth0\n");
+    }
+
+    #[ktest]
+    fn read_attr_bytes_of_multi_page_value() {
+        const VALUE_LEN: usize = 2 * PAGE_SIZE + 100;
+
+        let leaf = new_leaf(&[("firmware", SysAttrFlags::CAN_READ | SysAttrFlags::IS_BINARY)]);
+        let firmware = |offset: usize, writer: &mut VmWriter<Fallible>| -> Result<usize> {
+            let chunk: Vec<u8> = (offset..VALUE_LEN)
+                .take(writer.avail())
+                .map(pattern_byte)
+                .collect();
+            let mut reader = VmReader::from(chunk.as_slice());
+            writer
+                .write_fallible(&mut reader)
+                .map_err(|_| Error::BadAddress)
+        };
+        leaf.set_handler("firmware", Arc::new(firmware)).unwrap();
+
+        let value = leaf.read_attr_bytes("firmware").unwrap();
+        assert_eq!(value.len(), VALUE_LEN);
+        assert!(value
+            .iter()
+            .enumerate()
+            .all(|(offset, byte)| *byte == pattern_byte(offset)));
+    }
+
+    #[ktest]
+    fn store_attr_bytes_writes_non_utf8_bytes() {
+        let leaf = new_leaf(&[(
+            "firmware",
+            SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE | SysAttrFlags::IS_BINARY,
+        )]);
+        leaf.set_handler("firmware", Arc::new(SysStoredValue::new(&[]))).unwrap();
+
+        let blob = [0xde, 0xad, 0xbe, 0xef, 0xff, 0x00, 0x80];
+        assert!(core::str::from_utf8(&blob).is_err());
+        leaf.store_attr_bytes("firmware", &blob).unwrap();
+        assert_eq!(leaf.read_attr_bytes("firmware").unwrap(), blob);
+    }
+
+    #[ktest]
+    fn attr_size_of_stored_and_computed_values() {
+        let leaf = new_leaf(&[
+            ("name", SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE),
+            ("stat", SysAttrFlags::CAN_READ),
+        ]);
+        leaf.set_handler("name", Arc::new(SysStoredValue::new(b"eth0\n"))).unwrap();
+        let stat = |_offset: usize, _writer: &mut VmWriter<Fallible>| -> Result<usize> { Ok(0) };
+        leaf.set_handler("stat", Arc::new(stat)).unwrap();
+
+        // The size of a stored value is exact and follows the writes
+        assert_eq!(leaf.attr_size("name").unwrap(), 5);
+        leaf.store_attr("name", "wlan0.1\n").unwrap();
+        assert_eq!(leaf.attr_size("name").unwrap(), 8);
+        // That of a computed value is estimated as one page
+        assert_eq!(leaf.attr_size("stat").unwrap(), PAGE_SIZE);
+        assert_eq!(leaf.attr_size("no_such_attr"), Err(Error::NotFound));
+    }
+
+    #[ktest]
+    fn read_attr_bytes_of_write_only_attr() {
+        let leaf = new_leaf(&[("reset", SysAttrFlags::CAN_WRITE)]);
+        leaf.set_handler("reset", Arc::new(WriteOnlyValue)).unwrap();
+
+        assert_eq!(leaf.read_attr_bytes("reset"), Err(Error::PermissionDenied));
+        assert_eq!(leaf.read_attr_bytes("no_such_attr"), Err(Error::NotFound));
+    }
+
+    #[ktest]
+    fn visit_children_limited_stops_at_max_count() {
+        let tree = TreeBuilder::new()
+            .add("/cpu/cpu0", SysNodeType::Leaf, &[])
+            .add("/cpu/cpu1", SysNodeType::Leaf, &[])
+            .add("/cpu/cpu2", SysNodeType::Leaf, &[])
+            .add("/cpu/cpu3", SysNodeType::Leaf, &[])
+            .add("/cpu/cpu4", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        let cpu = tree.lookup("/cpu").unwrap().arc_as_branch().unwrap();
+
+        let mut num_visited = 0;
+        cpu.visit_children_limited(0, 2, &mut |_child| {
+            num_visited += 1;
+            Some(())
+        });
+        assert_eq!(num_visited, 2);
+
+        // The closure can still stop the iteration earlier
+        let mut num_visited = 0;
+        cpu.visit_children_limited(0, 2, &mut |_child| {
+            num_visited += 1;
+            None
+        });
+        assert_eq!(num_visited, 1);
+
+        let mut num_visited = 0;
+        cpu.visit_children_limited(0, 0, &mut |_child| {
+            num_visited += 1;
+            Some(())
+        });
+        assert_eq!(num_visited, 0);
+    }
+
+    #[ktest]
+    fn children_sorted_by_name_in_alphabetical_order() {
+        let tree = TreeBuilder::new()
+            .add("/net/wlan0", SysNodeType::Leaf, &[])
+            .add("/net/eth0", SysNodeType::Leaf, &[])
+            .add("/net/lo", SysNodeType::Leaf, &[])
+            .add_symlink("/net/bond0", "eth0")
+            .build()
+            .unwrap();
+        let net = tree.lookup("/net").unwrap().arc_as_branch().unwrap();
+
+        let names: Vec<SysStr> = net
+            .children_sorted_by_name()
+            .iter()
+            .map(|child| child.name())
+            .collect();
+        assert_eq!(names, ["bond0", "eth0", "lo", "wlan0"]);
+    }
+
+    #[ktest]
+    fn common_ancestors_of_nodes() {
+        let tree = TreeBuilder::new()
+            .add("/devices/pci0/eth0", SysNodeType::Leaf, &[])
+            .add("/devices/pci0/eth1", SysNodeType::Leaf, &[])
+            .add("/devices/usb0/wlan0", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        let other_tree = TreeBuilder::new()
+            .add("/devices/pci0/eth0", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        let lookup = |tree: &SysTree, path: &str| tree.lookup(path).unwrap();
+        let eth0 = lookup(&tree, "/devices/pci0/eth0");
+
+        // Siblings
+        let ancestor = common_ancestor(&eth0, &lookup(&tree, "/devices/pci0/eth1")).unwrap();
+        assert_eq!(ancestor.path(), "/devices/pci0");
+        // Cousins
+        let ancestor = common_ancestor(&eth0, &lookup(&tree, "/devices/usb0/wlan0")).unwrap();
+        assert_eq!(ancestor.path(), "/devices");
+        // An ancestor and its descendant
+        let ancestor = common_ancestor(&lookup(&tree, "/devices/pci0"), &eth0).unwrap();
+        assert_eq!(ancestor.path(), "/devices/pci0");
+        // Unrelated nodes
+        let other_eth0 = lookup(&other_tree, "/devices/pci0/eth0");
+        assert!(common_ancestor(&eth0, &other_eth0).is_none());
+    }
+
+    #[ktest]
+    fn corrupted_parent_cycle_does_not_hang() {
+        let a = FixtureBranch::new("a".into(), &[], no_parent());
+        let b = FixtureBranch::new("b".into(), &[], no_parent());
+        // Corrupt the parents and the children into cycles
+        a.relocate(Arc::downgrade(&b) as _, "a".into()).unwrap();
+        b.relocate(Arc::downgrade(&a) as _, "b".into()).unwrap();
+        a.attach_child(b.clone()).unwrap();
+        b.attach_child(a.clone()).unwrap();
+
+        // The truncated path is not absolute
+        let path = a.path();
+        assert!(!path.starts_with('/'));
+        assert_eq!(path.split('/').count(), SysTree::MAX_DEPTH);
+        assert_eq!(a.ancestors().count(), SysTree::MAX_DEPTH);
+
+        let mut max_visited_depth = 0;
+        let res = a.walk(usize::MAX, SysWalkOrder::Pre, &mut |_node, depth| {
+            max_visited_depth = max_visited_depth.max(depth);
+            ControlFlow::Continue(())
+        });
+        assert_eq!(res, ControlFlow::Continue(()));
+        assert_eq!(max_visited_depth, SysTree::MAX_DEPTH);
+
+        // Break the cycles so that the nodes can be dropped
+        a.detach_child("b").unwrap();
+        b.detach_child("a").unwrap();
+    }
+
+    #[ktest]
+    fn node_type_names_round_trip() {
+        let types = [
+            (SysNodeType::Branch, "branch"),
+            (SysNodeType::Leaf, "leaf"),
+            (SysNodeType::Symlink, "symlink"),
+        ];
+        for (type_, name) in types {
+            assert_eq!(type_.as_str(), name);
+            assert_eq!(name.parse::<SysNodeType>(), Ok(type_));
+        }
+        assert_eq!("Branch".parse::<SysNodeType>(), Err(Error::InvalidArgument));
+        assert_eq!("".parse::<SysNodeType>(), Err(Error::InvalidArgument));
+    }
+
+    #[ktest]
+    fn read_attrs_as_integers() {
+        let leaf = new_leaf(&[
+            ("offset", SysAttrFlags::CAN_READ),
+            ("mtu", SysAttrFlags::CAN_READ),
+            ("name", SysAttrFlags::CAN_READ),
+        ]);
+        let values: [(&str, &'static [u8]); 3] =
+            [("offset", b"-42"), ("mtu", b" 1500\n"), ("name", b"eth0\n")];
+        for (name, value) in values {
+            leaf.set_handler(name, Arc::new(SysStoredValue::new(value)))
+                .unwrap();
+        }
+
+        assert_eq!(leaf.read_attr_i64("offset"), Ok(-42));
+        assert_eq!(leaf.read_attr_u64("offset"), Err(Error::InvalidArgument));
+        assert_eq!(leaf.read_attr_i64("mtu"), Ok(1500));
+        assert_eq!(leaf.read_attr_u64("mtu"), Ok(1500));
+        assert_eq!(leaf.read_attr_i64("name"), Err(Error::InvalidArgument));
+        assert_eq!(leaf.read_attr_u64("name"), Err(Error::InvalidArgument));
+        assert_eq!(leaf.read_attr_i64("missing"), Err(Error::NotFound));
+    }
+
+    #[ktest]
+    fn stream_attr_of_large_computed_value() {
+        const VALUE_LEN: usize = 5 * PAGE_SIZE + 7;
+
+        let leaf = new_leaf(&[("trace", SysAttrFlags::CAN_READ | SysAttrFlags::IS_BINARY)]);
+        let trace = |offset: usize, writer: &mut VmWriter<Fallible>| -> Result<usize> {
+            let chunk: Vec<u8> = (offset..VALUE_LEN)
+                .take(writer.avail())
+                .map(pattern_byte)
+                .collect();
+            let mut reader = VmReader::from(chunk.as_slice());
+            writer
+                .write_fallible(&mut reader)
+                .map_err(|_| Error::BadAddress)
+        };
+        leaf.set_handler("trace", Arc::new(trace)).unwrap();
+
+        let mut value = Vec::new();
+        let mut num_chunks = 0;
+        leaf.stream_attr("trace", &mut |chunk| {
+            assert!(chunk.len() <= PAGE_SIZE);
+            value.extend_from_slice(chunk);
+            num_chunks += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(num_chunks, 6);
+        assert_eq!(value.len(), VALUE_LEN);
+        assert!(value
+            .iter()
+            .enumerate()
+            .all(|(offset, byte)| *byte == pattern_byte(offset)));
+
+        // The streaming stops at the first error of the sink
+        let mut num_chunks = 0;
+        let res = leaf.stream_attr("trace", &mut |_chunk| {
+            num_chunks += 1;
+            Err(Error::NoSpace)
+        });
+        assert_eq!(res, Err(Error::NoSpace));
+        assert_eq!(num_chunks, 1);
+    }
+
+    /// A sensor whose attributes are unreadable while the hardware is offline.
+    #[derive(Debug)]
+    struct Sensor {
+        leaf: Arc<SysLeafNode>,
+        is_online: AtomicBool,
+    }
+
+    impl SysObj for Sensor {
+        fn id(&self) -> &SysNodeId {
+            self.leaf.id()
+        }
+
+        fn type_(&self) -> SysNodeType {
+            SysNodeType::Leaf
+        }
+
+        fn name(&self) -> SysStr {
+            self.leaf.name()
+        }
+
+        fn parent(&self) -> Weak<dyn SysBranchNode> {
+            self.leaf.parent()
+        }
+    }
+
+    impl SysNode for Sensor {
+        fn node_attrs(&self) -> Arc<SysAttrSet> {
+            self.leaf.node_attrs()
+        }
+
+        fn read_attr(&self, name: &str, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+            self.leaf.read_attr(name, offset, writer)
+        }
+
+        fn write_attr(&self, name: &str, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+            self.leaf.write_attr(name, offset, reader)
+        }
+
+        fn attr_is_readable_now(&self, name: &str) -> bool {
+            self.is_online.load(Ordering::Relaxed) && self.leaf.attr_is_readable_now(name)
+        }
+    }
+
+    #[ktest]
+    fn attr_readability_follows_dynamic_state() {
+        let leaf = new_leaf(&[
+            ("temp", SysAttrFlags::CAN_READ),
+            ("reset", SysAttrFlags::CAN_WRITE),
+        ]);
+        // By default, the readability follows the flags
+        assert!(leaf.attr_is_readable_now("temp"));
+        assert!(!leaf.attr_is_readable_now("reset"));
+        assert!(!leaf.attr_is_readable_now("missing"));
+
+        let sensor = Sensor {
+            leaf,
+            is_online: AtomicBool::new(true),
+        };
+        assert!(sensor.attr_is_readable_now("temp"));
+        sensor.is_online.store(false, Ordering::Relaxed);
+        assert!(!sensor.attr_is_readable_now("temp"));
+        sensor.is_online.store(true, Ordering::Relaxed);
+        assert!(sensor.attr_is_readable_now("temp"));
+        assert!(!sensor.attr_is_readable_now("reset"));
+    }
+
+    #[ktest]
+    fn append_only_attr_accumulates_writes() {
+        let leaf = FixtureLeaf::new("audit".into(), &[], no_parent());
+        let flags = SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE | SysAttrFlags::IS_APPEND_ONLY;
+        leaf.add_attr(SysAttr::new(0, "log".into(), flags)).unwrap();
+
+        leaf.store_attr("log", "login root\n").unwrap();
+        // The offset is ignored
+        let mut reader = VmReader::from(b"logout root\n".as_slice()).to_fallible();
+        leaf.write_attr("log", 0, &mut reader).unwrap();
+        assert_eq!(leaf.show_attr("log").unwrap(), "login root\nlogout root\n");
+
+        // The accumulated content is bounded
+        let entry = vec![b'x'; PAGE_SIZE];
+        assert_eq!(leaf.store_attr_bytes("log", &entry), Err(Error::TooLarge));
+        assert_eq!(leaf.show_attr("log").unwrap(), "login root\nlogout root\n");
+    }
+
+    #[ktest]
+    fn child_by_name_present_and_absent() {
+        let tree = TreeBuilder::new()
+            .add("/block/sda", SysNodeType::Leaf, &[])
+            .add_symlink("/block/disk", "sda")
+            .build()
+            .unwrap();
+        let block = tree.lookup("/block").unwrap().arc_as_branch().unwrap();
+
+        let sda = block.child("sda").unwrap();
+        assert!(Arc::ptr_eq(&sda, &tree.lookup("/block/sda").unwrap()));
+        assert_eq!(block.child("disk").unwrap().type_(), SysNodeType::Symlink);
+
+        assert!(block.child("sdb").is_none());
+        assert!(block.child("").is_none());
+        let mut visited_absent = false;
+        block.visit_child_with("sdb", &mut |child_opt| {
+            assert!(child_opt.is_none());
+            visited_absent = true;
+        });
+        assert!(visited_absent);
+    }
+
+    #[ktest]
+    fn read_textual_attr_whose_handler_ignores_offset() {
+        let leaf = new_leaf(&[("uptime", SysAttrFlags::CAN_READ)]);
+        // The offset is ignored, so every read returns the whole value
+        let uptime = |_offset: usize, writer: &mut VmWriter<Fallible>| -> Result<usize> {
+            let mut reader = VmReader::from(b"42\n".as_slice());
+            writer
+                .write_fallible(&mut reader)
+                .map_err(|_| Error::BadAddress)
+        };
+        leaf.set_handler("uptime", Arc::new(uptime)).unwrap();
+
+        assert_eq!(leaf.show_attr("uptime").unwrap(), "42\n");
+        assert_eq!(leaf.read_attr_u64("uptime").unwrap(), 42);
+        let mut num_chunks = 0;
+        leaf.stream_attr("uptime", &mut |_chunk| {
+            num_chunks += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(num_chunks, 1);
+    }
+}
@@ -0,0 +1,615 @@
+//! Utilities for building `SysTree`s in tests.
+//!
+//! This module is only available with the `test-util` feature
+//! and in the tests of this crate.
+
+use crate::{
+    attr::{SysAttr, SysAttrFlags, SysAttrGroup, SysAttrSet},
+    event::SysEvent,
+    node::{SysBranchNode, SysNode, SysNodeId, SysNodeType, SysObj, SysSymlink},
+    tree::SysTree,
+    utils::{SysBranchNodeFields, SysNormalNodeFields, SysStoredValue},
+    Error, Result, SysAttrSetBuilder, SysStr,
+};
+
+/// A builder that constructs a `SysTree` from a declarative spec.
+///
+/// Each entry of the spec describes a node with its path, its type,
+/// and the names of its attributes.
+/// The parents of a node are created implicitly as branching nodes
+/// if they are not in the spec.
+/// All the attributes are readable and writable, and initially empty.
+///
+/// # Example
+///
+/// ```ignore
+/// let tree = TreeBuilder::new()
+///     .add("/devices", SysNodeType::Branch, &[])
+///     .add("/devices/cpu0", SysNodeType::Leaf, &["online"])
+///     .add_symlink("/class/cpu/cpu0", "../../devices/cpu0")
+///     .build()?;
+/// ```
+pub struct TreeBuilder {
+    specs: Vec<NodeSpec>,
+}
+
+struct NodeSpec {
+    path: String,
+    type_: SysNodeType,
+    attrs: Vec<&'static str>,
+    target_path: Option<String>,
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        Self { specs: Vec::new() }
+    }
+
+    /// Adds a branching or leaf node to the spec.
+    pub fn add(mut self, path: &str, type_: SysNodeType, attrs: &[&'static str]) -> Self {
+        debug_assert!(type_ != SysNodeType::Symlink);
+        self.specs.push(NodeSpec {
+            path: path.to_string(),
+            type_,
+            attrs: attrs.to_vec(),
+            target_path: None,
+        });
+        self
+    }
+
+    /// Adds a symlink node to the spec.
+    pub fn add_symlink(mut self, path: &str, target_path: &str) -> Self {
+        self.specs.push(NodeSpec {
+            path: path.to_string(),
+            type_: SysNodeType::Symlink,
+            attrs: Vec::new(),
+            target_path: Some(target_path.to_string()),
+        });
+        self
+    }
+
+    /// Builds a `SysTree` from the spec.
+    ///
+    /// The nodes are added to the tree in the order of their depths,
+    /// so the parent pointers of all the nodes are wired up.
+    pub fn build(mut self) -> Result<SysTree> {
+        let tree = SysTree::new();
+        let mut branches: BTreeMap<String, Arc<FixtureBranch>> = BTreeMap::new();
+
+        self.specs.sort_by_key(|spec| depth_of(&spec.path));
+        for spec in self.specs {
+            let (parent_path, name) = split_path(&spec.path)?;
+            let parent = ensure_branch(&tree, &mut branches, parent_path)?;
+            if branches.contains_key(&spec.path) {
+                // The node has been created implicitly as a parent
+                continue;
+            }
+
+            let name: SysStr = name.to_string().into();
+            let parent_weak = parent_weak_of(&tree, parent.as_ref());
+            let node: Arc<dyn SysObj> = match spec.type_ {
+                SysNodeType::Branch => {
+                    let branch = FixtureBranch::new(name, &spec.attrs, parent_weak);
+                    branches.insert(spec.path.clone(), branch.clone());
+                    branch
+                }
+                SysNodeType::Leaf => FixtureLeaf::new(name, &spec.attrs, parent_weak),
+                SysNodeType::Symlink => {
+                    let target_path = spec.target_path.unwrap_or_default();
+                    FixtureSymlink::new(name, target_path, parent_weak)
+                }
+            };
+            add_child_to(&tree, parent.as_ref(), node)?;
+        }
+
+        Ok(tree)
+    }
+}
+
+fn depth_of(path: &str) -> usize {
+    path.split('/').filter(|name| !name.is_empty()).count()
+}
+
+/// Splits a path into the path of the parent and the name.
+fn split_path(path: &str) -> Result<(&str, &str)> {
+    let path = path.trim_end_matches('/');
+    let (parent_path, name) = path.rsplit_once('/').ok_or(Error::InvalidArgument)?;
+    if name.is_empty() {
+        return Err(Error::InvalidArgument);
+    }
+    Ok((parent_path, name))
+}
+
+/// Returns the branching node of the given path,
+/// creating it (and its ancestors) if it does not exist.
+///
+/// `None` stands for the root node.
+fn ensure_branch(
+    tree: &SysTree,
+    branches: &mut BTreeMap<String, Arc<FixtureBranch>>,
+    path: &str,
+) -> Result<Option<Arc<FixtureBranch>>> {
+    if path.is_empty() {
+        return Ok(None);
+    }
+    if let Some(branch) = branches.get(path) {
+        return Ok(Some(branch.clone()));
+    }
+
+    let (parent_path, name) = split_path(path)?;
+    let parent = ensure_branch(tree, branches, parent_path)?;
+    let parent_weak = parent_weak_of(tree, parent.as_ref());
+    let branch = FixtureBranch::new(name.to_string().into(), &[], parent_weak);
+    add_child_to(tree, parent.as_ref(), branch.clone())?;
+    branches.insert(path.to_string(), branch.clone());
+    Ok(Some(branch))
+}
+
+fn parent_weak_of(tree: &SysTree, parent: Option<&Arc<FixtureBranch>>) -> Weak<dyn SysBranchNode> {
+    match parent {
+        Some(parent) => Arc::downgrade(parent) as Weak<dyn SysBranchNode>,
+        None => Arc::downgrade(tree.root()) as Weak<dyn SysBranchNode>,
+    }
+}
+
+fn add_child_to(
+    tree: &SysTree,
+    parent: Option<&Arc<FixtureBranch>>,
+    child: Arc<dyn SysObj>,
+) -> Result<()> {
+    match parent {
+        Some(parent) => parent.fields.add_child(child),
+        None => {
+            // The children of the root must be nodes, not symlinks
+            let child = child.arc_as_node().ok_or(Error::InvalidArgument)?;
+            tree.root().add_child(child)
+        }
+    }
+}
+
+fn new_attr_set(attrs: &[&'static str]) -> SysAttrSet {
+    let mut builder = SysAttrSetBuilder::new();
+    for attr in attrs {
+        builder.add(SysStr::Borrowed(attr), SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE);
+    }
+    builder.build()
+}
+
+/// The values of the attributes of a fixture node.
+///
+/// The size of each value is bounded by `SysStoredValue::DEFAULT_MAX_LEN`.
+struct AttrValues(RwMutex<BTreeMap<String, SysStoredValue>>);
+
+impl AttrValues {
+    fn new() -> Self {
+        Self(RwMutex::new(BTreeMap::new()))
+    }
+
+    fn read(&self, name: &str, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        let values = self.0.read();
+        let Some(value) = values.get(name) else {
+            return Ok(0);
+        };
+        value.read(offset, writer)
+    }
+
+    fn write(&self, name: &str, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        let mut values = self.0.write();
+        let value = values
+            .entry(name.to_string())
+            .or_insert_with(|| SysStoredValue::new(&[]));
+        value.write(offset, reader)
+    }
+
+    fn append(&self, name: &str, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        let mut values = self.0.write();
+        let value = values
+            .entry(name.to_string())
+            .or_insert_with(|| SysStoredValue::new(&[]));
+        value.append(reader)
+    }
+}
+
+/// A branching node built by `TreeBuilder`.
+pub struct FixtureBranch {
+    fields: SysBranchNodeFields<dyn SysObj>,
+    values: AttrValues,
+    parent: RwMutex<Weak<dyn SysBranchNode>>,
+}
+
+impl FixtureBranch {
+    /// Creates a branching node whose attributes are readable and writable.
+    ///
+    /// The node is not attached to its parent.
+    pub fn new(name: SysStr, attrs: &[&'static str], parent: Weak<dyn SysBranchNode>) -> Arc<Self> {
+        Arc::new(Self {
+            fields: SysBranchNodeFields::new(name, new_attr_set(attrs)),
+            values: AttrValues::new(),
+            parent: RwMutex::new(parent),
+        })
+    }
+}
+
+impl Debug for FixtureBranch {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FixtureBranch")
+            .field("name", &self.fields.name())
+            .finish()
+    }
+}
+
+impl SysObj for FixtureBranch {
+    fn id(&self) -> &SysNodeId {
+        self.fields.id()
+    }
+
+    fn type_(&self) -> SysNodeType {
+        SysNodeType::Branch
+    }
+
+    fn name(&self) -> SysStr {
+        self.fields.name()
+    }
+
+    fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.parent.read().clone()
+    }
+
+    fn relocate(&self, new_parent: Weak<dyn SysBranchNode>, new_name: SysStr) -> Result<()> {
+        *self.parent.write() = new_parent;
+        self.fields.set_name(new_name);
+        Ok(())
+    }
+
+    fn as_node(&self) -> Option<&dyn SysNode> {
+        Some(self)
+    }
+
+    fn as_branch(&self) -> Option<&dyn SysBranchNode> {
+        Some(self)
+    }
+
+    fn arc_as_node(self: Arc<Self>) -> Option<Arc<dyn SysNode>> {
+        Some(self)
+    }
+
+    fn arc_as_branch(self: Arc<Self>) -> Option<Arc<dyn SysBranchNode>> {
+        Some(self)
+    }
+}
+
+impl SysNode for FixtureBranch {
+    fn node_attrs(&self) -> Arc<SysAttrSet> {
+        self.fields.attr_set()
+    }
+
+    fn replace_node_attrs(&self, new_attrs: SysAttrSet) -> Result<()> {
+        self.fields.replace_attr_set(new_attrs);
+        Ok(())
+    }
+
+    fn add_attr(&self, attr: SysAttr) -> Result<()> {
+        self.fields.add_attr(attr.name().clone(), attr.flags())
+    }
+
+    fn read_attr(&self, name: &str, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        if !self.node_attrs().contains(name) {
+            return Err(Error::NotFound);
+        }
+        self.values.read(name, offset, writer)
+    }
+
+    fn write_attr(&self, name: &str, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        let Some(flags) = self.attr_flags(name) else {
+            return Err(Error::NotFound);
+        };
+        if flags.contains(SysAttrFlags::IS_APPEND_ONLY) {
+            return self.values.append(name, reader);
+        }
+        self.values.write(name, offset, reader)
+    }
+}
+
+impl SysBranchNode for FixtureBranch {
+    fn visit_child_with(&self, name: &str, f: &mut dyn FnMut(Option<&Arc<dyn SysObj>>)) {
+        let children = self.fields.children.read();
+        f(children.get(name));
+    }
+
+    fn visit_children_with(&self, min_id: u64, f: &mut dyn FnMut(&Arc<dyn SysObj>) -> Option<()>) {
+        let children = self.fields.children.read();
+        for child in children.values().filter(|child| child.id().as_u64() >= min_id) {
+            if f(child).is_none() {
+                break;
+            }
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.fields.generation()
+    }
+
+    fn children_sorted_by_name(&self) -> Vec<Arc<dyn SysObj>> {
+        self.fields.children_sorted()
+    }
+
+    fn detach_child(&self, name: &str) -> Result<Arc<dyn SysObj>> {
+        self.fields.remove_child(name).ok_or(Error::NotFound)
+    }
+
+    fn attach_child(&self, child: Arc<dyn SysObj>) -> Result<()> {
+        self.fields.add_child(child)
+    }
+
+    fn rename_child(&self, old_name: &str, new_name: SysStr) -> Result<()> {
+        self.fields.rename_child(old_name, new_name)
+    }
+}
+
+/// A leaf node built by `TreeBuilder`.
+pub struct FixtureLeaf {
+    fields: SysNormalNodeFields,
+    values: AttrValues,
+    attr_groups: Vec<SysAttrGroup>,
+    parent: RwMutex<Weak<dyn SysBranchNode>>,
+    is_internal: bool,
+}
+
+impl FixtureLeaf {
+    /// Creates a leaf node whose attributes are readable and writable.
+    ///
+    /// The node is not attached to its parent.
+    pub fn new(name: SysStr, attrs: &[&'static str], parent: Weak<dyn SysBranchNode>) -> Arc<Self> {
+        Self::do_new(name, attrs, Vec::new(), parent, false)
+    }
+
+    /// Creates a leaf node whose attributes are organized in groups
+    /// (see `SysNode::attr_groups`).
+    ///
+    /// The attributes of the groups must be among `attrs`.
+    /// The node is not attached to its parent.
+    pub fn new_with_groups(
+        name: SysStr,
+        attrs: &[&'static str],
+        attr_groups: Vec<SysAttrGroup>,
+        parent: Weak<dyn SysBranchNode>,
+    ) -> Arc<Self> {
+        Self::do_new(name, attrs, attr_groups, parent, false)
+    }
+
+    /// Creates an internal leaf node (see `SysObj::is_internal`).
+    ///
+    /// The node is not attached to its parent.
+    pub fn new_internal(name: SysStr, attrs: &[&'static str], parent: Weak<dyn SysBranchNode>) -> Arc<Self> {
+        Self::do_new(name, attrs, Vec::new(), parent, true)
+    }
+
+    fn do_new(
+        name: SysStr,
+        attrs: &[&'static str],
+        attr_groups: Vec<SysAttrGroup>,
+        parent: Weak<dyn SysBranchNode>,
+        is_internal: bool,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            fields: SysNormalNodeFields::new(name, new_attr_set(attrs)),
+            values: AttrValues::new(),
+            attr_groups,
+            parent: RwMutex::new(parent),
+            is_internal,
+        })
+    }
+}
+
+impl Debug for FixtureLeaf {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FixtureLeaf")
+            .field("name", &self.fields.name())
+            .finish()
+    }
+}
+
+impl SysObj for FixtureLeaf {
+    fn id(&self) -> &SysNodeId {
+        self.fields.id()
+    }
+
+    fn type_(&self) -> SysNodeType {
+        SysNodeType::Leaf
+    }
+
+    fn name(&self) -> SysStr {
+        self.fields.name()
+    }
+
+    fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.parent.read().clone()
+    }
+
+    fn relocate(&self, new_parent: Weak<dyn SysBranchNode>, new_name: SysStr) -> Result<()> {
+        *self.parent.write() = new_parent;
+        self.fields.set_name(new_name);
+        Ok(())
+    }
+
+    fn is_internal(&self) -> bool {
+        self.is_internal
+    }
+
+    fn as_node(&self) -> Option<&dyn SysNode> {
+        Some(self)
+    }
+
+    fn arc_as_node(self: Arc<Self>) -> Option<Arc<dyn SysNode>> {
+        Some(self)
+    }
+}
+
+impl SysNode for FixtureLeaf {
+    fn node_attrs(&self) -> Arc<SysAttrSet> {
+        self.fields.attr_set()
+    }
+
+    fn attr_groups(&self) -> &[SysAttrGroup] {
+        &self.attr_groups
+    }
+
+    fn replace_node_attrs(&self, new_attrs: SysAttrSet) -> Result<()> {
+        self.fields.replace_attr_set(new_attrs);
+        Ok(())
+    }
+
+    fn add_attr(&self, attr: SysAttr) -> Result<()> {
+        self.fields.add_attr(attr.name().clone(), attr.flags())
+    }
+
+    fn read_attr(&self, name: &str, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        if !self.node_attrs().contains(name) {
+            return Err(Error::NotFound);
+        }
+        self.values.read(name, offset, writer)
+    }
+
+    fn write_attr(&self, name: &str, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        let Some(flags) = self.attr_flags(name) else {
+            return Err(Error::NotFound);
+        };
+        if flags.contains(SysAttrFlags::IS_APPEND_ONLY) {
+            return self.values.append(name, reader);
+        }
+        self.values.write(name, offset, reader)
+    }
+}
+
+/// A symlink node built by `TreeBuilder`.
+#[derive(Debug)]
+pub struct FixtureSymlink {
+    id: SysNodeId,
+    name: RwMutex<SysStr>,
+    target_path: String,
+    parent: RwMutex<Weak<dyn SysBranchNode>>,
+}
+
+impl FixtureSymlink {
+    /// Creates a symlink node.
+    ///
+    /// The node is not attached to its parent.
+    pub fn new(name: SysStr, target_path: String, parent: Weak<dyn SysBranchNode>) -> Arc<Self> {
+        Arc::new(Self {
+            id: SysNodeId::new(),
+            name: RwMutex::new(name),
+            target_path,
+            parent: RwMutex::new(parent),
+        })
+    }
+}
+
+impl SysObj for FixtureSymlink {
+    fn id(&self) -> &SysNodeId {
+        &self.id
+    }
+
+    fn type_(&self) -> SysNodeType {
+        SysNodeType::Symlink
+    }
+
+    fn name(&self) -> SysStr {
+        self.name.read().clone()
+    }
+
+    fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.parent.read().clone()
+    }
+
+    fn relocate(&self, new_parent: Weak<dyn SysBranchNode>, new_name: SysStr) -> Result<()> {
+        *self.parent.write() = new_parent;
+        *self.name.write() = new_name;
+        Ok(())
+    }
+}
+
+impl SysSymlink for FixtureSymlink {
+    fn target_path(&self) -> &str {
+        &self.target_path
+    }
+}
+
+/// An observer that records the events it receives, in order.
+pub struct EventRecorder {
+    events: Mutex<Vec<SysEvent>>,
+}
+
+impl EventRecorder {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            events: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Returns the recorded events.
+    pub fn events(&self) -> Vec<SysEvent> {
+        self.events.lock().clone()
+    }
+
+    /// Returns the paths of the recorded events.
+    pub fn paths(&self) -> Vec<String> {
+        self.events
+            .lock()
+            .iter()
+            .map(|event| event.path().to_string())
+            .collect()
+    }
+
+    /// Forgets the recorded events.
+    pub fn clear(&self) {
+        self.events.lock().clear();
+    }
+}
+
+impl Observer<SysEvent> for EventRecorder {
+    fn on_events(&self, event: &SysEvent) {
+        self.events.lock().push(event.clone());
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+
+    #[ktest]
+    fn build_three_level_tree() {
+        let tree = TreeBuilder::new()
+            .add("/devices", SysNodeType::Branch, &["uevent"])
+            .add("/devices/system/cpu0", SysNodeType::Leaf, &["online", "freq"])
+            .add_symlink("/class/cpu/cpu0", "../../devices/system/cpu0")
+            .build()
+            .unwrap();
+
+        let cpu0 = tree.lookup_node("/devices/system/cpu0").unwrap();
+        assert_eq!(cpu0.type_(), SysNodeType::Leaf);
+        assert_eq!(cpu0.path(), "/devices/system/cpu0");
+        assert!(cpu0.node_attrs().contains("online"));
+        assert!(cpu0.node_attrs().contains("freq"));
+        cpu0.store_attr("online", "1").unwrap();
+        assert_eq!(cpu0.show_attr("online").unwrap(), "1");
+
+        // The parents not in the spec are created implicitly
+        let system = tree.lookup("/devices/system").unwrap();
+        assert_eq!(system.type_(), SysNodeType::Branch);
+        let parent = cpu0.parent().upgrade().unwrap();
+        assert_eq!(parent.id(), system.id());
+
+        let link = tree.lookup("/class/cpu/cpu0").unwrap();
+        assert_eq!(link.type_(), SysNodeType::Symlink);
+        assert_eq!(link.path(), "/class/cpu/cpu0");
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[ktest]
+    fn build_rejects_symlinks_under_root() {
+        let res = TreeBuilder::new().add_symlink("/cpu0", "devices/cpu0").build();
+        assert_eq!(res.err(), Some(Error::InvalidArgument));
+    }
+}
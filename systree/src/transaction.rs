@@ -0,0 +1,298 @@
+//! Transactions that mutate multiple nodes in a `SysTree` at once.
+
+use crate::{
+    event::{SysEventAction, SysEventKv},
+    node::{SysBranchNode, SysNode, SysObj},
+    tree::{node_type_details, SysTree},
+    Error, Result, SysStr,
+};
+
+/// A transaction that mutates multiple nodes in a `SysTree`,
+/// created by `SysTree::transaction`.
+///
+/// Registering a device often touches several nodes
+/// (e.g., adding the device and adding its symlink in a class),
+/// which should appear atomically to the observers of the tree.
+/// A transaction batches such mutations,
+/// applies them in order upon commit,
+/// and publishes their events only after all of them have succeeded.
+///
+/// Note that only the events are deferred, not the mutations.
+/// The mutations are applied to the nodes one by one during the commit,
+/// so a reader that does not observe events (e.g., a sysfs lookup)
+/// may see a partially-applied transaction.
+///
+/// If any mutation fails, the applied ones are rolled back in reverse order
+/// and no events are published.
+/// Rolling back may fail as well (e.g., if a node with the same name
+/// has been added concurrently), in which case the tree is left partially applied
+/// and the failure is reported in `SysTxError`.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut tx = systree::singleton().transaction();
+/// tx.add(&devices, device.clone())
+///     .add(&class_net, device_link.clone())
+///     .change(parent.clone(), Vec::new());
+/// tx.commit()?;
+/// ```
+pub struct SysTransaction<'a> {
+    tree: &'a SysTree,
+    ops: Vec<TxOp>,
+}
+
+enum TxOp {
+    Add {
+        parent: Arc<dyn SysBranchNode>,
+        child: Arc<dyn SysObj>,
+    },
+    Remove {
+        parent: Arc<dyn SysBranchNode>,
+        name: SysStr,
+    },
+    Change {
+        node: Arc<dyn SysNode>,
+        details: Vec<SysEventKv>,
+    },
+}
+
+// A mutation that has been applied, which can be rolled back.
+enum AppliedOp {
+    Added {
+        parent: Arc<dyn SysBranchNode>,
+        name: SysStr,
+    },
+    Removed {
+        parent: Arc<dyn SysBranchNode>,
+        child: Arc<dyn SysObj>,
+    },
+    Changed,
+}
+
+impl<'a> SysTransaction<'a> {
+    pub(crate) fn new(tree: &'a SysTree) -> Self {
+        Self {
+            tree,
+            ops: Vec::new(),
+        }
+    }
+
+    /// Adds a child to a branching node (see `SysBranchNode::attach_child`),
+    /// publishing an `Add` event.
+    pub fn add(&mut self, parent: &Arc<dyn SysBranchNode>, child: Arc<dyn SysObj>) -> &mut Self {
+        self.ops.push(TxOp::Add {
+            parent: parent.clone(),
+            child,
+        });
+        self
+    }
+
+    /// Removes a child from a branching node (see `SysBranchNode::detach_child`),
+    /// publishing a `Remove` event.
+    pub fn remove(&mut self, parent: &Arc<dyn SysBranchNode>, name: SysStr) -> &mut Self {
+        self.ops.push(TxOp::Remove {
+            parent: parent.clone(),
+            name,
+        });
+        self
+    }
+
+    /// Publishes a `Change` event for a node that has been changed
+    /// (e.g., a counter of the node has been bumped).
+    pub fn change(&mut self, node: Arc<dyn SysNode>, details: Vec<SysEventKv>) -> &mut Self {
+        self.ops.push(TxOp::Change { node, details });
+        self
+    }
+
+    /// Commits the transaction.
+    ///
+    /// On failure, the transaction is rolled back
+    /// and the error of the failed mutation is returned,
+    /// along with the number of the mutations that cannot be rolled back.
+    pub fn commit(self) -> core::result::Result<(), SysTxError> {
+        let mut applied_ops = Vec::with_capacity(self.ops.len());
+        let mut events = Vec::with_capacity(self.ops.len());
+        for op in self.ops {
+            match apply_op(op) {
+                Ok((applied_op, event)) => {
+                    applied_ops.push(applied_op);
+                    events.push(event);
+                }
+                Err(error) => {
+                    let num_rollback_failures = applied_ops
+                        .into_iter()
+                        .rev()
+                        .map(rollback_op)
+                        .filter(|res| res.is_err())
+                        .count();
+                    return Err(SysTxError {
+                        error,
+                        num_rollback_failures,
+                    });
+                }
+            }
+        }
+
+        let event_hub = self.tree.event_hub();
+        for (path, action, details) in events {
+            if path.starts_with('/') {
+                event_hub.publish_event_at(path.into(), action, details);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error of a failed `SysTransaction::commit`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SysTxError {
+    /// The error of the mutation that fails the transaction.
+    pub error: Error,
+    /// The number of the applied mutations that cannot be rolled back.
+    ///
+    /// If this is not zero, the tree is left partially applied.
+    pub num_rollback_failures: usize,
+}
+
+impl SysTxError {
+    /// Returns whether the transaction has been rolled back completely.
+    pub fn is_rolled_back(&self) -> bool {
+        self.num_rollback_failures == 0
+    }
+}
+
+impl From<Error> for SysTxError {
+    fn from(error: Error) -> Self {
+        // The error occurs before any mutation is applied
+        Self {
+            error,
+            num_rollback_failures: 0,
+        }
+    }
+}
+
+impl From<SysTxError> for Error {
+    fn from(tx_error: SysTxError) -> Self {
+        tx_error.error
+    }
+}
+
+fn apply_op(op: TxOp) -> Result<(AppliedOp, (String, SysEventAction, Vec<SysEventKv>))> {
+    match op {
+        TxOp::Add { parent, child } => {
+            let name = child.name();
+            parent.attach_child(child.clone())?;
+            let event = (child.path(), SysEventAction::Add, node_type_details(child.as_ref()));
+            Ok((AppliedOp::Added { parent, name }, event))
+        }
+        TxOp::Remove { parent, name } => {
+            // The path is captured before it can no longer be obtained
+            let path = parent.child(&name).map(|child| child.path());
+            let child = parent.detach_child(&name)?;
+            let path = path.unwrap_or_else(|| child.path());
+            let event = (path, SysEventAction::Remove, node_type_details(child.as_ref()));
+            Ok((AppliedOp::Removed { parent, child }, event))
+        }
+        TxOp::Change { node, details } => {
+            let event = (node.path(), SysEventAction::Change, details);
+            Ok((AppliedOp::Changed, event))
+        }
+    }
+}
+
+fn rollback_op(applied_op: AppliedOp) -> Result<()> {
+    // Undoing an applied mutation restores the children of a branching node
+    // to a previous state, which fails only if the children have been
+    // changed concurrently by others.
+    match applied_op {
+        AppliedOp::Added { parent, name } => parent.detach_child(&name).map(|_| ()),
+        AppliedOp::Removed { parent, child } => parent.attach_child(child),
+        AppliedOp::Changed => Ok(()),
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use ostd::prelude::ktest;
+
+    use super::*;
+    use crate::{
+        test_util::{EventRecorder, FixtureLeaf, FixtureSymlink, TreeBuilder},
+        SysEventSelector, SysNodeType,
+    };
+
+    fn new_tree() -> SysTree {
+        TreeBuilder::new()
+            .add("/devices", SysNodeType::Branch, &[])
+            .add("/class/net", SysNodeType::Branch, &[])
+            .build()
+            .unwrap()
+    }
+
+    fn branch_at(tree: &SysTree, path: &str) -> Arc<dyn SysBranchNode> {
+        tree.lookup(path).unwrap().arc_as_branch().unwrap()
+    }
+
+    #[ktest]
+    fn commit_publishes_events_after_all_mutations() {
+        let tree = new_tree();
+        let devices = branch_at(&tree, "/devices");
+        let class_net = branch_at(&tree, "/class/net");
+
+        // Record whether the class symlink is visible when each event is observed
+        struct LinkProbe {
+            class_net: Arc<dyn SysBranchNode>,
+            seen_link: Mutex<Vec<bool>>,
+        }
+        impl Observer<SysEvent> for LinkProbe {
+            fn on_events(&self, _event: &SysEvent) {
+                let has_link = self.class_net.child("eth0").is_some();
+                self.seen_link.lock().push(has_link);
+            }
+        }
+        let probe = Arc::new(LinkProbe {
+            class_net: class_net.clone(),
+            seen_link: Mutex::new(Vec::new()),
+        });
+        let recorder = EventRecorder::new();
+        tree.register_observer(Arc::downgrade(&probe) as _, SysEventSelector::All);
+        tree.register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+
+        let device = FixtureLeaf::new("eth0".into(), &[], Arc::downgrade(&devices));
+        let device_link = FixtureSymlink::new(
+            "eth0".into(),
+            "../../devices/eth0".to_string(),
+            Arc::downgrade(&class_net),
+        );
+        let mut tx = tree.transaction();
+        tx.add(&devices, device).add(&class_net, device_link);
+        assert!(recorder.events().is_empty());
+
+        tx.commit().unwrap();
+        assert_eq!(recorder.paths(), ["/devices/eth0", "/class/net/eth0"]);
+        // Even the first event is published after the symlink is added
+        assert_eq!(*probe.seen_link.lock(), [true, true]);
+    }
+
+    #[ktest]
+    fn failed_commit_rolls_back_without_events() {
+        let tree = new_tree();
+        let devices = branch_at(&tree, "/devices");
+        let class_net = branch_at(&tree, "/class/net");
+        let recorder = EventRecorder::new();
+        tree.register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+
+        let device = FixtureLeaf::new("eth0".into(), &[], Arc::downgrade(&devices));
+        let mut tx = tree.transaction();
+        tx.add(&devices, device)
+            // Fails since there is no such child
+            .remove(&class_net, "eth0".into());
+
+        let tx_error = tx.commit().unwrap_err();
+        assert_eq!(tx_error.error, Error::NotFound);
+        assert!(tx_error.is_rolled_back());
+        assert!(devices.child("eth0").is_none());
+        assert!(recorder.events().is_empty());
+    }
+}
@@ -1,16 +1,56 @@
-use crate::utils::SysBranchNodeFields;
+use crate::event::is_path_in_subtree;
+use crate::transaction::{SysTransaction, SysTxError};
+use crate::utils::{layout_epoch, validate_name, SysBranchNodeFields};
 
 /// A tree structure to expose the system state.
 pub struct SysTree {
     root: Arc<SysTreeRoot>,
+    // The hub also keeps the registered subsystems (see `register_subsystem`),
+    // so that the published events carry the subsystems of their paths.
     event_hub: SysEventHub,
+    // The nodes whose reference counts are tracked,
+    // indexed by the node IDs.
+    #[cfg(feature = "debug-refcounts")]
+    tracked_nodes: Mutex<BTreeMap<SysNodeId, Weak<dyn SysObj>>>,
+    // The cached paths of branching nodes (see `path_of`).
+    path_cache: Mutex<PathCache>,
+}
+
+// The paths of branching nodes, indexed by the node IDs.
+//
+// The paths are valid within a layout epoch (see `utils::layout_epoch`),
+// so the whole cache is dropped once the epoch advances.
+struct PathCache {
+    epoch: u64,
+    paths: BTreeMap<SysNodeId, String>,
+}
+
+impl PathCache {
+    // The maximum number of the cached paths
+    const CAPACITY: usize = 1024;
 }
 
 impl SysTree {
+    /// The path of the root node.
+    pub const ROOT_PATH: &'static str = "/";
+
+    /// The maximum depth of the nodes in the tree.
+    ///
+    /// The methods that walk up or down the tree
+    /// (e.g., `SysObj::path` and `SysTree::find_with_attr`)
+    /// stop at this depth, so that a corrupted tree cannot hang them.
+    pub const MAX_DEPTH: usize = 64;
+
     pub(crate) fn new() -> Self {
         Self {
             root: SysTreeRoot::new(),
             event_hub: SysEventHub::new(),
+            #[cfg(feature = "debug-refcounts")]
+            tracked_nodes: Mutex::new(BTreeMap::new()),
+            path_cache: Mutex::new(PathCache {
+                epoch: 0,
+                paths: BTreeMap::new(),
+            }),
         }
     }
 
@@ -18,29 +58,771 @@ impl SysTree {
         &self.root
     }
 
+    /// Returns whether a path refers to the root node.
+    pub fn is_root_path(path: &str) -> bool {
+        path == Self::ROOT_PATH
+    }
+
     pub fn register_observer(&self, observer: Weak<dyn Observer<SysEvent>>, filter: SysEventSelector)
+        -> ObserverHandle
     {
         self.event_hub.register_observer(observer, filter)
     }
     
+    /// Invokes a callback on the first event selected by the filter.
+    ///
+    /// The callback is invoked at most once,
+    /// after which the observer is unregistered automatically.
+    /// This is useful for waiting on a single event (e.g., of a device).
+    ///
+    /// The tree owns the callback until it is invoked.
+    /// If the awaited event may never be published,
+    /// cancel the observer with `unregister_observer_by_handle`
+    /// using the returned handle, which drops the callback without invoking it.
+    /// Otherwise, the callback (and whatever it captures) is kept alive forever.
+    pub fn observe_once<F>(&self, filter: SysEventSelector, f: F) -> ObserverHandle
+    where
+        F: FnOnce(&SysEvent) + Send + Sync + 'static,
+    {
+        let observer = Arc::new(OnceObserver {
+            f: Mutex::new(Some(f)),
+        });
+        self.event_hub.register_one_shot_observer(observer, filter)
+    }
+
     pub fn unregister_observer(&self, observer: Weak<dyn Observer<SysEvent>>) -> Option<Weak<dyn Observer<SysEvent>>>
     {
         self.event_hub.unregister_observer(observer)
     }
+
+    /// Unregisters the observer identified by a handle
+    /// (e.g., one returned by `register_observer` or `observe_once`),
+    /// returning whether the observer was registered.
+    pub fn unregister_observer_by_handle(&self, handle: ObserverHandle) -> bool {
+        self.event_hub.unregister_observer_by_handle(handle)
+    }
     
+    /// Lists the handles of the registered observers
+    /// along with the descriptions of their selectors (see `SysEventSelector::describe`),
+    /// which is intended for diagnostics.
+    pub fn list_observers(&self) -> Vec<(ObserverHandle, String)> {
+        self.event_hub.list_observers()
+    }
+
     pub fn publish_event(&self, node: &dyn SysNode, action: SysEventAction, details: Vec<SysEventKv>) {
         self.event_hub.publish_event(node, action, details)
     }
+
+    /// Registers a subsystem with a default set of attributes.
+    ///
+    /// A subsystem is a branching node at the top level of the tree
+    /// (e.g., `/net`) whose name has been registered with this method.
+    /// The nodes below it are the members of the subsystem.
+    /// The other top-level nodes (e.g., `/devices`) are not subsystems,
+    /// nor are the names registered without top-level nodes of the same names
+    /// until such nodes are added.
+    ///
+    /// Every member node of a subsystem is expected to inherit
+    /// the default attributes of the subsystem,
+    /// which is done by building the attribute set of the member node
+    /// with `SysAttrSetBuilder::with_parent`:
+    ///
+    /// ```ignore
+    /// let default_attrs = tree.subsystem_attrs("net").unwrap();
+    /// let mut builder = SysAttrSetBuilder::with_parent(default_attrs);
+    /// builder.add("carrier".into(), SysAttrFlags::CAN_READ);
+    /// let attr_set = builder.build();
+    /// ```
+    ///
+    /// So controllers need not re-declare the common attributes of a subsystem.
+    pub fn register_subsystem(&self, name: SysStr, default_attrs: Arc<SysAttrSet>) -> Result<()> {
+        self.event_hub.register_subsystem(name, default_attrs)
+    }
+
+    /// Returns the default set of attributes of a registered subsystem.
+    pub fn subsystem_attrs(&self, name: &str) -> Option<Arc<SysAttrSet>> {
+        self.event_hub.subsystem_attrs(name)
+    }
+
+    /// Returns the path of a node in the tree.
+    ///
+    /// This method is equivalent to `node.path()`,
+    /// but the paths of the ancestors are cached by the tree,
+    /// so the path is obtained without walking up to the root in the common case.
+    ///
+    /// The cached paths stay valid until a node is renamed or moved,
+    /// or the children of a branching node change,
+    /// which is tracked for the nodes built on `SysBranchNodeFields`
+    /// and the other fields in `utils`.
+    /// So the path of a node is only cached
+    /// if all of its ancestors maintain their generations
+    /// (see `SysBranchNode::generation`);
+    /// otherwise, this method falls back to `node.path()`.
+    pub fn path_of(&self, node: &dyn SysObj) -> String {
+        let Some(parent) = node.parent().upgrade() else {
+            return node.path();
+        };
+        if parent.is_root() {
+            return format!("/{}", node.name());
+        }
+        match self.cached_branch_path(&parent) {
+            Some(parent_path) => format!("{}/{}", parent_path, node.name()),
+            None => node.path(),
+        }
+    }
+
+    fn cached_branch_path(&self, branch: &Arc<dyn SysBranchNode>) -> Option<String> {
+        // The epoch is loaded before the path is computed,
+        // so a path computed during a change of the layout is dropped later
+        let epoch = layout_epoch();
+        {
+            let cache = self.path_cache.lock();
+            if cache.epoch == epoch {
+                if let Some(path) = cache.paths.get(branch.id()) {
+                    return Some(path.clone());
+                }
+            }
+        }
+
+        let is_cacheable = branch.generation() != 0
+            && branch.ancestors().all(|ancestor| ancestor.generation() != 0);
+        if !is_cacheable {
+            return None;
+        }
+        let path = branch.path();
+        if !path.starts_with('/') {
+            // The branching node is detached
+            return None;
+        }
+
+        let mut cache = self.path_cache.lock();
+        if cache.epoch < epoch {
+            cache.epoch = epoch;
+            cache.paths.clear();
+        }
+        if cache.epoch == epoch {
+            if cache.paths.len() >= PathCache::CAPACITY {
+                cache.paths.clear();
+            }
+            cache.paths.insert(*branch.id(), path.clone());
+        }
+        Some(path)
+    }
+
+    /// Returns the subsystems, i.e., the branching nodes at the top level of the tree
+    /// whose names have been registered (see `register_subsystem`).
+    pub fn subsystems(&self) -> Vec<Arc<dyn SysBranchNode>> {
+        self.root
+            .children()
+            .into_iter()
+            .filter(|child| self.event_hub.subsystem_attrs(&child.name()).is_some())
+            .filter_map(|child| child.arc_as_branch())
+            .collect()
+    }
+
+    /// Returns the name of the subsystem (see `register_subsystem`)
+    /// whose members include the node of the given path.
+    ///
+    /// `None` is returned if the node is not a member of any subsystem.
+    /// The node of a subsystem itself is not a member of the subsystem.
+    pub fn subsystem_of(&self, path: &str) -> Option<SysStr> {
+        self.event_hub.subsystem_of(path)
+    }
+
+    /// Waits for a node with the given path to appear in the tree.
+    ///
+    /// If the node already exists, it is returned immediately.
+    /// Otherwise, this method blocks until the node appears
+    /// and returns the node,
+    /// or fails with `Error::TimedOut` once the timeout expires.
+    ///
+    /// The node may appear along with a whole subtree
+    /// (e.g., a device is added with its children populated in advance),
+    /// in which case only an `Add` event for an ancestor is published.
+    /// So the path is checked again on every `Add` or `Move` event
+    /// whose path is the path itself or that of one of its ancestors.
+    pub fn wait_for_path(&self, path: &str, timeout: Duration) -> Result<Arc<dyn SysObj>> {
+        let waiter = Arc::new(PathWaiter::new(path));
+        let observer: Weak<dyn Observer<SysEvent>> = Arc::downgrade(&waiter) as _;
+
+        // The observer is registered before checking the path for the first time
+        // so that the `Add` event of the path cannot be missed.
+        self.register_observer(
+            observer.clone(),
+            SysEventSelector::AnyOf(vec![
+                SysEventSelector::Action(SysEventAction::Add),
+                SysEventSelector::Action(SysEventAction::Move),
+            ]),
+        );
+        let res = waiter
+            .wait_queue
+            .wait_until_or_timeout(|| self.find_path(path), &timeout)
+            .map_err(|_| Error::TimedOut);
+        self.unregister_observer(observer);
+
+        res
+    }
+
+    /// Finds all the nodes that have an attribute of the given name.
+    ///
+    /// The tree is walked in depth-first order,
+    /// down to at most `SysTree::MAX_DEPTH`.
+    /// Symlink nodes are skipped.
+    pub fn find_with_attr(&self, attr_name: &str) -> Vec<Arc<dyn SysNode>> {
+        let mut found_nodes = Vec::new();
+        let mut pending_nodes: Vec<(Arc<dyn SysObj>, usize)> = self
+            .root
+            .children()
+            .into_iter()
+            .rev()
+            .map(|child| (child, 1))
+            .collect();
+        while let Some((node, depth)) = pending_nodes.pop() {
+            let Some(normal_node) = node.arc_as_node() else {
+                continue;
+            };
+
+            if let Some(branch) = normal_node.clone().arc_as_branch() {
+                if depth < Self::MAX_DEPTH {
+                    let children = branch.children();
+                    pending_nodes.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+                }
+            }
+            if normal_node.node_attrs().contains(attr_name) {
+                found_nodes.push(normal_node);
+            }
+        }
+        found_nodes
+    }
+
+    /// Looks up the node with the given absolute path
+    /// (e.g., `/devices/pci0000:00/net/eth0`).
+    ///
+    /// The path is resolved from the root one name at a time.
+    /// A trailing `/` is allowed, while empty names (e.g., in `/a//b`) are not.
+    /// The names `.` and `..` are resolved against the nodes traversed so far,
+    /// and `..` at the root stays at the root.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::InvalidArgument` if the path contains empty names;
+    /// * `Error::NotFound` if a name is missing
+    ///   or if a node in the middle of the path is not a branching node.
+    pub fn lookup(&self, path: &str) -> Result<Arc<dyn SysObj>> {
+        let path = path.strip_prefix('/').unwrap_or(path);
+        let path = path.strip_suffix('/').unwrap_or(path);
+
+        let mut traversed: Vec<Arc<dyn SysObj>> = vec![self.root.clone()];
+        if path.is_empty() {
+            return Ok(traversed.pop().unwrap());
+        }
+        for name in path.split('/') {
+            match name {
+                "" => return Err(Error::InvalidArgument),
+                "." => {}
+                ".." => {
+                    if traversed.len() > 1 {
+                        traversed.pop();
+                    }
+                }
+                name => {
+                    let node = traversed.last().unwrap();
+                    let child = node
+                        .as_branch()
+                        .and_then(|branch| branch.child(name))
+                        .ok_or(Error::NotFound)?;
+                    traversed.push(child);
+                }
+            }
+        }
+        Ok(traversed.pop().unwrap())
+    }
+
+    /// Looks up the "normal" node (i.e., a branching or leaf node)
+    /// with the given absolute path (see the `lookup` method).
+    ///
+    /// `Error::InvalidArgument` is returned if the node is a symlink.
+    pub fn lookup_node(&self, path: &str) -> Result<Arc<dyn SysNode>> {
+        self.lookup(path)?
+            .arc_as_node()
+            .ok_or(Error::InvalidArgument)
+    }
+
+    // Finds the node with the given path,
+    // which is resolved in the same way as the `lookup` method.
+    fn find_path(&self, path: &str) -> Option<Arc<dyn SysObj>> {
+        self.lookup(path).ok()
+    }
+
+    /// Removes a node from its parent on behalf of the node itself,
+    /// publishing a `Remove` event for the node.
+    ///
+    /// This method is intended for the special attributes
+    /// whose writes request the removal of their own nodes
+    /// (e.g., the `delete` or `remove` attribute of a device).
+    /// So it is fine to call this method inside `SysNode::write_attr`.
+    ///
+    /// # Ordering
+    ///
+    /// The removal is done in the following order:
+    /// 1. The path of the node is captured,
+    ///    since it can no longer be obtained once the node is detached;
+    /// 2. The node is detached from its parent,
+    ///    which takes the lock of the parent's children;
+    /// 3. The `Remove` event is published after the lock has been released,
+    ///    so observers may access the parent without deadlock.
+    ///
+    /// As with `SysTree::publish_event`, no event is published
+    /// if the node is not attached to the tree (e.g., its parent has been removed).
+    ///
+    /// # Deadlock
+    ///
+    /// The caller must _not_ hold the lock of the parent's children
+    /// (e.g., by calling this method inside `SysBranchNode::visit_child_with`).
+    pub fn remove_self(&self, node: &dyn SysObj) -> Result<()> {
+        let parent = node.parent().upgrade().ok_or(Error::NotFound)?;
+        let path = node.path();
+
+        let removed_node = parent.detach_child(&node.name())?;
+        drop(parent);
+
+        let details = node_type_details(removed_node.as_ref());
+        if path.starts_with('/') {
+            self.event_hub.publish_event_at(path.into(), SysEventAction::Remove, details);
+        }
+        drop(removed_node);
+        Ok(())
+    }
+
+    /// Adds a child to a branching node (see `SysBranchNode::attach_child`),
+    /// publishing an `Add` event for the child
+    /// with the type of the child in the `NODE_TYPE` detail.
+    ///
+    /// The event is published after the lock of the parent's children has been released,
+    /// so observers see the child in the tree
+    /// and may access the parent without deadlock.
+    pub fn add_child(&self, parent: &dyn SysBranchNode, child: Arc<dyn SysObj>) -> Result<()> {
+        parent.attach_child(child.clone())?;
+
+        let details = node_type_details(child.as_ref());
+        self.event_hub.publish_event(child.as_ref(), SysEventAction::Add, details);
+        Ok(())
+    }
+
+    /// Removes a child from a branching node (see `SysBranchNode::detach_child`),
+    /// publishing a `Remove` event for the child
+    /// with the type of the child in the `NODE_TYPE` detail.
+    ///
+    /// The path of the child is captured before the child is detached,
+    /// since it can no longer be obtained afterwards.
+    /// As in the `remove_self` method, the event is published
+    /// after the lock of the parent's children has been released,
+    /// and no event is published if the parent is not attached to the tree.
+    pub fn remove_child(&self, parent: &dyn SysBranchNode, name: &str) -> Result<Arc<dyn SysObj>> {
+        let path = parent.child(name).ok_or(Error::NotFound)?.path();
+        let removed_child = parent.detach_child(name)?;
+
+        let details = node_type_details(removed_child.as_ref());
+        if path.starts_with('/') {
+            self.event_hub.publish_event_at(path.into(), SysEventAction::Remove, details);
+        }
+        Ok(removed_child)
+    }
+
+    /// Moves the node at `from_path` to `to_path`,
+    /// publishing a `Move` event for the node
+    /// with the old path in the `OLD_DEVPATH` detail.
+    ///
+    /// If the two paths share the same parent, the node is renamed in place
+    /// atomically (see `SysBranchNode::rename_child`).
+    /// Otherwise, the node is detached from its parent
+    /// and attached to the parent of `to_path`,
+    /// so it is briefly missing for concurrent lookups.
+    /// Either way, the node (including its ID) stays intact
+    /// (see `SysObj::relocate`).
+    ///
+    /// If attaching the node to the new parent fails
+    /// (e.g., a child of the same name has been added concurrently),
+    /// the node is attached back to its old parent.
+    /// Like that of a transaction, the rollback may fail as well,
+    /// in which case the node is left out of the tree
+    /// and the failure is reported in `SysTxError`.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::NotFound` if the node or the new parent does not exist;
+    /// * `Error::InvalidArgument` if the node is the root,
+    ///   if the new parent is not a branching node,
+    ///   if the new name is invalid,
+    ///   or if the node would be moved into its own subtree;
+    /// * `Error::AlreadyExists` if the new parent already has a child of the new name;
+    /// * `Error::NotSupported` if a parent does not support the mutation.
+    pub fn move_node(&self, from_path: &str, to_path: &str) -> core::result::Result<(), SysTxError> {
+        let node = self.find_path(from_path).ok_or(Error::NotFound)?;
+        if node.is_root() {
+            return Err(Error::InvalidArgument.into());
+        }
+        let old_parent = node.parent().upgrade().ok_or(Error::NotFound)?;
+        let old_name = node.name();
+        let old_path = node.path();
+
+        let (new_parent_path, new_name) = to_path
+            .trim_end_matches('/')
+            .rsplit_once('/')
+            .ok_or(Error::InvalidArgument)?;
+        validate_name(new_name)?;
+        let new_parent = self
+            .find_path(new_parent_path)
+            .ok_or(Error::NotFound)?
+            .arc_as_branch()
+            .ok_or(Error::InvalidArgument)?;
+
+        let is_rename = new_parent.id() == old_parent.id();
+        if is_rename && new_name == old_name.as_ref() {
+            return Ok(());
+        }
+        let new_name: SysStr = new_name.to_string().into();
+        let node = if is_rename {
+            old_parent.rename_child(&old_name, new_name)?;
+            node
+        } else {
+            // A node cannot be moved into its own subtree
+            if is_path_in_subtree(&new_parent.path(), &old_path) {
+                return Err(Error::InvalidArgument.into());
+            }
+            if new_parent.child(&new_name).is_some() {
+                return Err(Error::AlreadyExists.into());
+            }
+
+            let node = old_parent.detach_child(&old_name)?;
+            let res = node
+                .relocate(Arc::downgrade(&new_parent), new_name)
+                .and_then(|()| new_parent.attach_child(node.clone()));
+            if let Err(error) = res {
+                // Put the node back where it was
+                let rollback_res = node
+                    .relocate(Arc::downgrade(&old_parent), old_name)
+                    .and_then(|()| old_parent.attach_child(node));
+                return Err(SysTxError {
+                    error,
+                    num_rollback_failures: usize::from(rollback_res.is_err()),
+                });
+            }
+            node
+        };
+
+        let details = vec![SysEventKv {
+            key: "OLD_DEVPATH".into(),
+            value: old_path.into(),
+        }];
+        self.event_hub.publish_event_at(node.path().into(), SysEventAction::Move, details);
+        Ok(())
+    }
+
+    /// Observes the events of a node and its descendants with a callback.
+    ///
+    /// The observer is registered until the returned subscription is dropped,
+    /// which ties the lifetime of the observer to an RAII guard.
+    ///
+    /// The node is identified by its path at the time of calling this method,
+    /// which selects the events to deliver (see `SysEventSelector::Subtree`),
+    /// so the events under other paths never reach the callback.
+    /// If the node (or any of its ancestors) is moved afterwards
+    /// (see `SysTree::move_node`), the subscription stays at the old path:
+    /// the events at the new path, including the `Move` event itself, are not delivered,
+    /// whereas those of any node added at the old path are.
+    /// To follow a moved node, observe it again at its new path.
+    pub fn observe_node<F>(&self, node: &Arc<dyn SysObj>, f: F) -> NodeSubscription<'_>
+    where
+        F: Fn(&SysEvent) + Send + Sync + 'static,
+    {
+        let observer = Arc::new(NodeObserver { f });
+        let observer: Arc<dyn Observer<SysEvent>> = observer;
+        let selector = SysEventSelector::Subtree(node.path());
+        self.register_observer(Arc::downgrade(&observer), selector);
+        NodeSubscription {
+            tree: self,
+            observer,
+        }
+    }
+
+    /// Sets the flags of an attribute of a node at runtime.
+    ///
+    /// On success, a `Change` event is published for the node,
+    /// with the name and the new flags of the attribute in the details.
+    pub fn set_attr_flags(&self, node: &dyn SysNode, name: &str, flags: SysAttrFlags) -> Result<()> {
+        node.set_attr_flags(name, flags)?;
+
+        let details = vec![
+            SysEventKv {
+                key: "ATTR".into(),
+                value: name.to_string().into(),
+            },
+            SysEventKv {
+                key: "ATTR_FLAGS".into(),
+                value: format!("{:#x}", flags.bits()).into(),
+            },
+        ];
+        self.publish_event(node, SysEventAction::Change, details);
+        Ok(())
+    }
+
+    /// Replaces the attribute set of a node atomically at runtime.
+    ///
+    /// On success, a `Change` event is published for the node.
+    /// Unlike removing the node and adding it back with the new attributes,
+    /// the node (including its ID) stays intact,
+    /// so do the inodes of the node in sysfs.
+    pub fn replace_node_attrs(&self, node: &dyn SysNode, new_attrs: SysAttrSet) -> Result<()> {
+        node.replace_node_attrs(new_attrs)?;
+        self.publish_event(node, SysEventAction::Change, Vec::new());
+        Ok(())
+    }
+
+    /// Adds an attribute to a node at runtime (see `SysNode::add_attr`).
+    ///
+    /// On success, a `Change` event is published for the node,
+    /// with the name of the new attribute in the details.
+    /// The new attribute is visible in sysfs immediately.
+    pub fn add_attr(&self, node: &dyn SysNode, attr: SysAttr) -> Result<()> {
+        let name = attr.name().clone();
+        node.add_attr(attr)?;
+
+        let details = vec![SysEventKv {
+            key: "ATTR".into(),
+            value: name,
+        }];
+        self.publish_event(node, SysEventAction::Change, details);
+        Ok(())
+    }
+
+    /// Tracks the reference counts of a node,
+    /// which can then be queried with `SysTree::node_refcounts`.
+    ///
+    /// Only a weak reference to the node is kept,
+    /// so tracking a node does not keep it alive.
+    #[cfg(feature = "debug-refcounts")]
+    pub fn track_node(&self, node: &Arc<dyn SysObj>) {
+        let mut tracked_nodes = self.tracked_nodes.lock();
+        tracked_nodes.retain(|_, node| node.strong_count() > 0);
+        tracked_nodes.insert(*node.id(), Arc::downgrade(node));
+    }
+
+    /// Returns the strong and weak reference counts of a tracked node,
+    /// which helps diagnose leaks of references
+    /// (e.g., a node that is not freed after being removed).
+    ///
+    /// The weak count excludes the weak reference held for tracking.
+    /// `None` is returned if the node is not tracked.
+    ///
+    /// The counts are approximate if the references to the node
+    /// are being acquired or released concurrently.
+    #[cfg(feature = "debug-refcounts")]
+    pub fn node_refcounts(&self, id: &SysNodeId) -> Option<(usize, usize)> {
+        let tracked_nodes = self.tracked_nodes.lock();
+        let node = tracked_nodes.get(id)?;
+        let strong_count = node.strong_count();
+        let weak_count = node.weak_count().saturating_sub(1);
+        Some((strong_count, weak_count))
+    }
+
+    /// Creates a transaction that mutates multiple nodes in the tree
+    /// and publishes the events of the mutations at once.
+    pub fn transaction(&self) -> SysTransaction<'_> {
+        SysTransaction::new(self)
+    }
+
+    pub(crate) fn event_hub(&self) -> &SysEventHub {
+        &self.event_hub
+    }
+
+    /// Checks the invariants of the tree and returns all the violations found.
+    ///
+    /// The invariants checked are:
+    /// * The children of a branching node have unique names;
+    /// * The parent of a child points back to the branching node containing it;
+    /// * The IDs of all nodes are unique;
+    /// * The attribute IDs of a node are contiguous and start from zero.
+    ///
+    /// This method walks the entire tree, so it is intended
+    /// as a tool for testing and debugging.
+    pub fn check_invariants(&self) -> core::result::Result<(), Vec<String>> {
+        let mut visited_ids = BTreeSet::new();
+        let mut violations = Vec::new();
+        check_node_invariants(self.root.as_ref(), 0, &mut visited_ids, &mut violations);
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+/// A subscription to the events of a node, created by `SysTree::observe_node`.
+///
+/// The observer of the subscription is unregistered when it is dropped.
+pub struct NodeSubscription<'a> {
+    tree: &'a SysTree,
+    observer: Arc<dyn Observer<SysEvent>>,
+}
+
+impl Drop for NodeSubscription<'_> {
+    fn drop(&mut self) {
+        self.tree.unregister_observer(Arc::downgrade(&self.observer));
+    }
+}
+
+/// Returns the details of an `Add` or `Remove` event for a node,
+/// which contain the type of the node in the `NODE_TYPE` key.
+pub(crate) fn node_type_details(node: &dyn SysObj) -> Vec<SysEventKv> {
+    vec![SysEventKv {
+        key: "NODE_TYPE".into(),
+        value: node.type_().as_str().into(),
+    }]
+}
+
+/// An observer that invokes a callback on the events of a node and its descendants,
+/// which are selected when the observer is registered (see `SysTree::observe_node`).
+struct NodeObserver<F> {
+    f: F,
+}
+
+impl<F: Fn(&SysEvent) + Send + Sync + 'static> Observer<SysEvent> for NodeObserver<F> {
+    fn on_events(&self, event: &SysEvent) {
+        (self.f)(event);
+    }
+}
+
+/// An observer that invokes a callback on the first event it receives
+/// (see `SysTree::observe_once`).
+struct OnceObserver<F> {
+    f: Mutex<Option<F>>,
+}
+
+impl<F: FnOnce(&SysEvent) + Send + Sync + 'static> Observer<SysEvent> for OnceObserver<F> {
+    fn on_events(&self, event: &SysEvent) {
+        // The hub delivers at most one event to a one-shot observer,
+        // but the callback is taken out anyway so that it can only be called once.
+        let Some(f) = self.f.lock().take() else {
+            return;
+        };
+        f(event);
+    }
+}
+
+/// A transient observer that wakes up the waiters of a path
+/// once the path or one of its ancestors is added to the tree.
+struct PathWaiter {
+    path: String,
+    wait_queue: WaitQueue,
+}
+
+impl PathWaiter {
+    fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+}
+
+impl Observer<SysEvent> for PathWaiter {
+    fn on_events(&self, event: &SysEvent) {
+        // The waiters check the path again,
+        // so a wakeup for an ancestor whose subtree lacks the path is harmless.
+        if is_path_in_subtree(&self.path, event.path()) {
+            self.wait_queue.wake_all();
+        }
+    }
+}
+
+fn check_node_invariants(
+    node: &dyn SysObj,
+    depth: usize,
+    visited_ids: &mut BTreeSet<SysNodeId>,
+    violations: &mut Vec<String>,
+) {
+    if depth > SysTree::MAX_DEPTH {
+        violations.push(format!(
+            "node `{}` exceeds the maximum depth of {}",
+            node.path(),
+            SysTree::MAX_DEPTH
+        ));
+        return;
+    }
+
+    if !visited_ids.insert(*node.id()) {
+        violations.push(format!(
+            "node id {} of `{}` is not unique",
+            node.id().as_u64(),
+            node.path()
+        ));
+    }
+
+    if let Some(normal_node) = node.as_node() {
+        let mut attr_ids: Vec<SysAttrId> = normal_node
+            .node_attrs()
+            .iter()
+            .map(|attr| attr.id())
+            .collect();
+        attr_ids.sort_unstable();
+        let is_contiguous = attr_ids
+            .iter()
+            .enumerate()
+            .all(|(expected_id, attr_id)| *attr_id as usize == expected_id);
+        if !is_contiguous {
+            violations.push(format!(
+                "attribute ids of `{}` are not contiguous: {:?}",
+                node.path(),
+                attr_ids
+            ));
+        }
+    }
+
+    let Some(branch) = node.as_branch() else {
+        return;
+    };
+    let branch_ptr = branch as *const dyn SysBranchNode as *const ();
+    let mut child_names = BTreeSet::new();
+    branch.visit_children_with(0, &mut |child| {
+        if !child_names.insert(child.name()) {
+            violations.push(format!(
+                "child name `{}` is not unique under `{}`",
+                child.name(),
+                node.path()
+            ));
+        }
+
+        let is_parent_consistent = child
+            .parent()
+            .upgrade()
+            .is_some_and(|parent| Arc::as_ptr(&parent) as *const () == branch_ptr);
+        if !is_parent_consistent {
+            violations.push(format!(
+                "parent of `{}` does not point back to `{}`",
+                child.name(),
+                node.path()
+            ));
+        }
+
+        check_node_invariants(child.as_ref(), depth + 1, visited_ids, violations);
+        Some(())
+    });
 }
 
-struct SysTreeRoot(SysBranchNodeFields<dyn SysNode>);
+pub(crate) struct SysTreeRoot(SysBranchNodeFields<dyn SysNode>);
 
 impl SysTreeRoot {
     pub fn new() -> Arc<Self> {
-        let name = ""; // Only the root has an empty name
-        let attr_set = SysAttrSet::new_empty(); // The root has no attributes
-        let inner = SysBranchNodeFields::new(name, attr_set);
-        Arc::new(Self(inner))
+        Arc::new_cyclic(|this| {
+            let name = ""; // Only the root has an empty name
+            let attr_set = SysAttrSet::new_empty(); // The root has no attributes
+            // The root is the parent of the top-level nodes
+            let inner = SysBranchNodeFields::new(name, attr_set)
+                .with_this(this.clone() as Weak<dyn SysBranchNode>);
+            Self(inner)
+        })
     }
 }
 
@@ -55,24 +837,54 @@ impl SysTreeRoot {
 impl SysBranchNode for SysTreeRoot {
     fn visit_child_with(&self,
         name: &str, 
-        f: &mut dyn FnMut(Option<&dyn SysNode>)
+        f: &mut dyn FnMut(Option<&Arc<dyn SysObj>>)
     );
     fn visit_children_with(&self, 
         min_id: u64,
-        f: &mut dyn FnMut(&dyn SysObj) -> Option<()>,
+        f: &mut dyn FnMut(&Arc<dyn SysObj>) -> Option<()>,
     );
     fn child(&self, name: &str) -> Option<Arc<dyn SysObj>>;
     fn children(&self) -> Vec<Arc<dyn SysObj>>;
     fn count_children(&self) -> usize;
+    fn generation(&self) -> u64;
+
+    fn children_sorted_by_name(&self) -> Vec<Arc<dyn SysObj>> {
+        self.0
+            .children_sorted()
+            .into_iter()
+            .map(|child| child as Arc<dyn SysObj>)
+            .collect()
+    }
+
+    fn detach_child(&self, name: &str) -> Result<Arc<dyn SysObj>> {
+        let child = self.0.remove_child(name).ok_or(Error::NotFound)?;
+        Ok(child)
+    }
+
+    fn attach_child(&self, child: Arc<dyn SysObj>) -> Result<()> {
+        // The children of the root must be nodes, not symlinks
+        let child = child.arc_as_node().ok_or(Error::InvalidArgument)?;
+        self.0.add_child(child)
+    }
+
+    fn rename_child(&self, old_name: &str, new_name: SysStr) -> Result<()> {
+        self.0.rename_child(old_name, new_name)
+    }
 }
 
 #[inherit_methods(from = "self.0")]
 impl SysNode for SysTreeRoot {
-    fn node_attrs(&self) -> &SysAttrSet;
-    fn show_attr(&self, name: &str) -> SysStr;
-    fn store_attr(&self, name: &str, new_val: &str) -> Result<()>;
-    fn read_attr(&self, name: &str, offset: usize, writer: VmWriter<Falliable>) -> Result<()>;
-    fn write_attr(&self, name: &str, offset: usize, reader: VmReader<Falliable>) -> Result<()>;
+    fn node_attrs(&self) -> Arc<SysAttrSet>;
+
+    fn read_attr(&self, _name: &str, _offset: usize, _writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        // The root has no attributes
+        Err(Error::NotFound)
+    }
+
+    fn write_attr(&self, _name: &str, _offset: usize, _reader: &mut VmReader<Fallible>) -> Result<usize> {
+        // The root has no attributes
+        Err(Error::NotFound)
+    }
 }
 
 #[inherit_methods(from = "self.0")]
@@ -80,5 +892,615 @@ impl SysObj for SysTreeRoot {
     fn id(&self) -> &SysNodeId;
     fn type_(&self) -> SysNodeType;
     fn name(&self) -> SysStr;
+    fn parent(&self) -> Weak<dyn SysBranchNode>;
+
+    fn is_root(&self) -> bool {
+        true
+    }
+
+    fn path(&self) -> String {
+        SysTree::ROOT_PATH.to_string()
+    }
+
+    fn as_node(&self) -> Option<&dyn SysNode> {
+        Some(self)
+    }
+
+    fn as_branch(&self) -> Option<&dyn SysBranchNode> {
+        Some(self)
+    }
+
+    fn arc_as_node(self: Arc<Self>) -> Option<Arc<dyn SysNode>> {
+        Some(self)
+    }
+
+    fn arc_as_branch(self: Arc<Self>) -> Option<Arc<dyn SysBranchNode>> {
+        Some(self)
+    }
 }
 
+
+#[cfg(ktest)]
+mod test {
+    use ostd::{
+        prelude::ktest,
+        task::{Task, TaskOptions},
+    };
+
+    use super::*;
+    use crate::{
+        test_util::{EventRecorder, FixtureBranch, FixtureLeaf, TreeBuilder},
+        utils::{no_parent, SysAttrHandler, SysLazyBranchNode, SysLeafNode},
+        SysAttrSetBuilder,
+    };
+
+    #[ktest]
+    fn wait_for_path_returns_added_node() {
+        let tree: &'static SysTree = Box::leak(Box::new(
+            TreeBuilder::new()
+                .add("/devices", SysNodeType::Branch, &[])
+                .build()
+                .unwrap(),
+        ));
+
+        TaskOptions::new(move || {
+            // Give the waiter a chance to wait
+            for _ in 0..10 {
+                Task::yield_now();
+            }
+            let devices = tree.lookup("/devices").unwrap().arc_as_branch().unwrap();
+            let eth0 = FixtureLeaf::new("eth0".into(), &[], Arc::downgrade(&devices));
+            tree.add_child(devices.as_ref(), eth0).unwrap();
+        })
+        .data(())
+        .spawn()
+        .unwrap();
+
+        let eth0 = tree
+            .wait_for_path("/devices/eth0", Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(eth0.path(), "/devices/eth0");
+        // The transient observer is unregistered
+        assert!(tree.list_observers().is_empty());
+
+        // An existing path is found immediately
+        let eth0 = tree.wait_for_path("/devices/eth0", Duration::ZERO).unwrap();
+        assert_eq!(eth0.name(), "eth0");
+    }
+
+    #[ktest]
+    fn wait_for_path_times_out() {
+        let tree = TreeBuilder::new()
+            .add("/devices", SysNodeType::Branch, &[])
+            .build()
+            .unwrap();
+        let res = tree.wait_for_path("/devices/eth0", Duration::from_millis(10));
+        assert_eq!(res.err(), Some(Error::TimedOut));
+        assert!(tree.list_observers().is_empty());
+    }
+
+    /// The handler of a `delete` attribute, whose write removes the node itself.
+    struct DeleteAttr {
+        tree: &'static SysTree,
+        node: Weak<SysLeafNode>,
+    }
+
+    impl SysAttrHandler for DeleteAttr {
+        fn read(&self, _offset: usize, _writer: &mut VmWriter<Fallible>) -> Result<usize> {
+            Err(Error::PermissionDenied)
+        }
+
+        fn write(&self, _offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+            let node = self.node.upgrade().ok_or(Error::NotFound)?;
+            self.tree.remove_self(node.as_ref())?;
+            Ok(reader.remain())
+        }
+    }
+
+    #[ktest]
+    fn writing_delete_attr_removes_node() {
+        let tree: &'static SysTree = Box::leak(Box::new(
+            TreeBuilder::new()
+                .add("/devices", SysNodeType::Branch, &[])
+                .build()
+                .unwrap(),
+        ));
+        let devices = tree.lookup("/devices").unwrap().arc_as_branch().unwrap();
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add("delete".into(), SysAttrFlags::CAN_WRITE);
+        let dev0 = SysLeafNode::new("dev0".into(), builder.build());
+        let delete_attr = DeleteAttr {
+            tree,
+            node: Arc::downgrade(&dev0),
+        };
+        dev0.set_handler("delete", Arc::new(delete_attr)).unwrap();
+        dev0.set_parent(Arc::downgrade(&devices));
+        tree.add_child(devices.as_ref(), dev0.clone()).unwrap();
+
+        let recorder = EventRecorder::new();
+        tree.register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+        dev0.store_attr("delete", "1").unwrap();
+
+        assert_eq!(tree.lookup("/devices/dev0").err(), Some(Error::NotFound));
+        let events = recorder.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action(), SysEventAction::Remove);
+        assert_eq!(events[0].path(), "/devices/dev0");
+    }
+
+    #[ktest]
+    fn node_subscription_stops_on_drop() {
+        let tree = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &[])
+            .add("/devices/eth1", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        let eth0 = tree.lookup("/devices/eth0").unwrap();
+        let eth0_node = eth0.clone().arc_as_node().unwrap();
+        let eth1_node = tree.lookup_node("/devices/eth1").unwrap();
+
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let subscription = {
+            let paths = paths.clone();
+            tree.observe_node(&eth0, move |event| {
+                paths.lock().push(event.path().to_string());
+            })
+        };
+        tree.publish_event(eth0_node.as_ref(), SysEventAction::Change, Vec::new());
+        // The events of other nodes are not observed, nor even delivered
+        tree.publish_event(eth1_node.as_ref(), SysEventAction::Change, Vec::new());
+        assert_eq!(*paths.lock(), ["/devices/eth0"]);
+        let observers = tree.list_observers();
+        assert_eq!(observers.len(), 1);
+        assert_eq!(observers[0].1, "events under `/devices/eth0`");
+
+        // The subscription stays at the old path after the node is moved
+        tree.move_node("/devices/eth0", "/devices/wan0").unwrap();
+        tree.publish_event(eth0_node.as_ref(), SysEventAction::Change, Vec::new());
+        assert_eq!(paths.lock().len(), 1);
+
+        drop(subscription);
+        assert!(tree.list_observers().is_empty());
+        tree.publish_event(eth0_node.as_ref(), SysEventAction::Change, Vec::new());
+        assert_eq!(paths.lock().len(), 1);
+    }
+
+    #[ktest]
+    fn root_attrs_are_accessed_with_offsets() {
+        let tree = SysTree::new();
+        let root: Arc<dyn SysNode> = tree.root().clone();
+
+        let mut buf = [0u8; 8];
+        let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+        assert_eq!(root.read_attr("uevent", 0, &mut writer), Err(Error::NotFound));
+        let mut reader = VmReader::from(b"add".as_slice()).to_fallible();
+        assert_eq!(root.write_attr("uevent", 0, &mut reader), Err(Error::NotFound));
+        // So are the helpers built on the offset-bearing methods
+        assert_eq!(root.read_attr_bytes("uevent"), Err(Error::NotFound));
+        assert!(root.node_attrs().is_empty());
+    }
+
+    #[ktest]
+    fn find_with_attr_finds_all_nodes() {
+        let tree = TreeBuilder::new()
+            .add("/devices/hwmon0", SysNodeType::Branch, &["name", "temp"])
+            .add("/devices/hwmon0/sensor1", SysNodeType::Leaf, &["temp"])
+            .add("/devices/eth0", SysNodeType::Leaf, &["mtu"])
+            .add_symlink("/class/hwmon/hwmon0", "../../devices/hwmon0")
+            .build()
+            .unwrap();
+
+        let mut paths: Vec<String> = tree
+            .find_with_attr("temp")
+            .iter()
+            .map(|node| node.path())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, ["/devices/hwmon0", "/devices/hwmon0/sensor1"]);
+        assert!(tree.find_with_attr("no_such_attr").is_empty());
+    }
+
+    #[ktest]
+    fn root_is_detected() {
+        let tree = TreeBuilder::new()
+            .add("/devices/cpu0", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        assert!(tree.root().is_root());
+        assert_eq!(tree.root().path(), "/");
+        assert!(SysTree::is_root_path(&tree.root().path()));
+
+        let cpu0 = tree.lookup("/devices/cpu0").unwrap();
+        assert!(!cpu0.is_root());
+        assert_eq!(cpu0.path(), "/devices/cpu0");
+        assert!(!SysTree::is_root_path(&cpu0.path()));
+        assert!(!SysTree::is_root_path(""));
+    }
+
+    #[ktest]
+    fn built_tree_passes_invariants() {
+        let tree = TreeBuilder::new()
+            .add("/devices/system/cpu/cpu0", SysNodeType::Leaf, &["online", "uevent"])
+            .add("/class/cpu", SysNodeType::Branch, &[])
+            .add_symlink("/class/cpu/cpu0", "../../devices/system/cpu/cpu0")
+            .build()
+            .unwrap();
+        assert_eq!(tree.check_invariants(), Ok(()));
+    }
+
+    #[ktest]
+    fn corrupted_tree_fails_invariants() {
+        let tree = TreeBuilder::new()
+            .add("/devices", SysNodeType::Branch, &[])
+            .add("/class", SysNodeType::Branch, &[])
+            .build()
+            .unwrap();
+        let devices = tree.lookup("/devices").unwrap().arc_as_branch().unwrap();
+        let class = tree.lookup("/class").unwrap().arc_as_branch().unwrap();
+
+        // Attach a child whose parent pointer refers to another branch
+        let cpu0 = FixtureLeaf::new("cpu0".into(), &[], Arc::downgrade(&class));
+        devices.attach_child(cpu0).unwrap();
+
+        let violations = tree.check_invariants().unwrap_err();
+        assert_eq!(violations, ["parent of `cpu0` does not point back to `/devices`"]);
+    }
+
+    #[ktest]
+    fn path_of_matches_path() {
+        let tree = TreeBuilder::new()
+            .add("/devices/system/cpu/cpu0", SysNodeType::Leaf, &["online"])
+            .add("/devices/system/cpu/cpu1", SysNodeType::Leaf, &["online"])
+            .add("/class/cpu", SysNodeType::Branch, &[])
+            .add_symlink("/class/cpu/cpu0", "../../devices/system/cpu/cpu0")
+            .build()
+            .unwrap();
+        let paths = [
+            "/devices",
+            "/devices/system/cpu",
+            "/devices/system/cpu/cpu0",
+            "/devices/system/cpu/cpu1",
+            "/class/cpu/cpu0",
+        ];
+        // The second round hits the cached paths
+        for _ in 0..2 {
+            for path in paths {
+                let node = tree.lookup(path).unwrap();
+                assert_eq!(tree.path_of(node.as_ref()), node.path());
+                assert_eq!(tree.path_of(node.as_ref()), path);
+            }
+        }
+        assert_eq!(tree.path_of(tree.root().as_ref()), SysTree::ROOT_PATH);
+    }
+
+    #[ktest]
+    fn path_of_follows_moves() {
+        let tree = TreeBuilder::new()
+            .add("/devices/virtual/net/eth0", SysNodeType::Leaf, &[])
+            .add("/devices/pci0", SysNodeType::Branch, &[])
+            .build()
+            .unwrap();
+        let eth0 = tree.lookup("/devices/virtual/net/eth0").unwrap();
+        assert_eq!(tree.path_of(eth0.as_ref()), "/devices/virtual/net/eth0");
+
+        tree.move_node("/devices/virtual/net", "/devices/pci0/net").unwrap();
+        assert_eq!(tree.path_of(eth0.as_ref()), eth0.path());
+        assert_eq!(tree.path_of(eth0.as_ref()), "/devices/pci0/net/eth0");
+    }
+
+    #[ktest]
+    fn subsystem_members_inherit_default_attrs() {
+        let tree = TreeBuilder::new()
+            .add("/net", SysNodeType::Branch, &[])
+            .build()
+            .unwrap();
+        let mut builder = SysAttrSetBuilder::new();
+        builder
+            .add("address".into(), SysAttrFlags::CAN_READ)
+            .add("mtu".into(), SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE);
+        tree.register_subsystem("net".into(), Arc::new(builder.build()))
+            .unwrap();
+        assert_eq!(
+            tree.register_subsystem("net".into(), Arc::new(SysAttrSetBuilder::new().build())),
+            Err(Error::AlreadyExists)
+        );
+
+        let default_attrs = tree.subsystem_attrs("net").unwrap();
+        let mut builder = SysAttrSetBuilder::with_parent(default_attrs);
+        builder.add("carrier".into(), SysAttrFlags::CAN_READ);
+        let eth0 = SysLeafNode::new("eth0".into(), builder.build());
+        let net = tree.lookup("/net").unwrap().arc_as_branch().unwrap();
+        eth0.set_parent(Arc::downgrade(&net));
+        tree.add_child(net.as_ref(), eth0).unwrap();
+
+        let eth0 = tree.lookup("/net/eth0").unwrap();
+        let attrs = eth0.node_attrs();
+        assert_eq!(attrs.len(), 3);
+        assert!(attrs.contains("address"));
+        assert!(attrs.contains("carrier"));
+        let mtu_flags = attrs.get("mtu").unwrap().flags();
+        assert!(mtu_flags.contains(SysAttrFlags::CAN_WRITE));
+        assert_eq!(tree.subsystem_of("/net/eth0").as_deref(), Some("net"));
+        assert!(tree.subsystem_attrs("block").is_none());
+    }
+
+    #[ktest]
+    fn subsystems_are_registered_top_level_branches() {
+        let tree = TreeBuilder::new()
+            .add("/devices", SysNodeType::Branch, &[])
+            .add("/net", SysNodeType::Branch, &[])
+            .add("/block", SysNodeType::Branch, &[])
+            .add("/power", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        for name in ["net", "block", "power", "bus"] {
+            let default_attrs = Arc::new(SysAttrSetBuilder::new().build());
+            tree.register_subsystem(name.into(), default_attrs).unwrap();
+        }
+
+        let mut names: Vec<_> = tree
+            .subsystems()
+            .iter()
+            .map(|subsystem| subsystem.name().to_string())
+            .collect();
+        names.sort();
+        // Neither the unregistered nor the non-branching nodes are subsystems,
+        // nor are the registered names without nodes
+        assert_eq!(names, ["block", "net"]);
+    }
+
+    #[cfg(feature = "debug-refcounts")]
+    #[ktest]
+    fn refcounts_of_removed_node_reflect_holders() {
+        let tree = TreeBuilder::new()
+            .add("/devices", SysNodeType::Branch, &[])
+            .build()
+            .unwrap();
+        let devices = tree.lookup("/devices").unwrap().arc_as_branch().unwrap();
+        let eth0: Arc<dyn SysObj> = FixtureLeaf::new("eth0".into(), &[], Arc::downgrade(&devices));
+        let id = *eth0.id();
+        tree.track_node(&eth0);
+        tree.add_child(devices.as_ref(), eth0.clone()).unwrap();
+        // Held by both the tree and the caller
+        assert_eq!(tree.node_refcounts(&id).map(|(strong, _)| strong), Some(2));
+
+        tree.remove_child(devices.as_ref(), "eth0").unwrap();
+        // Still held by the caller
+        assert_eq!(tree.node_refcounts(&id).map(|(strong, _)| strong), Some(1));
+        drop(eth0);
+        assert_eq!(tree.node_refcounts(&id).map(|(strong, _)| strong), Some(0));
+
+        let untracked = tree.lookup("/devices").unwrap();
+        assert_eq!(tree.node_refcounts(untracked.id()), None);
+    }
+
+    #[ktest]
+    fn observers_are_listed_with_descriptions() {
+        let tree = TreeBuilder::new().build().unwrap();
+        let recorders = [EventRecorder::new(), EventRecorder::new(), EventRecorder::new()];
+        let selectors = [
+            SysEventSelector::All,
+            SysEventSelector::Action(SysEventAction::Add),
+            SysEventSelector::AllOf(vec![
+                SysEventSelector::Subtree("/devices".to_string()),
+                SysEventSelector::AnyOf(vec![
+                    SysEventSelector::Action(SysEventAction::Remove),
+                    SysEventSelector::Action(SysEventAction::Move),
+                ]),
+            ]),
+        ];
+        let handles: Vec<ObserverHandle> = recorders
+            .iter()
+            .zip(selectors)
+            .map(|(recorder, selector)| {
+                tree.register_observer(Arc::downgrade(recorder) as _, selector)
+            })
+            .collect();
+
+        let observers = tree.list_observers();
+        let expected = [
+            (handles[0], "all events".to_string()),
+            (handles[1], "events of action `add`".to_string()),
+            (
+                handles[2],
+                "(events under `/devices`) and ((events of action `remove`) or (events of action `move`))"
+                    .to_string(),
+            ),
+        ];
+        assert_eq!(observers, expected);
+
+        // Neither the unregistered nor the dropped observers are listed
+        assert!(tree.unregister_observer_by_handle(handles[1]));
+        assert!(!tree.unregister_observer_by_handle(handles[1]));
+        let [first, _, third] = recorders;
+        drop(third);
+        assert_eq!(tree.list_observers(), [expected[0].clone()]);
+        drop(first);
+    }
+
+    fn new_move_tree() -> (SysTree, Arc<EventRecorder>) {
+        let tree = TreeBuilder::new()
+            .add("/devices/pci0/eth0", SysNodeType::Leaf, &["mtu"])
+            .add("/devices/pci0/eth1", SysNodeType::Leaf, &["mtu"])
+            .add("/devices/virtual/net", SysNodeType::Branch, &[])
+            .build()
+            .unwrap();
+        let recorder = EventRecorder::new();
+        tree.register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+        (tree, recorder)
+    }
+
+    fn assert_single_move_event(recorder: &EventRecorder, old_path: &str, new_path: &str) {
+        let events = recorder.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action(), SysEventAction::Move);
+        assert_eq!(events[0].path(), new_path);
+        let old_devpath = SysEventKv {
+            key: "OLD_DEVPATH".into(),
+            value: old_path.to_string().into(),
+        };
+        assert_eq!(events[0].details(), [old_devpath]);
+    }
+
+    #[ktest]
+    fn move_node_renames_in_place() {
+        let (tree, recorder) = new_move_tree();
+        let eth0 = tree.lookup("/devices/pci0/eth0").unwrap();
+        let id = *eth0.id();
+
+        tree.move_node("/devices/pci0/eth0", "/devices/pci0/wan0").unwrap();
+        assert_eq!(tree.lookup("/devices/pci0/eth0").err(), Some(Error::NotFound));
+        let wan0 = tree.lookup("/devices/pci0/wan0").unwrap();
+        assert_eq!(*wan0.id(), id);
+        assert_eq!(wan0.name(), "wan0");
+        assert_single_move_event(&recorder, "/devices/pci0/eth0", "/devices/pci0/wan0");
+    }
+
+    #[ktest]
+    fn move_node_reparents_across_parents() {
+        let (tree, recorder) = new_move_tree();
+        let id = *tree.lookup("/devices/pci0/eth1").unwrap().id();
+
+        tree.move_node("/devices/pci0/eth1", "/devices/virtual/net/eth1").unwrap();
+        assert_eq!(tree.lookup("/devices/pci0/eth1").err(), Some(Error::NotFound));
+        let eth1 = tree.lookup("/devices/virtual/net/eth1").unwrap();
+        assert_eq!(*eth1.id(), id);
+        assert_eq!(eth1.path(), "/devices/virtual/net/eth1");
+        assert_single_move_event(&recorder, "/devices/pci0/eth1", "/devices/virtual/net/eth1");
+    }
+
+    #[ktest]
+    fn move_node_rejects_collisions_and_cycles() {
+        let (tree, recorder) = new_move_tree();
+
+        // A collision within the same parent or at another parent
+        let res = tree.move_node("/devices/pci0/eth0", "/devices/pci0/eth1");
+        assert_eq!(res, Err(Error::AlreadyExists.into()));
+        let res = tree.move_node("/devices/virtual/net", "/devices/pci0");
+        assert_eq!(res, Err(Error::AlreadyExists.into()));
+        // A cycle
+        let res = tree.move_node("/devices/virtual", "/devices/virtual/net/virtual");
+        assert_eq!(res, Err(Error::InvalidArgument.into()));
+        let res = tree.move_node("/devices", "/devices/pci0/devices");
+        assert_eq!(res, Err(Error::InvalidArgument.into()));
+        // Others
+        let res = tree.move_node("/devices/pci0/eth9", "/devices/pci0/eth10");
+        assert_eq!(res, Err(Error::NotFound.into()));
+        let res = tree.move_node("/devices/pci0/eth0", "/devices/pci0/eth0/eth0");
+        assert_eq!(res, Err(Error::InvalidArgument.into()));
+
+        // The failed moves leave the tree intact without any events
+        for path in ["/devices/pci0/eth0", "/devices/pci0/eth1", "/devices/virtual/net"] {
+            assert_eq!(tree.lookup(path).unwrap().path(), path);
+        }
+        assert!(recorder.events().is_empty());
+    }
+
+    #[ktest]
+    fn move_node_reports_rollback_failures() {
+        let (tree, recorder) = new_move_tree();
+        let devices = tree.lookup("/devices").unwrap().arc_as_branch().unwrap();
+        // Lazily-populated branching nodes can detach children but not attach them
+        for name in ["pids", "tasks"] {
+            let lazy = SysLazyBranchNode::new(name.into(), |name, _parent| {
+                let _pid: u32 = name.parse().ok()?;
+                let child = SysLeafNode::new(name.to_string().into(), SysAttrSet::new_empty());
+                Some(child as Arc<dyn SysObj>)
+            });
+            lazy.set_parent(Arc::downgrade(&devices));
+            devices.attach_child(lazy).unwrap();
+        }
+        let id = *tree.lookup("/devices/pids/1").unwrap().id();
+
+        // The node cannot be attached to the new parent, nor back to the old one
+        let tx_error = tree.move_node("/devices/pids/1", "/devices/tasks/init").unwrap_err();
+        assert_eq!(tx_error.error, Error::NotSupported);
+        assert_eq!(tx_error.num_rollback_failures, 1);
+        assert!(!tx_error.is_rolled_back());
+        // The node is left out of the tree, which populates a new one on lookup
+        assert_ne!(*tree.lookup("/devices/pids/1").unwrap().id(), id);
+        assert!(recorder.events().is_empty());
+
+        // A rolled-back move leaves the node intact
+        let tx_error = tree.move_node("/devices/pci0/eth0", "/devices/tasks/eth0").unwrap_err();
+        assert_eq!(tx_error.error, Error::NotSupported);
+        assert!(tx_error.is_rolled_back());
+        assert_eq!(tree.lookup("/devices/pci0/eth0").unwrap().path(), "/devices/pci0/eth0");
+        assert!(recorder.events().is_empty());
+    }
+
+    #[ktest]
+    fn one_shot_observer_fires_exactly_once() {
+        let tree = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &[])
+            .add("/devices/eth1", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        let eth0 = tree.lookup_node("/devices/eth0").unwrap();
+        let eth1 = tree.lookup_node("/devices/eth1").unwrap();
+
+        let fired_paths = Arc::new(Mutex::new(Vec::new()));
+        let handle = {
+            let fired_paths = fired_paths.clone();
+            tree.observe_once(SysEventSelector::Action(SysEventAction::Change), move |event| {
+                fired_paths.lock().push(event.path().to_string());
+            })
+        };
+
+        // Unselected events do not consume the callback
+        tree.publish_event(eth0.as_ref(), SysEventAction::Add, Vec::new());
+        assert!(fired_paths.lock().is_empty());
+
+        tree.publish_event(eth0.as_ref(), SysEventAction::Change, Vec::new());
+        tree.publish_event(eth1.as_ref(), SysEventAction::Change, Vec::new());
+        tree.publish_event(eth0.as_ref(), SysEventAction::Change, Vec::new());
+        assert_eq!(*fired_paths.lock(), ["/devices/eth0"]);
+
+        // The observer has been unregistered automatically
+        assert!(tree.list_observers().iter().all(|(h, _)| *h != handle));
+        assert!(!tree.unregister_observer_by_handle(handle));
+    }
+
+    #[ktest]
+    fn cancelled_one_shot_observer_never_fires() {
+        let tree = TreeBuilder::new()
+            .add("/devices/eth0", SysNodeType::Leaf, &[])
+            .build()
+            .unwrap();
+        let eth0 = tree.lookup_node("/devices/eth0").unwrap();
+
+        let fired_paths = Arc::new(Mutex::new(Vec::new()));
+        let handle = {
+            let fired_paths = fired_paths.clone();
+            tree.observe_once(SysEventSelector::All, move |event| {
+                fired_paths.lock().push(event.path().to_string());
+            })
+        };
+        assert_eq!(Arc::strong_count(&fired_paths), 2);
+
+        assert!(tree.unregister_observer_by_handle(handle));
+        // The callback is dropped without being invoked
+        assert_eq!(Arc::strong_count(&fired_paths), 1);
+        tree.publish_event(eth0.as_ref(), SysEventAction::Change, Vec::new());
+        assert!(fired_paths.lock().is_empty());
+    }
+
+    #[ktest]
+    fn removals_under_detached_parents_publish_no_events() {
+        let tree = TreeBuilder::new().build().unwrap();
+        let recorder = EventRecorder::new();
+        tree.register_observer(Arc::downgrade(&recorder) as _, SysEventSelector::All);
+
+        let orphan = FixtureBranch::new("orphan".into(), &[], no_parent());
+        for name in ["eth0", "eth1"] {
+            let leaf = FixtureLeaf::new(name.into(), &[], Arc::downgrade(&orphan) as _);
+            orphan.attach_child(leaf).unwrap();
+        }
+
+        tree.remove_child(orphan.as_ref(), "eth0").unwrap();
+        let eth1 = orphan.child("eth1").unwrap();
+        tree.remove_self(eth1.as_ref()).unwrap();
+        assert_eq!(orphan.count_children(), 0);
+        assert!(recorder.events().is_empty());
+    }
+}
@@ -1,39 +1,150 @@
 //! A set of reference implementations for nodes in a `SysTree`.
 
-use crate::{attr::SysAttrSet, node::SysNodeId};
+use crate::{attr::SysAttrSet, node::SysNodeId, tree::SysTreeRoot, Error, Result, SysAttrSetBuilder};
 
 pub struct SysObjFields {
     id: SysNodeId,
-    name: SysStr,
+    name: RwMutex<SysStr>,
+    // The parent, which is wired up once the node is added to a branching node
+    // (see `SysBranchNodeFields::add_child`).
+    parent: RwMutex<Weak<dyn SysBranchNode>>,
 }
 
 impl SysObjFields {
+    /// Creates the fields of a node with the given name.
+    ///
+    /// The name is expected to be valid (see `validate_name`),
+    /// except that the root of a `SysTree` has an empty name.
+    /// A node with an invalid name is rejected
+    /// when it is added to a branching node.
+    /// Use `try_new` if the name comes from an untrusted source;
+    /// so do the fields and the nodes built on these fields
+    /// (e.g., `SysBranchNodeFields::try_new` and `SysLeafNode::try_new`).
     pub fn new(name: SysStr) -> Self {
+        debug_assert!(name.is_empty() || validate_name(&name).is_ok());
         Self {
             id: SysNodeId::new(),
-            name,
+            name: RwMutex::new(name),
+            parent: RwMutex::new(no_parent()),
         }
     }
 
+    /// Creates the fields of a node with the given name,
+    /// returning `Error::InvalidArgument` if the name is invalid
+    /// (see `validate_name`).
+    pub fn try_new(name: SysStr) -> Result<Self> {
+        validate_name(&name)?;
+        Ok(Self::new(name))
+    }
+
     pub fn id(&self) -> &SysNodeId {
         &self.id
     }
 
-    pub fn name(&self) -> &str {
-        self.name.deref()
+    /// Returns the name.
+    ///
+    /// Cloning a borrowed name is free,
+    /// so only owned names incur an allocation.
+    pub fn name(&self) -> SysStr {
+        self.name.read().clone()
+    }
+
+    /// Renames the node.
+    ///
+    /// The node must have been detached from its parent
+    /// since the parent looks up its children by their names
+    /// (see `SysObj::relocate`).
+    pub fn set_name(&self, name: SysStr) {
+        *self.name.write() = name;
+        advance_layout_epoch();
+    }
+
+    pub fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.parent.read().clone()
+    }
+
+    /// Sets the parent (see `SysObj::set_parent`).
+    pub fn set_parent(&self, parent: Weak<dyn SysBranchNode>) {
+        *self.parent.write() = parent;
+        advance_layout_epoch();
     }
 }
 
+// The epoch of the layout of the nodes built on the fields of this module,
+// which advances whenever such a node is renamed or gets a new parent,
+// or the children of such a branching node change.
+//
+// The paths of these nodes stay the same within an epoch,
+// so the paths can be cached (see `SysTree::path_of`).
+static LAYOUT_EPOCH: AtomicU64 = AtomicU64::new(0);
+
+pub(crate) fn layout_epoch() -> u64 {
+    LAYOUT_EPOCH.load(Ordering::Acquire)
+}
+
+fn advance_layout_epoch() {
+    LAYOUT_EPOCH.fetch_add(1, Ordering::Release);
+}
+
+/// Returns an empty `Weak`, which is the parent of a detached node.
+pub fn no_parent() -> Weak<dyn SysBranchNode> {
+    Weak::<SysTreeRoot>::new()
+}
+
 pub struct SysNormalNodeFields {
-    base: StdObjFields,
-    attr_set: SysAttrSet,
+    base: SysObjFields,
+    attrs: RwMutex<NodeAttrs>,
+    // Whether any attribute has a dedicated lock,
+    // which spares the accesses to the nodes without serialized attributes
+    // from taking the lock of `attrs` (see `Self::access_attr`).
+    has_serialized_attrs: AtomicBool,
+}
+
+// The attributes of a node, which may be replaced as a whole at runtime.
+struct NodeAttrs {
+    set: Arc<SysAttrSet>,
+    // The dedicated locks of the attributes with `SysAttrFlags::IS_SERIALIZED`,
+    // indexed by the attribute IDs.
+    locks: BTreeMap<SysAttrId, Arc<Mutex<()>>>,
+    // The flags of the attributes that have been changed at runtime,
+    // indexed by the attribute IDs.
+    changed_flags: BTreeMap<SysAttrId, SysAttrFlags>,
+}
+
+impl NodeAttrs {
+    fn new(set: SysAttrSet) -> Self {
+        let locks = set
+            .iter()
+            .filter(|attr| attr.flags().contains(SysAttrFlags::IS_SERIALIZED))
+            .map(|attr| (attr.id(), Arc::new(Mutex::new(()))))
+            .collect();
+        Self {
+            set: Arc::new(set),
+            locks,
+            changed_flags: BTreeMap::new(),
+        }
+    }
 }
 
 impl SysNormalNodeFields {
     pub fn new(name: SysStr, attr_set: SysAttrSet) -> Self {
+        Self::new_with(SysObjFields::new(name), attr_set)
+    }
+
+    /// Creates the fields of a node with the given name and attributes,
+    /// returning `Error::InvalidArgument` if the name is invalid
+    /// (see `SysObjFields::try_new`).
+    pub fn try_new(name: SysStr, attr_set: SysAttrSet) -> Result<Self> {
+        Ok(Self::new_with(SysObjFields::try_new(name)?, attr_set))
+    }
+
+    fn new_with(base: SysObjFields, attr_set: SysAttrSet) -> Self {
+        let attrs = NodeAttrs::new(attr_set);
+        let has_serialized_attrs = AtomicBool::new(!attrs.locks.is_empty());
         Self {
-            base: StdObjFields::new(name),
-            attr_set,
+            base,
+            attrs: RwMutex::new(attrs),
+            has_serialized_attrs,
         }
     }
 
@@ -41,53 +152,2470 @@ impl SysNormalNodeFields {
         self.base.id()
     }
 
-    pub fn name(&self) -> &str {
+    pub fn name(&self) -> SysStr {
         self.base.name()
     }
 
-    pub fn attr_set(&self) -> &SysAttrSet {
-        &self.attr_set
+    /// Renames the node (see `SysObjFields::set_name`).
+    pub fn set_name(&self, name: SysStr) {
+        self.base.set_name(name);
+    }
+
+    pub fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.base.parent()
+    }
+
+    /// Sets the parent (see `SysObj::set_parent`).
+    pub fn set_parent(&self, parent: Weak<dyn SysBranchNode>) {
+        self.base.set_parent(parent);
+    }
+
+    pub fn attr_set(&self) -> Arc<SysAttrSet> {
+        self.attrs.read().set.clone()
+    }
+
+    /// Replaces the attribute set atomically, returning the old one.
+    ///
+    /// The flags changed at runtime are reset to those in the new attribute set.
+    /// The replacement does not wait for the ongoing accesses to
+    /// the serialized attributes (see `Self::access_attr`),
+    /// so the handler of an attribute may replace the attributes of its own node.
+    pub fn replace_attr_set(&self, new_attr_set: SysAttrSet) -> Arc<SysAttrSet> {
+        let new_attrs = NodeAttrs::new(new_attr_set);
+        let mut attrs = self.attrs.write();
+        self.has_serialized_attrs
+            .store(!new_attrs.locks.is_empty(), Ordering::Release);
+        let old_attrs = core::mem::replace(&mut *attrs, new_attrs);
+        old_attrs.set
+    }
+
+    /// Adds an attribute at runtime (see `SysNode::add_attr`).
+    ///
+    /// The new attribute set extends the old one,
+    /// so the new attribute is allocated the next unused ID
+    /// and the existing attributes keep their IDs and changed flags.
+    pub fn add_attr(&self, name: SysStr, flags: SysAttrFlags) -> Result<()> {
+        let mut attrs = self.attrs.write();
+        if attrs.set.contains(&name) {
+            return Err(Error::AlreadyExists);
+        }
+        if attrs.set.len() >= SysAttrSet::MAX_ATTRS {
+            return Err(Error::NoSpace);
+        }
+
+        let mut builder = SysAttrSetBuilder::with_parent(attrs.set.clone());
+        builder.try_add(name.clone(), flags)?;
+        attrs.set = Arc::new(builder.build());
+        if flags.contains(SysAttrFlags::IS_SERIALIZED) {
+            let attr_id = attrs.set.get(&name).map(|attr| attr.id()).unwrap();
+            attrs.locks.insert(attr_id, Arc::new(Mutex::new(())));
+            self.has_serialized_attrs.store(true, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    /// Returns the current flags of an attribute.
+    pub fn attr_flags(&self, name: &str) -> Option<SysAttrFlags> {
+        let attrs = self.attrs.read();
+        let attr = attrs.set.get(name)?;
+        let flags = attrs
+            .changed_flags
+            .get(&attr.id())
+            .copied()
+            .unwrap_or(attr.flags());
+        Some(flags)
+    }
+
+    /// Sets the flags of an attribute at runtime.
+    pub fn set_attr_flags(&self, name: &str, flags: SysAttrFlags) -> Result<()> {
+        let mut attrs = self.attrs.write();
+        let Some(attr_id) = attrs.set.get(name).map(|attr| attr.id()) else {
+            return Err(Error::NotFound);
+        };
+        attrs.changed_flags.insert(attr_id, flags);
+        Ok(())
+    }
+
+    /// Accesses an attribute with a closure.
+    ///
+    /// If the attribute has the `SysAttrFlags::IS_SERIALIZED` flag,
+    /// the closure is executed while holding the dedicated lock of the attribute,
+    /// so a write followed by a read observes the complete result of the write.
+    /// Otherwise, the closure is executed without any locking.
+    ///
+    /// The closure is never executed with the lock of the attribute set held,
+    /// so it may change the attributes of the node
+    /// (e.g., with `Self::set_attr_flags` or `Self::add_attr`).
+    ///
+    /// Implementations of `SysNode::read_attr` and `SysNode::write_attr`
+    /// are expected to access the value of an attribute through this method.
+    pub fn access_attr<R>(&self, name: &str, f: impl FnOnce() -> Result<R>) -> Result<R> {
+        if !self.has_serialized_attrs.load(Ordering::Acquire) {
+            return f();
+        }
+
+        let attr_lock = {
+            let attrs = self.attrs.read();
+            attrs
+                .set
+                .get(name)
+                .and_then(|attr| attrs.locks.get(&attr.id()))
+                .cloned()
+        };
+        let _guard = attr_lock.as_ref().map(|lock| lock.lock());
+        f()
     }
 }
 
 pub struct SysBranchNodeFields<C: ?Sized> {
     base: SysNormalNodeFields,
     pub children: RwMutex<BTreeMap<SysStr, Arc<C>>>,
+    name_cmp: fn(&str, &str) -> Ordering,
+    // The generation of the children, which advances on every mutation.
+    generation: AtomicU64,
+    // The branching node that owns the fields,
+    // which becomes the parent of the added children (see `Self::with_this`).
+    this: Option<Weak<dyn SysBranchNode>>,
 }
 
 impl<C: SysObj + ?Sized> SysBranchNodeFields<C> {
+    pub fn new(name: SysStr, attr_set: SysAttrSet) -> Self {
+        Self {
+            base: SysNormalNodeFields::new(name, attr_set),
+            children: RwMutex::new(BTreeMap::new()),
+            name_cmp: str::cmp,
+            // Zero is reserved for the nodes whose children must not be cached
+            // (see `SysBranchNode::generation`).
+            generation: AtomicU64::new(1),
+            this: None,
+        }
+    }
+
+    /// Creates the fields of a branching node with the given name and attributes,
+    /// returning `Error::InvalidArgument` if the name is invalid
+    /// (see `SysObjFields::try_new`).
+    pub fn try_new(name: SysStr, attr_set: SysAttrSet) -> Result<Self> {
+        validate_name(&name)?;
+        Ok(Self::new(name, attr_set))
+    }
+
+    pub fn id(&self) -> &SysNodeId {
+        self.base.id()
+    }
+
+    pub fn name(&self) -> SysStr {
+        self.base.name()
+    }
+
+    /// Renames the node (see `SysObjFields::set_name`).
+    pub fn set_name(&self, name: SysStr) {
+        self.base.set_name(name);
+    }
+
+    pub fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.base.parent()
+    }
+
+    /// Sets the parent (see `SysObj::set_parent`).
+    pub fn set_parent(&self, parent: Weak<dyn SysBranchNode>) {
+        self.base.set_parent(parent);
+    }
+
+    pub fn attr_set(&self) -> Arc<SysAttrSet> {
+        self.base.attr_set()
+    }
+
+    /// Replaces the attribute set atomically, returning the old one
+    /// (see `SysNormalNodeFields::replace_attr_set`).
+    pub fn replace_attr_set(&self, new_attr_set: SysAttrSet) -> Arc<SysAttrSet> {
+        self.base.replace_attr_set(new_attr_set)
+    }
+
+    /// Adds an attribute at runtime (see `SysNormalNodeFields::add_attr`).
+    pub fn add_attr(&self, name: SysStr, flags: SysAttrFlags) -> Result<()> {
+        self.base.add_attr(name, flags)
+    }
+
+    /// Returns the generation of the children.
+    ///
+    /// The generation starts from one
+    /// and advances whenever a child is added, removed, or renamed,
+    /// but not when the children are read.
+    /// So caches can compare the generation at the time of caching
+    /// with the current one to detect staleness.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    fn advance_generation(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        advance_layout_epoch();
+    }
+
+    /// Sets the comparator of the child names,
+    /// which is used by the `children_sorted` method.
+    ///
+    /// The default comparator is the `Ord` of strings.
+    /// Use `natural_name_cmp` for the natural ordering of names.
+    pub fn with_name_cmp(mut self, name_cmp: fn(&str, &str) -> Ordering) -> Self {
+        self.name_cmp = name_cmp;
+        self
+    }
+
+    /// Sets the branching node that owns the fields,
+    /// typically in the closure of `Arc::new_cyclic`.
+    ///
+    /// Once set, the node becomes the parent of the children
+    /// upon their insertion (see `SysObj::set_parent`),
+    /// and the parent is cleared upon their removal.
+    /// Otherwise, the parents of the children are left as they are.
+    pub fn with_this(mut self, this: Weak<dyn SysBranchNode>) -> Self {
+        self.this = Some(this);
+        self
+    }
+
+    /// Collects all children, sorted by their names with the comparator of the names.
+    pub fn children_sorted(&self) -> Vec<Arc<C>> {
+        let mut children: Vec<Arc<C>> = self.children.read().values().cloned().collect();
+        children.sort_by(|a, b| (self.name_cmp)(&a.name(), &b.name()));
+        children
+    }
+
+    /// Creates the fields of a branching node with an initial set of children.
+    ///
+    /// The names of the children must be unique.
+    /// Otherwise, an error is returned.
+    pub fn from_children(
+        name: SysStr,
+        attr_set: SysAttrSet,
+        children: impl IntoIterator<Item = Arc<C>>,
+    ) -> Result<Self> {
+        let new_self = Self::new(name, attr_set);
+        for child in children {
+            new_self.add_child(child)?;
+        }
+        Ok(new_self)
+    }
+
     pub fn contains(&self, child_name: &str) -> bool {
         let children = self.children.read();
         children.contains_key(child_name)
     }
 
+    /// Adds a child.
+    ///
+    /// The names `.` and `..` are reserved by the directories of sysfs,
+    /// and the empty name is reserved by the root of a `SysTree`,
+    /// which is the only node with an empty name.
+    /// So adding a child with any of these names fails.
+    ///
+    /// If the owner of the fields has been set (see `Self::with_this`),
+    /// the parent of the child is wired up to the owner.
+    /// A child that already has a different live parent is rejected
+    /// with `Error::InvalidArgument`, rather than being re-parented silently,
+    /// since a node cannot be contained in two branching nodes.
+    ///
+    /// This method does not publish any events.
+    /// To publish an `Add` event for the child,
+    /// add it through `SysTree::add_child` instead.
     pub fn add_child(&self, new_child: Arc<C>) -> Result<()> {
-        let mut children = self.children.write();
+        self.try_add_child(new_child)
+            .map_err(|(err, _)| Error::from(err))
+    }
 
+    /// Adds a child, reporting the existing child on a name collision.
+    ///
+    /// This method behaves the same as the `add_child` method,
+    /// except that if a child with the same name already exists,
+    /// the existing child is returned along with `AddChildError::NameExists`.
+    /// This supports the "insert or inspect the existing one" pattern
+    /// without a second lookup.
+    pub fn try_add_child(&self, new_child: Arc<C>)
+        -> core::result::Result<(), (AddChildError, Option<Arc<C>>)>
+    {
         let name = new_child.name();
-        if children.contains_key(name) {
-            return Err(...);
+        if validate_name(&name).is_err() {
+            return Err((AddChildError::InvalidName, None));
+        }
+        if let Some(this) = &self.this {
+            let has_other_parent = new_child
+                .parent()
+                .upgrade()
+                .is_some_and(|parent| !Weak::ptr_eq(&Arc::downgrade(&parent), this));
+            if has_other_parent {
+                return Err((AddChildError::HasParent, None));
+            }
         }
 
+        let mut children = self.children.write();
+        if let Some(old_child) = children.get(&name) {
+            return Err((AddChildError::NameExists, Some(old_child.clone())));
+        }
+
+        if let Some(this) = &self.this {
+            new_child.set_parent(this.clone());
+        }
         children.insert(name.clone(), new_child);
+        self.advance_generation();
         Ok(())
     }
 
+    /// Removes a child.
+    ///
+    /// This method does not publish any events.
+    /// To publish a `Remove` event for the child,
+    /// remove it through `SysTree::remove_child` instead.
     pub fn remove_child(&self, child_name: &str) -> Option<Arc<C>> {
+        self.remove_child_reporting(child_name)
+            .map(|(removed_child, _)| removed_child)
+    }
+
+    /// Removes a child, also returning whether the branching node is now empty.
+    ///
+    /// Both are determined under the same lock of the children,
+    /// so the caller can safely cascade the removal to the branching node itself
+    /// once its last child leaves
+    /// (whereas checking `SysBranchNode::count_children` afterwards would race).
+    pub fn remove_child_reporting(&self, child_name: &str) -> Option<(Arc<C>, bool)> {
+        let mut children = self.children.write();
+        let removed_child = children.remove(child_name)?;
+        self.advance_generation();
+        if self.this.is_some() {
+            removed_child.set_parent(no_parent());
+        }
+        Some((removed_child, children.is_empty()))
+    }
+
+    /// Renames a child, while the child (including its ID) stays intact.
+    ///
+    /// The name stored in the child is updated with `SysObj::relocate`.
+    /// Like `add_child` and `remove_child`, this method does not publish any events.
+    /// To publish a `Move` event for the child,
+    /// rename it through `SysTree::move_node` instead.
+    ///
+    /// # Errors
+    ///
+    /// * `Error::InvalidArgument` if the new name is invalid (see `validate_name`);
+    /// * `Error::NotFound` if there is no child with the old name;
+    /// * `Error::AlreadyExists` if there is a child with the new name;
+    /// * the error of `SysObj::relocate` if the child cannot be renamed.
+    pub fn rename_child(&self, old_name: &str, new_name: SysStr) -> Result<()> {
+        validate_name(&new_name)?;
+
+        let mut children = self.children.write();
+        if children.contains_key(&new_name) {
+            return Err(Error::AlreadyExists);
+        }
+        let child = children.get(old_name).ok_or(Error::NotFound)?.clone();
+        child.relocate(child.parent(), new_name.clone())?;
+        children.remove(old_name);
+        children.insert(new_name, child);
+        self.advance_generation();
+        Ok(())
+    }
+
+    /// Removes all children, returning the removed ones
+    /// (e.g., to publish their `Remove` events).
+    ///
+    /// The children are removed under a single lock of the children,
+    /// so no child can be added or removed concurrently.
+    pub fn clear(&self) -> Vec<Arc<C>> {
         let mut children = self.children.write();
-        children.remove(child_name)
+        if children.is_empty() {
+            return Vec::new();
+        }
+        let removed_children: Vec<Arc<C>> = core::mem::take(&mut *children).into_values().collect();
+        self.advance_generation();
+        if self.this.is_some() {
+            for removed_child in removed_children.iter() {
+                removed_child.set_parent(no_parent());
+            }
+        }
+        removed_children
     }
 }
 
+/// The fields of a lazily-populated branching node,
+/// whose children are materialized on demand rather than upfront.
+///
+/// This is useful when the set of children is huge
+/// and expensive to materialize (e.g., the per-PID dirs).
+/// A child is produced by the populate callback when it is first accessed
+/// and then cached among the children of the node.
+///
+/// The enumeration of the children (e.g., `SysBranchNode::children`)
+/// is best-effort: only the cached children are enumerated,
+/// unless an enumerate callback is provided (see `with_enumerate`)
+/// and `populate_all` is called beforehand.
+///
+/// See `SysLazyBranchNode` for a branching node built on these fields.
+pub struct SysLazyBranchNodeFields<C: ?Sized> {
+    cached: SysBranchNodeFields<C>,
+    populate: Box<dyn Fn(&str, Weak<dyn SysBranchNode>) -> Option<Arc<C>> + Send + Sync>,
+    enumerate: Option<Box<dyn Fn() -> Vec<SysStr> + Send + Sync>>,
+}
 
-/// A reference implementation for a symlink node.
-pub struct SymlinkNode {
-    base: StdObjFields,
-    // The properties specific to a SysSymlink
-    target_path: String,
-    target_node: Weak<dyn SysNode>,
+impl<C: SysObj + ?Sized> SysLazyBranchNodeFields<C> {
+    /// Creates the fields of a lazily-populated branching node.
+    ///
+    /// The populate callback returns the child with the given name,
+    /// or `None` if there is no such child.
+    /// The name of the returned child must be the given name.
+    /// The callback is also given the branching node that owns the fields
+    /// (see `with_this`), which is to be the parent of the child;
+    /// the `Weak` is empty if the owner has not been set.
+    pub fn new(
+        name: SysStr,
+        attr_set: SysAttrSet,
+        populate: impl Fn(&str, Weak<dyn SysBranchNode>) -> Option<Arc<C>> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            cached: SysBranchNodeFields::new(name, attr_set),
+            populate: Box::new(populate),
+            enumerate: None,
+        }
+    }
+
+    /// Sets the enumerate callback,
+    /// which returns the names of all the children
+    /// (see the `populate_all` method).
+    pub fn with_enumerate(
+        mut self,
+        enumerate: impl Fn() -> Vec<SysStr> + Send + Sync + 'static,
+    ) -> Self {
+        self.enumerate = Some(Box::new(enumerate));
+        self
+    }
+
+    /// Sets the branching node that owns the fields
+    /// (see `SysBranchNodeFields::with_this`).
+    ///
+    /// Once set, the node becomes the parent of the materialized children.
+    pub fn with_this(mut self, this: Weak<dyn SysBranchNode>) -> Self {
+        self.cached = self.cached.with_this(this);
+        self
+    }
+
+    pub fn id(&self) -> &SysNodeId {
+        self.cached.id()
+    }
+
+    pub fn name(&self) -> SysStr {
+        self.cached.name()
+    }
+
+    /// Renames the node (see `SysObjFields::set_name`).
+    pub fn set_name(&self, name: SysStr) {
+        self.cached.set_name(name);
+    }
+
+    pub fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.cached.parent()
+    }
+
+    /// Sets the parent (see `SysObj::set_parent`).
+    pub fn set_parent(&self, parent: Weak<dyn SysBranchNode>) {
+        self.cached.set_parent(parent);
+    }
+
+    pub fn attr_set(&self) -> Arc<SysAttrSet> {
+        self.cached.attr_set()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.cached.generation()
+    }
+
+    /// Returns the fields of the cached children,
+    /// which can be used to visit or remove the materialized children.
+    pub fn cached(&self) -> &SysBranchNodeFields<C> {
+        &self.cached
+    }
+
+    /// Returns the child with the given name,
+    /// materializing and caching it upon the first access.
+    pub fn child(&self, name: &str) -> Option<Arc<C>> {
+        if let Some(child) = self.cached.children.read().get(name) {
+            return Some(child.clone());
+        }
+
+        // The callback is invoked without holding the lock of the children,
+        // so a concurrent access may materialize the same child.
+        // Then the child cached first wins.
+        let this = self.cached.this.clone().unwrap_or_else(no_parent);
+        let new_child = (self.populate)(name, this)?;
+        debug_assert!(new_child.name() == name);
+        match self.cached.try_add_child(new_child.clone()) {
+            Ok(()) => Some(new_child),
+            Err((AddChildError::NameExists, Some(old_child))) => Some(old_child),
+            Err(_) => None,
+        }
+    }
+
+    /// Materializes all the children returned by the enumerate callback.
+    ///
+    /// Without an enumerate callback, this method does nothing.
+    pub fn populate_all(&self) {
+        let Some(enumerate) = &self.enumerate else {
+            return;
+        };
+        for name in enumerate() {
+            let _ = self.child(&name);
+        }
+    }
+}
+
+/// Checks whether a name is valid for a node other than the root.
+///
+/// A valid name is non-empty and contains neither `/` nor `\0`,
+/// which would corrupt the paths of the nodes.
+/// The names `.` and `..` are also invalid
+/// since they refer to the current and parent dirs in paths and sysfs.
+///
+/// `Error::InvalidArgument` is returned if the name is invalid.
+pub fn validate_name(name: &str) -> Result<()> {
+    if matches!(name, "" | "." | "..") || name.contains(['/', '\0']) {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Compares two names in the natural order.
+///
+/// Unlike the lexicographical order,
+/// the natural order compares the numbers embedded in the names by their values
+/// (e.g., `eth2` is ordered before `eth10`).
+pub fn natural_name_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = NameChunks(a);
+    let mut b_chunks = NameChunks(b);
+    loop {
+        let (a_chunk, b_chunk) = match (a_chunks.next(), b_chunks.next()) {
+            (Some(a_chunk), Some(b_chunk)) => (a_chunk, b_chunk),
+            (a_chunk, b_chunk) => return a_chunk.is_some().cmp(&b_chunk.is_some()),
+        };
+
+        let is_num = |chunk: &str| chunk.as_bytes()[0].is_ascii_digit();
+        let ordering = if is_num(a_chunk) && is_num(b_chunk) {
+            // Compare the numbers by their values without parsing them,
+            // so that arbitrarily long numbers are supported.
+            let a_num = a_chunk.trim_start_matches('0');
+            let b_num = b_chunk.trim_start_matches('0');
+            a_num
+                .len()
+                .cmp(&b_num.len())
+                .then_with(|| a_num.cmp(b_num))
+                .then_with(|| a_chunk.len().cmp(&b_chunk.len()))
+        } else {
+            a_chunk.cmp(b_chunk)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// An iterator that splits a name into chunks of digits and non-digits.
+struct NameChunks<'a>(&'a str);
+
+impl<'a> Iterator for NameChunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let first = self.0.as_bytes().first()?;
+        let is_digit = first.is_ascii_digit();
+        let chunk_len = self
+            .0
+            .bytes()
+            .position(|byte| byte.is_ascii_digit() != is_digit)
+            .unwrap_or(self.0.len());
+        let (chunk, rest) = self.0.split_at(chunk_len);
+        self.0 = rest;
+        Some(chunk)
+    }
+}
+
+/// The error of adding a child to a branching node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AddChildError {
+    /// The name of the child is invalid (see `validate_name`).
+    InvalidName,
+    /// A child with the same name already exists.
+    NameExists,
+    /// The child already has a different parent.
+    HasParent,
+}
+
+impl From<AddChildError> for Error {
+    fn from(err: AddChildError) -> Self {
+        match err {
+            AddChildError::InvalidName => Error::InvalidArgument,
+            AddChildError::NameExists => Error::AlreadyExists,
+            AddChildError::HasParent => Error::InvalidArgument,
+        }
+    }
+}
+
+/// A reference implementation for the stored value of a binary attribute
+/// that is kept compressed in memory.
+///
+/// Large binary attributes (e.g., firmware images) may inflate the memory usage
+/// if their values are stored as is.
+/// Backing such an attribute with this type trades CPU for RAM:
+/// the value is compressed when stored and decompressed when read.
+/// Using this type is opt-in on a per-attribute basis,
+/// by setting it as the handler of the attribute (see `SysLeafNode::set_handler`).
+///
+/// The value is compressed with run-length encoding,
+/// which is cheap and works well for blobs with long runs of
+/// the same bytes (e.g., zero paddings).
+/// If the compression does not save memory,
+/// the value is stored uncompressed.
+///
+/// A read decompresses only the part of the value that it returns,
+/// so reading a large value page by page does not decompress it repeatedly.
+/// A write recompresses the whole value,
+/// which suits values that are read far more often than written.
+pub struct SysCompressedValue {
+    bytes: RwMutex<CompressedBytes>,
+    max_len: usize,
+}
+
+struct CompressedBytes {
+    len: usize,
+    // The stored representation of the value.
+    //
+    // If compressed, the bytes are a sequence of `(run_len, byte)` pairs,
+    // where `run_len` is in the range of `1..=u8::MAX`.
+    stored: Box<[u8]>,
+    is_compressed: bool,
+}
+
+impl CompressedBytes {
+    fn new(bytes: &[u8]) -> Self {
+        let mut runs = Vec::new();
+        let mut iter = bytes.iter().copied().peekable();
+        while let Some(byte) = iter.next() {
+            let mut run_len: u8 = 1;
+            while run_len < u8::MAX && iter.next_if_eq(&byte).is_some() {
+                run_len += 1;
+            }
+            runs.push(run_len);
+            runs.push(byte);
+        }
+
+        let is_compressed = runs.len() < bytes.len();
+        let stored = if is_compressed {
+            runs.into_boxed_slice()
+        } else {
+            Box::from(bytes)
+        };
+        Self {
+            len: bytes.len(),
+            stored,
+            is_compressed,
+        }
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        if !self.is_compressed {
+            return self.stored.to_vec();
+        }
+
+        let mut bytes = Vec::with_capacity(self.len);
+        for run in self.stored.chunks_exact(2) {
+            let (run_len, byte) = (run[0] as usize, run[1]);
+            bytes.resize(bytes.len() + run_len, byte);
+        }
+        bytes
+    }
+
+    fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        if !self.is_compressed {
+            let Some(bytes) = self.stored.get(offset..) else {
+                return Ok(0);
+            };
+            let mut reader = VmReader::from(bytes);
+            return writer
+                .write_fallible(&mut reader)
+                .map_err(|_| Error::BadAddress);
+        }
+
+        // The runs before the offset are skipped without being decompressed,
+        // and the rest are decompressed one run at a time
+        // until the writer is full.
+        let mut run_buf = [0u8; u8::MAX as usize];
+        let mut run_start = 0;
+        let mut read_len = 0;
+        for run in self.stored.chunks_exact(2) {
+            let (run_len, byte) = (run[0] as usize, run[1]);
+            let run_end = run_start + run_len;
+            if run_end <= offset {
+                run_start = run_end;
+                continue;
+            }
+
+            let chunk = &mut run_buf[..run_end - run_start.max(offset)];
+            chunk.fill(byte);
+            let mut reader = VmReader::from(&*chunk);
+            let write_len = writer
+                .write_fallible(&mut reader)
+                .map_err(|_| Error::BadAddress)?;
+            read_len += write_len;
+            if write_len < chunk.len() || writer.avail() == 0 {
+                break;
+            }
+            run_start = run_end;
+        }
+        Ok(read_len)
+    }
+}
+
+impl SysCompressedValue {
+    /// Creates a compressed value from the given bytes.
+    ///
+    /// The value can be rewritten up to the default maximum size
+    /// (see `SysStoredValue::DEFAULT_MAX_LEN`)
+    /// or the size of the given bytes, whichever is larger.
+    pub fn new(bytes: &[u8]) -> Self {
+        Self::with_max_len(bytes, bytes.len().max(SysStoredValue::DEFAULT_MAX_LEN))
+    }
+
+    /// Creates a compressed value with the given maximum size.
+    pub fn with_max_len(bytes: &[u8], max_len: usize) -> Self {
+        debug_assert!(bytes.len() <= max_len);
+        Self {
+            bytes: RwMutex::new(CompressedBytes::new(bytes)),
+            max_len,
+        }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Returns the length of the (uncompressed) value.
+    pub fn len(&self) -> usize {
+        self.bytes.read().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the length of the stored representation of the value.
+    pub fn stored_len(&self) -> usize {
+        self.bytes.read().stored.len()
+    }
+
+    /// Returns the (uncompressed) value.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.bytes.read().to_vec()
+    }
+
+    /// Reads the (uncompressed) value into a writer, starting from the given offset.
+    ///
+    /// Only the bytes that fit in the writer are decompressed.
+    /// Returns the number of bytes read.
+    pub fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        self.bytes.read().read(offset, writer)
+    }
+
+    /// Writes the value from a reader, starting from the given offset,
+    /// and compresses the new value.
+    ///
+    /// As with `SysStoredValue::write`, the value is truncated
+    /// at the end of the written bytes.
+    /// Returns the number of bytes written.
+    pub fn write(&self, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        let new_len = offset
+            .checked_add(reader.remain())
+            .ok_or(Error::TooLarge)?;
+        if new_len > self.max_len {
+            return Err(Error::TooLarge);
+        }
+
+        let mut new_bytes = vec![0; reader.remain()];
+        let mut writer = VmWriter::from(new_bytes.as_mut_slice());
+        let write_len = reader
+            .read_fallible(&mut writer)
+            .map_err(|_| Error::BadAddress)?;
+
+        let mut bytes = self.bytes.write();
+        let mut value = bytes.to_vec();
+        value.resize(offset, 0);
+        value.extend_from_slice(&new_bytes[..write_len]);
+        *bytes = CompressedBytes::new(&value);
+        Ok(write_len)
+    }
+}
+
+/// A boolean attribute, e.g., a knob like `enabled`.
+///
+/// The value is rendered as `0` or `1` (followed by a newline) on reads.
+/// On writes, `0`, `1`, `Y`, `N`, `y`, and `n`
+/// (optionally surrounded by whitespaces) are accepted,
+/// which matches the leniency of Linux's `kstrtobool`.
+/// Other values are rejected with `Error::InvalidArgument`.
+pub struct BoolAttr(AtomicBool);
+
+impl BoolAttr {
+    pub const fn new(value: bool) -> Self {
+        Self(AtomicBool::new(value))
+    }
+
+    pub fn get(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, value: bool) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    /// Reads the rendered value into a writer, starting from the given offset.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        let value: &[u8] = if self.get() { b"1\n" } else { b"0\n" };
+        let Some(bytes) = value.get(offset..) else {
+            return Ok(0);
+        };
+        let mut reader = VmReader::from(bytes);
+        writer
+            .write_fallible(&mut reader)
+            .map_err(|_| Error::BadAddress)
+    }
+
+    /// Writes a new value from a reader.
+    ///
+    /// Returns the number of bytes written.
+    pub fn write(&self, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        // A valid value is short, so there is no need to read more than a few bytes
+        const MAX_LEN: usize = 8;
+        if reader.remain() > MAX_LEN {
+            return Err(Error::InvalidArgument);
+        }
+
+        let mut buf = [0u8; MAX_LEN];
+        let mut writer = VmWriter::from(&mut buf[..]);
+        let write_len = reader
+            .read_fallible(&mut writer)
+            .map_err(|_| Error::BadAddress)?;
+
+        let value = core::str::from_utf8(&buf[..write_len]).map_err(|_| Error::InvalidArgument)?;
+        let new_value = match value.trim() {
+            "1" | "Y" | "y" => true,
+            "0" | "N" | "n" => false,
+            _ => return Err(Error::InvalidArgument),
+        };
+        self.set(new_value);
+        Ok(write_len)
+    }
 }
 
-impl SysSymlinkImpl {
+/// A value of an attribute that is stored in memory,
+/// whose size is bounded.
+///
+/// A write that would grow the value beyond the maximum size
+/// fails with `Error::TooLarge`,
+/// so a malicious writer cannot exhaust the memory through the attribute.
+pub struct SysStoredValue {
+    value: RwMutex<Vec<u8>>,
+    max_len: usize,
+}
+
+impl SysStoredValue {
+    /// The default maximum size of a stored value,
+    /// which is the conventional upper bound of the values of textual attributes.
+    pub const DEFAULT_MAX_LEN: usize = PAGE_SIZE;
+
+    pub fn new(bytes: &[u8]) -> Self {
+        Self::with_max_len(bytes, Self::DEFAULT_MAX_LEN)
+    }
+
+    /// Creates a stored value with the given maximum size.
+    pub fn with_max_len(bytes: &[u8], max_len: usize) -> Self {
+        debug_assert!(bytes.len() <= max_len);
+        Self {
+            value: RwMutex::new(bytes.to_vec()),
+            max_len,
+        }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Returns the size of the value in bytes.
+    pub fn len(&self) -> usize {
+        self.value.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.value.read().clone()
+    }
+
+    /// Reads the value into a writer, starting from the given offset.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        let value = self.value.read();
+        let Some(bytes) = value.get(offset..) else {
+            return Ok(0);
+        };
+        let mut reader = VmReader::from(bytes);
+        writer
+            .write_fallible(&mut reader)
+            .map_err(|_| Error::BadAddress)
+    }
+
+    /// Writes the value from a reader, starting from the given offset.
+    ///
+    /// The value is truncated at the end of the written bytes.
+    /// Returns the number of bytes written.
+    pub fn write(&self, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        let new_len = offset
+            .checked_add(reader.remain())
+            .ok_or(Error::TooLarge)?;
+        if new_len > self.max_len {
+            return Err(Error::TooLarge);
+        }
+
+        let mut new_bytes = vec![0; reader.remain()];
+        let mut writer = VmWriter::from(new_bytes.as_mut_slice());
+        let write_len = reader
+            .read_fallible(&mut writer)
+            .map_err(|_| Error::BadAddress)?;
+
+        let mut value = self.value.write();
+        value.resize(offset, 0);
+        value.extend_from_slice(&new_bytes[..write_len]);
+        Ok(write_len)
+    }
+
+    /// Appends to the value from a reader
+    /// (see `SysAttrFlags::IS_APPEND_ONLY`).
+    ///
+    /// Returns the number of bytes appended.
+    pub fn append(&self, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        // The size is checked before allocating the buffer for the new bytes,
+        // so an oversized write is rejected without exhausting memory
+        let new_len = self
+            .len()
+            .checked_add(reader.remain())
+            .ok_or(Error::TooLarge)?;
+        if new_len > self.max_len {
+            return Err(Error::TooLarge);
+        }
+
+        let mut new_bytes = vec![0; reader.remain()];
+        let mut writer = VmWriter::from(new_bytes.as_mut_slice());
+        let write_len = reader
+            .read_fallible(&mut writer)
+            .map_err(|_| Error::BadAddress)?;
 
+        // The size is checked again under the lock
+        // so that concurrent appends cannot exceed the maximum size together.
+        let mut value = self.value.write();
+        if value.len() + write_len > self.max_len {
+            return Err(Error::TooLarge);
+        }
+        value.extend_from_slice(&new_bytes[..write_len]);
+        Ok(write_len)
+    }
+}
+
+/// A value of an attribute that is written in multiple chunks
+/// (see `SysAttrFlags::IS_STREAMED`).
+///
+/// Values that exceed a page (e.g., a bulk configuration blob)
+/// cannot be written in a single call to `SysNode::write_attr`.
+/// Instead, the chunks of a new value are written in the order of their offsets
+/// and accumulated as a pending value,
+/// which replaces the committed value atomically once `SysStreamedValue::commit`
+/// is called at the end of the stream.
+/// Sysfs signals the end of the stream when the writer closes the file
+/// (see `SysNode::commit_attr`).
+/// Readers only ever observe committed values.
+///
+/// # Interleaved writers
+///
+/// A chunk at offset zero starts a new pending value,
+/// abandoning the incomplete one (if any).
+/// A chunk at any other offset must immediately follow the pending value,
+/// or it is rejected with `Error::InvalidArgument`.
+/// So of two interleaved writers,
+/// the one that started earlier fails on its next chunk,
+/// rather than having its chunks mixed with those of the other.
+/// Its commit fails as well,
+/// since the pending value no longer has the length that it has written.
+///
+/// # Maximum size
+///
+/// A chunk that would grow the pending value beyond the maximum size
+/// is rejected with `Error::TooLarge`.
+pub struct SysStreamedValue {
+    committed: RwMutex<Vec<u8>>,
+    pending: Mutex<Vec<u8>>,
+    max_len: usize,
+}
+
+impl SysStreamedValue {
+    /// The default maximum size of a streamed value.
+    ///
+    /// A streamed value is meant to exceed a page,
+    /// so the default is larger than `SysStoredValue::DEFAULT_MAX_LEN`.
+    pub const DEFAULT_MAX_LEN: usize = 16 * PAGE_SIZE;
+
+    pub fn new(bytes: &[u8]) -> Self {
+        Self::with_max_len(bytes, Self::DEFAULT_MAX_LEN)
+    }
+
+    /// Creates a streamed value with the given maximum size.
+    pub fn with_max_len(bytes: &[u8], max_len: usize) -> Self {
+        debug_assert!(bytes.len() <= max_len);
+        Self {
+            committed: RwMutex::new(bytes.to_vec()),
+            pending: Mutex::new(Vec::new()),
+            max_len,
+        }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Writes a chunk of a new value at the given offset.
+    ///
+    /// Returns the number of bytes written.
+    pub fn write(&self, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        // An oversized chunk is rejected before allocating the buffer for it
+        // and before discarding the pending value of any other writer
+        let new_len = offset
+            .checked_add(reader.remain())
+            .ok_or(Error::TooLarge)?;
+        if new_len > self.max_len {
+            return Err(Error::TooLarge);
+        }
+
+        let mut chunk = vec![0; reader.remain()];
+        let mut writer = VmWriter::from(chunk.as_mut_slice());
+        let chunk_len = reader
+            .read_fallible(&mut writer)
+            .map_err(|_| Error::BadAddress)?;
+        chunk.truncate(chunk_len);
+
+        let mut pending = self.pending.lock();
+        if offset == 0 {
+            pending.clear();
+        } else if offset != pending.len() {
+            return Err(Error::InvalidArgument);
+        }
+        pending.extend_from_slice(&chunk);
+        Ok(chunk_len)
+    }
+
+    /// Commits the pending value,
+    /// which is expected to be `len` bytes long.
+    ///
+    /// If the length of the pending value differs
+    /// (e.g., another writer has started a new value),
+    /// the pending value is left intact and `Error::InvalidArgument` is returned.
+    /// An empty value is committed if `len` is zero
+    /// and nothing is pending (e.g., a writer has truncated the value).
+    pub fn commit(&self, len: usize) -> Result<()> {
+        let mut pending = self.pending.lock();
+        if pending.len() != len {
+            return Err(Error::InvalidArgument);
+        }
+        *self.committed.write() = core::mem::take(&mut *pending);
+        Ok(())
+    }
+
+    /// Returns the committed value.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.committed.read().clone()
+    }
+
+    /// Reads the committed value into a writer, starting from the given offset.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        let committed = self.committed.read();
+        let Some(bytes) = committed.get(offset..) else {
+            return Ok(0);
+        };
+        let mut reader = VmReader::from(bytes);
+        writer
+            .write_fallible(&mut reader)
+            .map_err(|_| Error::BadAddress)
+    }
+}
+
+/// Parses the value of a multi-value attribute,
+/// whose values are separated by whitespaces (including newlines).
+pub fn parse_values(value: &str) -> Vec<String> {
+    value.split_whitespace().map(|v| v.to_string()).collect()
+}
+
+/// How a write of multiple tokens (see `write_tokens`) handles the failed tokens.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SysTokensMode {
+    /// All tokens are parsed before any of them is applied,
+    /// so no token is applied if any of them fails to parse.
+    ///
+    /// Note that only parsing is validated up front.
+    /// Applying the tokens stops at the first failure,
+    /// but the tokens applied before the failure are _not_ rolled back.
+    /// So `apply` should not fail on a successfully-parsed token
+    /// if the write must be all-or-nothing.
+    ParseAllFirst,
+    /// Every token is parsed and applied regardless of the failures of others.
+    BestEffort,
+}
+
+/// Handles a write of a sequence of whitespace-separated tokens
+/// to a multi-value attribute (e.g., writing `0 1 2` to bind several devices).
+///
+/// Each token is parsed with `parse` and then applied with `apply`.
+/// The failed tokens are handled according to `mode`,
+/// and the first error, if any, is returned.
+pub fn write_tokens<T>(
+    value: &str,
+    mode: SysTokensMode,
+    mut parse: impl FnMut(&str) -> Result<T>,
+    mut apply: impl FnMut(T) -> Result<()>,
+) -> Result<()> {
+    let tokens = value.split_whitespace();
+    match mode {
+        SysTokensMode::ParseAllFirst => {
+            let parsed_tokens = tokens.map(&mut parse).collect::<Result<Vec<T>>>()?;
+            parsed_tokens.into_iter().try_for_each(apply)
+        }
+        SysTokensMode::BestEffort => {
+            let mut first_err = None;
+            for token in tokens {
+                if let Err(err) = parse(token).and_then(&mut apply) {
+                    first_err.get_or_insert(err);
+                }
+            }
+            first_err.map_or(Ok(()), Err)
+        }
+    }
+}
+
+/// Renders the values of a multi-value attribute,
+/// separated by spaces and terminated by a newline.
+pub fn render_values<S: AsRef<str>>(values: &[S]) -> String {
+    let mut value = values
+        .iter()
+        .map(|v| v.as_ref())
+        .collect::<Vec<_>>()
+        .join(" ");
+    value.push('\n');
+    value
+}
+
+/// The maximum number in a cpulist (exclusive),
+/// which is the largest number of CPUs supported by Linux.
+///
+/// Bounding the numbers also bounds the memory of a parsed cpulist,
+/// so a malicious writer cannot exhaust the memory with a value like `0-4294967295`.
+pub const MAX_CPULIST_NUM: u32 = 8192;
+
+/// Parses the value of a numeric list attribute in the cpulist format of Linux
+/// (e.g., `0-3,5`) into a sorted list of numbers without duplicates.
+///
+/// An empty value (or one with only whitespaces) represents an empty list.
+/// A number no less than `MAX_CPULIST_NUM` is rejected with `Error::InvalidArgument`.
+pub fn parse_cpulist(value: &str) -> Result<Vec<u32>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let parse_num = |num: &str| {
+        let num = num.trim().parse::<u32>().map_err(|_| Error::InvalidArgument)?;
+        if num >= MAX_CPULIST_NUM {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(num)
+    };
+    // The numbers are deduplicated upon insertion,
+    // so there are at most `MAX_CPULIST_NUM` of them no matter how the ranges overlap.
+    let mut nums = BTreeSet::new();
+    for range in value.split(',') {
+        match range.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse_num(start)?, parse_num(end)?);
+                if start > end {
+                    return Err(Error::InvalidArgument);
+                }
+                nums.extend(start..=end);
+            }
+            None => {
+                nums.insert(parse_num(range)?);
+            }
+        }
+    }
+    Ok(nums.into_iter().collect())
+}
+
+/// Renders a list of numbers in the cpulist format of Linux (e.g., `0-3,5`).
+///
+/// Consecutive numbers are merged into ranges.
+/// The returned string is _not_ terminated by a newline.
+pub fn render_cpulist(nums: &[u32]) -> String {
+    let mut nums = nums.to_vec();
+    nums.sort_unstable();
+    nums.dedup();
+
+    let mut ranges = Vec::new();
+    let mut iter = nums.into_iter().peekable();
+    while let Some(start) = iter.next() {
+        let mut end = start;
+        while let Some(next) = iter.next_if(|next| *next == end + 1) {
+            end = next;
+        }
+        if start == end {
+            ranges.push(format!("{}", start));
+        } else {
+            ranges.push(format!("{}-{}", start, end));
+        }
+    }
+    ranges.join(",")
+}
+
+/// The handler of an attribute of a `SysLeafNode`,
+/// which backs the value of the attribute.
+///
+/// A handler can be a struct (e.g., `SysStoredValue` or `BoolAttr`)
+/// or a closure that reads the value.
+pub trait SysAttrHandler: Send + Sync + 'static {
+    /// Reads the value, starting from the given offset
+    /// (see `SysNode::read_attr`).
+    fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize>;
+
+    /// Writes the value, starting from the given offset
+    /// (see `SysNode::write_attr`).
+    ///
+    /// By default, the value cannot be written.
+    fn write(&self, _offset: usize, _reader: &mut VmReader<Fallible>) -> Result<usize> {
+        Err(Error::PermissionDenied)
+    }
+
+    /// Appends to the value, regardless of the offset of the write
+    /// (see `SysAttrFlags::IS_APPEND_ONLY`).
+    ///
+    /// By default, the value cannot be appended to.
+    fn append(&self, _reader: &mut VmReader<Fallible>) -> Result<usize> {
+        Err(Error::NotSupported)
+    }
+
+    /// Commits the value written in multiple chunks
+    /// (see `SysNode::commit_attr`).
+    ///
+    /// By default, there is nothing to commit.
+    fn commit(&self, _len: usize) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns the current size of the value in bytes
+    /// (see `SysNode::attr_size`).
+    ///
+    /// By default, the size is unknown, which suits computed values.
+    fn size(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl<F> SysAttrHandler for F
+where
+    F: Fn(usize, &mut VmWriter<Fallible>) -> Result<usize> + Send + Sync + 'static,
+{
+    fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        self(offset, writer)
+    }
+}
+
+impl SysAttrHandler for SysStoredValue {
+    fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        SysStoredValue::read(self, offset, writer)
+    }
+
+    fn write(&self, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        SysStoredValue::write(self, offset, reader)
+    }
+
+    fn append(&self, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        SysStoredValue::append(self, reader)
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl SysAttrHandler for SysCompressedValue {
+    fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        SysCompressedValue::read(self, offset, writer)
+    }
+
+    fn write(&self, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        SysCompressedValue::write(self, offset, reader)
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl SysAttrHandler for SysStreamedValue {
+    fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        SysStreamedValue::read(self, offset, writer)
+    }
+
+    fn write(&self, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        SysStreamedValue::write(self, offset, reader)
+    }
+
+    fn commit(&self, len: usize) -> Result<()> {
+        SysStreamedValue::commit(self, len)
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.committed.read().len())
+    }
+}
+
+impl SysAttrHandler for BoolAttr {
+    fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        BoolAttr::read(self, offset, writer)
+    }
+
+    fn write(&self, _offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        BoolAttr::write(self, reader)
+    }
+}
+
+/// A reference implementation for a leaf node,
+/// whose attributes are backed by pluggable handlers (see `SysAttrHandler`).
+///
+/// Accessing an attribute that is not in the attribute set,
+/// or one that has no handler, fails with `Error::NotFound`.
+pub struct SysLeafNode {
+    fields: SysNormalNodeFields,
+    // The handlers of the attributes, indexed by the names of the attributes.
+    handlers: RwMutex<BTreeMap<SysStr, Arc<dyn SysAttrHandler>>>,
+}
+
+impl SysLeafNode {
+    pub fn new(name: SysStr, attr_set: SysAttrSet) -> Arc<Self> {
+        Arc::new(Self {
+            fields: SysNormalNodeFields::new(name, attr_set),
+            handlers: RwMutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Creates a leaf node,
+    /// returning `Error::InvalidArgument` if the name is invalid
+    /// (see `validate_name`).
+    pub fn try_new(name: SysStr, attr_set: SysAttrSet) -> Result<Arc<Self>> {
+        Ok(Arc::new(Self {
+            fields: SysNormalNodeFields::try_new(name, attr_set)?,
+            handlers: RwMutex::new(BTreeMap::new()),
+        }))
+    }
+
+    /// Sets the handler of an attribute,
+    /// replacing the old one (if any).
+    ///
+    /// `Error::NotFound` is returned if the node has no such attribute.
+    pub fn set_handler(&self, name: &str, handler: Arc<dyn SysAttrHandler>) -> Result<()> {
+        let Some(attr) = self.fields.attr_set().get(name).cloned() else {
+            return Err(Error::NotFound);
+        };
+        self.handlers.write().insert(attr.name().clone(), handler);
+        Ok(())
+    }
+
+    fn handler(&self, name: &str) -> Result<Arc<dyn SysAttrHandler>> {
+        if !self.fields.attr_set().contains(name) {
+            return Err(Error::NotFound);
+        }
+        self.handlers.read().get(name).cloned().ok_or(Error::NotFound)
+    }
+}
+
+impl Debug for SysLeafNode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SysLeafNode")
+            .field("name", &self.fields.name())
+            .finish()
+    }
+}
+
+impl SysObj for SysLeafNode {
+    fn id(&self) -> &SysNodeId {
+        self.fields.id()
+    }
+
+    fn type_(&self) -> SysNodeType {
+        SysNodeType::Leaf
+    }
+
+    fn name(&self) -> SysStr {
+        self.fields.name()
+    }
+
+    fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.fields.parent()
+    }
+
+    fn set_parent(&self, parent: Weak<dyn SysBranchNode>) {
+        self.fields.set_parent(parent);
+    }
+
+    fn relocate(&self, new_parent: Weak<dyn SysBranchNode>, new_name: SysStr) -> Result<()> {
+        self.fields.set_parent(new_parent);
+        self.fields.set_name(new_name);
+        Ok(())
+    }
+
+    fn as_node(&self) -> Option<&dyn SysNode> {
+        Some(self)
+    }
+
+    fn arc_as_node(self: Arc<Self>) -> Option<Arc<dyn SysNode>> {
+        Some(self)
+    }
+}
+
+impl SysNode for SysLeafNode {
+    fn node_attrs(&self) -> Arc<SysAttrSet> {
+        self.fields.attr_set()
+    }
+
+    fn replace_node_attrs(&self, new_attrs: SysAttrSet) -> Result<()> {
+        self.fields.replace_attr_set(new_attrs);
+        Ok(())
+    }
+
+    fn add_attr(&self, attr: SysAttr) -> Result<()> {
+        self.fields.add_attr(attr.name().clone(), attr.flags())
+    }
+
+    fn attr_flags(&self, name: &str) -> Option<SysAttrFlags> {
+        self.fields.attr_flags(name)
+    }
+
+    fn set_attr_flags(&self, name: &str, flags: SysAttrFlags) -> Result<()> {
+        self.fields.set_attr_flags(name, flags)
+    }
+
+    fn read_attr(&self, name: &str, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        let handler = self.handler(name)?;
+        self.fields.access_attr(name, || handler.read(offset, writer))
+    }
+
+    fn write_attr(&self, name: &str, offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+        let handler = self.handler(name)?;
+        let is_append_only = self
+            .attr_flags(name)
+            .is_some_and(|flags| flags.contains(SysAttrFlags::IS_APPEND_ONLY));
+        if is_append_only {
+            return self.fields.access_attr(name, || handler.append(reader));
+        }
+        self.fields.access_attr(name, || handler.write(offset, reader))
+    }
+
+    fn commit_attr(&self, name: &str, len: usize) -> Result<()> {
+        let handler = self.handler(name)?;
+        self.fields.access_attr(name, || handler.commit(len))
+    }
+
+    fn attr_size(&self, name: &str) -> Result<usize> {
+        let handler = self.handler(name)?;
+        Ok(handler.size().unwrap_or(PAGE_SIZE))
+    }
+}
+
+/// A reference implementation for a lazily-populated branching node,
+/// which is built on `SysLazyBranchNodeFields` and has no attributes.
+///
+/// Looking up a child (e.g., `SysBranchNode::child` or `visit_child_with`)
+/// materializes the child with the populate callback upon the first access,
+/// while the enumeration of the children is best-effort
+/// (see `SysLazyBranchNodeFields`).
+///
+/// # Example
+///
+/// ```ignore
+/// let pids = SysLazyBranchNode::new("pids".into(), |name, parent| {
+///     let pid: u32 = name.parse().ok()?;
+///     let process = process_table().get(pid)?;
+///     Some(new_process_node(process, parent))
+/// });
+/// ```
+pub struct SysLazyBranchNode {
+    fields: SysLazyBranchNodeFields<dyn SysObj>,
+}
+
+impl SysLazyBranchNode {
+    /// Creates a lazily-populated branching node
+    /// (see `SysLazyBranchNodeFields::new`).
+    ///
+    /// The node is passed to the populate callback as the parent of the children.
+    pub fn new(
+        name: SysStr,
+        populate: impl Fn(&str, Weak<dyn SysBranchNode>) -> Option<Arc<dyn SysObj>> + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Self::new_with(SysLazyBranchNodeFields::new(name, SysAttrSet::new_empty(), populate))
+    }
+
+    /// Creates a lazily-populated branching node
+    /// whose children can be enumerated by the enumerate callback
+    /// (see `SysLazyBranchNodeFields::with_enumerate`).
+    pub fn new_enumerable(
+        name: SysStr,
+        populate: impl Fn(&str, Weak<dyn SysBranchNode>) -> Option<Arc<dyn SysObj>> + Send + Sync + 'static,
+        enumerate: impl Fn() -> Vec<SysStr> + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        let fields = SysLazyBranchNodeFields::new(name, SysAttrSet::new_empty(), populate)
+            .with_enumerate(enumerate);
+        Self::new_with(fields)
+    }
+
+    fn new_with(fields: SysLazyBranchNodeFields<dyn SysObj>) -> Arc<Self> {
+        Arc::new_cyclic(|this| {
+            let this = this.clone() as Weak<dyn SysBranchNode>;
+            Self {
+                fields: fields.with_this(this),
+            }
+        })
+    }
+
+    /// Materializes all the children (see `SysLazyBranchNodeFields::populate_all`).
+    pub fn populate_all(&self) {
+        self.fields.populate_all();
+    }
+
+    /// Returns the number of the materialized children.
+    pub fn count_cached(&self) -> usize {
+        self.fields.cached().children.read().len()
+    }
+}
+
+impl Debug for SysLazyBranchNode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SysLazyBranchNode")
+            .field("name", &self.fields.name())
+            .finish()
+    }
+}
+
+impl SysObj for SysLazyBranchNode {
+    fn id(&self) -> &SysNodeId {
+        self.fields.id()
+    }
+
+    fn type_(&self) -> SysNodeType {
+        SysNodeType::Branch
+    }
+
+    fn name(&self) -> SysStr {
+        self.fields.name()
+    }
+
+    fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.fields.parent()
+    }
+
+    fn set_parent(&self, parent: Weak<dyn SysBranchNode>) {
+        self.fields.set_parent(parent);
+    }
+
+    fn relocate(&self, new_parent: Weak<dyn SysBranchNode>, new_name: SysStr) -> Result<()> {
+        self.fields.set_parent(new_parent);
+        self.fields.set_name(new_name);
+        Ok(())
+    }
+
+    fn as_node(&self) -> Option<&dyn SysNode> {
+        Some(self)
+    }
+
+    fn as_branch(&self) -> Option<&dyn SysBranchNode> {
+        Some(self)
+    }
+
+    fn arc_as_node(self: Arc<Self>) -> Option<Arc<dyn SysNode>> {
+        Some(self)
+    }
+
+    fn arc_as_branch(self: Arc<Self>) -> Option<Arc<dyn SysBranchNode>> {
+        Some(self)
+    }
+}
+
+impl SysNode for SysLazyBranchNode {
+    fn node_attrs(&self) -> Arc<SysAttrSet> {
+        self.fields.attr_set()
+    }
+
+    fn read_attr(&self, _name: &str, _offset: usize, _writer: &mut VmWriter<Fallible>) -> Result<usize> {
+        Err(Error::NotFound)
+    }
+
+    fn write_attr(&self, _name: &str, _offset: usize, _reader: &mut VmReader<Fallible>) -> Result<usize> {
+        Err(Error::NotFound)
+    }
+}
+
+impl SysBranchNode for SysLazyBranchNode {
+    fn visit_child_with(&self, name: &str, f: &mut dyn FnMut(Option<&Arc<dyn SysObj>>)) {
+        // The child is materialized (if needed) before the closure is invoked,
+        // so the populate callback is never called with the lock of the children held
+        let child = self.fields.child(name);
+        f(child.as_ref());
+    }
+
+    fn visit_children_with(&self, min_id: u64, f: &mut dyn FnMut(&Arc<dyn SysObj>) -> Option<()>) {
+        let children = self.fields.cached().children.read();
+        for child in children.values().filter(|child| child.id().as_u64() >= min_id) {
+            if f(child).is_none() {
+                break;
+            }
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<Arc<dyn SysObj>> {
+        self.fields.child(name)
+    }
+
+    // The generation is left as zero (i.e., "no caching")
+    // since a lookup may materialize a child that others have not seen,
+    // and a child that the populate callback does not produce for now
+    // may be produced later without any mutation of the node.
+
+    fn children_sorted_by_name(&self) -> Vec<Arc<dyn SysObj>> {
+        self.fields.cached().children_sorted()
+    }
+
+    fn detach_child(&self, name: &str) -> Result<Arc<dyn SysObj>> {
+        self.fields.cached().remove_child(name).ok_or(Error::NotFound)
+    }
+
+    fn rename_child(&self, old_name: &str, new_name: SysStr) -> Result<()> {
+        self.fields.cached().rename_child(old_name, new_name)
+    }
+}
+
+/// A reference implementation for a symlink node.
+pub struct SymlinkNode {
+    base: SysObjFields,
+    // The properties specific to a SysSymlink
+    target_path: String,
+    target_node: Weak<dyn SysNode>,
+}
+
+impl SymlinkNode {
+    /// Creates a symlink node that points to the given path.
+    pub fn new(name: SysStr, target_path: String) -> Arc<Self> {
+        Arc::new(Self {
+            base: SysObjFields::new(name),
+            target_path,
+            target_node: Weak::<SysTreeRoot>::new(),
+        })
+    }
+
+    /// Creates a symlink node that points to the given node.
+    ///
+    /// The target path is the path of the target node
+    /// at the time of creating the symlink node,
+    /// so the target node should have been attached to the `SysTree`.
+    pub fn new_to_node(name: SysStr, target_node: Weak<dyn SysNode>) -> Arc<Self> {
+        let target_path = target_node
+            .upgrade()
+            .map(|node| node.path())
+            .unwrap_or_default();
+        Arc::new(Self {
+            base: SysObjFields::new(name),
+            target_path,
+            target_node,
+        })
+    }
+
+    /// Creates a symlink node that points to the given path,
+    /// returning `Error::InvalidArgument` if the name is invalid
+    /// (see `validate_name`).
+    pub fn try_new(name: SysStr, target_path: String) -> Result<Arc<Self>> {
+        validate_name(&name)?;
+        Ok(Self::new(name, target_path))
+    }
+
+    /// Creates a symlink node that points to the given node,
+    /// returning `Error::InvalidArgument` if the name is invalid
+    /// (see `validate_name`).
+    pub fn try_new_to_node(name: SysStr, target_node: Weak<dyn SysNode>) -> Result<Arc<Self>> {
+        validate_name(&name)?;
+        Ok(Self::new_to_node(name, target_node))
+    }
+
+    /// Returns the target node, if the symlink node is created
+    /// with one (see `Self::new_to_node`) and it is still alive.
+    pub fn target_node(&self) -> Option<Arc<dyn SysNode>> {
+        self.target_node.upgrade()
+    }
+}
+
+impl Debug for SymlinkNode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SymlinkNode")
+            .field("name", &self.base.name())
+            .field("target_path", &self.target_path)
+            .finish()
+    }
+}
+
+impl SysObj for SymlinkNode {
+    fn id(&self) -> &SysNodeId {
+        self.base.id()
+    }
+
+    fn type_(&self) -> SysNodeType {
+        SysNodeType::Symlink
+    }
+
+    fn name(&self) -> SysStr {
+        self.base.name()
+    }
+
+    fn parent(&self) -> Weak<dyn SysBranchNode> {
+        self.base.parent()
+    }
+
+    fn set_parent(&self, parent: Weak<dyn SysBranchNode>) {
+        self.base.set_parent(parent);
+    }
+
+    fn relocate(&self, new_parent: Weak<dyn SysBranchNode>, new_name: SysStr) -> Result<()> {
+        self.base.set_parent(new_parent);
+        self.base.set_name(new_name);
+        Ok(())
+    }
+}
+
+impl SysSymlink for SymlinkNode {
+    fn target_path(&self) -> &str {
+        &self.target_path
+    }
+}
+
+#[cfg(ktest)]
+mod test {
+    use core::sync::atomic::{AtomicU64, AtomicUsize};
+
+    use ostd::{
+        prelude::ktest,
+        task::{Task, TaskOptions},
+    };
+
+    use super::*;
+
+    fn new_lazy_pids(num_populated: Arc<AtomicUsize>) -> Arc<SysLazyBranchNode> {
+        SysLazyBranchNode::new("pids".into(), move |name, parent| {
+            let _pid: u32 = name.parse().ok()?;
+            num_populated.fetch_add(1, Ordering::Relaxed);
+            let child = SysLeafNode::new(name.to_string().into(), SysAttrSet::new_empty());
+            assert!(parent.upgrade().is_some());
+            Some(child as Arc<dyn SysObj>)
+        })
+    }
+
+    /// A command/response pair whose halves are updated one after another,
+    /// so an unserialized reader may observe a partially-updated value.
+    struct CmdValue {
+        cmd: AtomicU64,
+        resp: AtomicU64,
+    }
+
+    impl SysAttrHandler for CmdValue {
+        fn read(&self, offset: usize, writer: &mut VmWriter<Fallible>) -> Result<usize> {
+            let cmd = self.cmd.load(Ordering::Relaxed);
+            Task::yield_now();
+            let resp = self.resp.load(Ordering::Relaxed);
+            let value = format!("{:08} {:08}", cmd, resp);
+            let Some(bytes) = value.as_bytes().get(offset..) else {
+                return Ok(0);
+            };
+            let mut reader = VmReader::from(bytes);
+            writer
+                .write_fallible(&mut reader)
+                .map_err(|_| Error::BadAddress)
+        }
+
+        fn write(&self, _offset: usize, reader: &mut VmReader<Fallible>) -> Result<usize> {
+            let len = reader.remain();
+            let mut buf = vec![0; len];
+            reader
+                .read_fallible(&mut VmWriter::from(buf.as_mut_slice()))
+                .map_err(|_| Error::BadAddress)?;
+            let value: u64 = core::str::from_utf8(&buf)
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .ok_or(Error::InvalidArgument)?;
+            self.cmd.store(value, Ordering::Relaxed);
+            Task::yield_now();
+            self.resp.store(value, Ordering::Relaxed);
+            Ok(len)
+        }
+    }
+
+    #[ktest]
+    fn serialized_attr_is_never_partially_updated() {
+        const NUM_WRITES: u64 = 200;
+        const NUM_READERS: usize = 3;
+
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add(
+            "cmd".into(),
+            SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE | SysAttrFlags::IS_SERIALIZED,
+        );
+        let leaf = SysLeafNode::new("dev0".into(), builder.build());
+        let value = Arc::new(CmdValue {
+            cmd: AtomicU64::new(0),
+            resp: AtomicU64::new(0),
+        });
+        leaf.set_handler("cmd", value).unwrap();
+
+        let num_done = Arc::new(AtomicUsize::new(0));
+        for i in 0..NUM_READERS + 1 {
+            let leaf = leaf.clone();
+            let num_done = num_done.clone();
+            let is_writer = i == 0;
+            TaskOptions::new(move || {
+                for value in 1..=NUM_WRITES {
+                    if is_writer {
+                        leaf.store_attr("cmd", &value.to_string()).unwrap();
+                        // A write followed by a read observes the write
+                        let expected = format!("{:08} {:08}", value, value);
+                        assert_eq!(leaf.show_attr("cmd").unwrap(), expected);
+                    } else {
+                        let mut buf = [0u8; 32];
+                        let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+                        let read_len = leaf.read_attr("cmd", 0, &mut writer).unwrap();
+                        let (cmd, resp) = core::str::from_utf8(&buf[..read_len])
+                            .unwrap()
+                            .split_once(' ')
+                            .unwrap();
+                        assert_eq!(cmd, resp);
+                    }
+                }
+                num_done.fetch_add(1, Ordering::Relaxed);
+            })
+            .data(())
+            .spawn()
+            .unwrap();
+        }
+
+        while num_done.load(Ordering::Relaxed) < NUM_READERS + 1 {
+            Task::yield_now();
+        }
+    }
+
+    fn new_leaves(names: &[&'static str]) -> Vec<Arc<dyn SysObj>> {
+        names
+            .iter()
+            .map(|name| SysLeafNode::new(SysStr::Borrowed(name), SysAttrSet::new_empty()) as _)
+            .collect()
+    }
+
+    #[ktest]
+    fn obj_fields_name_keeps_borrowed_and_owned_names() {
+        static NAME: &str = "cpu0";
+        let fields = SysObjFields::new(SysStr::Borrowed(NAME));
+        let name = fields.name();
+        assert_eq!(name, "cpu0");
+        // Cloning a borrowed name does not copy the bytes
+        assert!(matches!(name, SysStr::Borrowed(_)));
+        assert_eq!(name.as_ptr(), NAME.as_ptr());
+
+        let fields = SysObjFields::new(SysStr::Owned("cpu1".to_string()));
+        let name = fields.name();
+        assert_eq!(name, "cpu1");
+        assert!(matches!(name, SysStr::Owned(_)));
+
+        let leaf = SysLeafNode::new("cpu2".to_string().into(), SysAttrSet::new_empty());
+        assert_eq!(SysObj::name(leaf.as_ref()), "cpu2");
+    }
+
+    #[ktest]
+    fn branch_fields_from_children() {
+        let children = new_leaves(&["cpu0", "cpu1", "cpu2"]);
+        let fields = SysBranchNodeFields::<dyn SysObj>::from_children(
+            "cpu".into(),
+            SysAttrSet::new_empty(),
+            children,
+        )
+        .unwrap();
+
+        let names: Vec<SysStr> = fields
+            .children_sorted()
+            .iter()
+            .map(|child| child.name())
+            .collect();
+        assert_eq!(names, ["cpu0", "cpu1", "cpu2"]);
+    }
+
+    #[ktest]
+    fn branch_fields_from_children_with_duplicate_names() {
+        let children = new_leaves(&["cpu0", "cpu1", "cpu0"]);
+        let res = SysBranchNodeFields::<dyn SysObj>::from_children(
+            "cpu".into(),
+            SysAttrSet::new_empty(),
+            children,
+        );
+        assert_eq!(res.err(), Some(Error::AlreadyExists));
+    }
+
+    fn sorted_names(fields: &SysBranchNodeFields<dyn SysObj>) -> Vec<SysStr> {
+        fields
+            .children_sorted()
+            .iter()
+            .map(|child| child.name())
+            .collect()
+    }
+
+    #[ktest]
+    fn children_sorted_with_natural_name_cmp() {
+        let children = new_leaves(&["eth10", "eth2", "eth1"]);
+        let fields = SysBranchNodeFields::<dyn SysObj>::from_children(
+            "net".into(),
+            SysAttrSet::new_empty(),
+            children.clone(),
+        )
+        .unwrap();
+        assert_eq!(sorted_names(&fields), ["eth1", "eth10", "eth2"]);
+
+        let fields = SysBranchNodeFields::<dyn SysObj>::new("net".into(), SysAttrSet::new_empty())
+            .with_name_cmp(natural_name_cmp);
+        for child in children {
+            fields.add_child(child).unwrap();
+        }
+        assert_eq!(sorted_names(&fields), ["eth1", "eth2", "eth10"]);
+    }
+
+    #[ktest]
+    fn natural_name_cmp_compares_numbers_by_values() {
+        assert_eq!(natural_name_cmp("eth2", "eth10"), Ordering::Less);
+        assert_eq!(natural_name_cmp("eth10", "eth10"), Ordering::Equal);
+        assert_eq!(natural_name_cmp("cpu1", "eth0"), Ordering::Less);
+        assert_eq!(natural_name_cmp("sda", "sda1"), Ordering::Less);
+    }
+
+    #[ktest]
+    fn remove_child_reports_emptiness() {
+        let fields = SysBranchNodeFields::<dyn SysObj>::new("net".into(), SysAttrSet::new_empty());
+        for child in new_leaves(&["eth0", "eth1"]) {
+            fields.add_child(child).unwrap();
+        }
+
+        let (removed_child, is_empty) = fields.remove_child_reporting("eth0").unwrap();
+        assert_eq!(removed_child.name(), "eth0");
+        assert!(!is_empty);
+        let (removed_child, is_empty) = fields.remove_child_reporting("eth1").unwrap();
+        assert_eq!(removed_child.name(), "eth1");
+        assert!(is_empty);
+        assert!(fields.remove_child_reporting("eth1").is_none());
+    }
+
+    #[ktest]
+    fn clear_removes_all_children() {
+        let fields = SysBranchNodeFields::<dyn SysObj>::new("net".into(), SysAttrSet::new_empty());
+        let generation = fields.generation();
+        assert!(fields.clear().is_empty());
+        assert_eq!(fields.generation(), generation);
+
+        for child in new_leaves(&["eth0", "eth1", "lo"]) {
+            fields.add_child(child).unwrap();
+        }
+        let mut removed_names: Vec<SysStr> = fields
+            .clear()
+            .iter()
+            .map(|child| child.name())
+            .collect();
+        removed_names.sort();
+        assert_eq!(removed_names, ["eth0", "eth1", "lo"]);
+        assert!(sorted_names(&fields).is_empty());
+        assert!(!fields.contains("eth0"));
+        assert!(fields.generation() > generation);
+    }
+
+    #[ktest]
+    fn generation_advances_on_mutations_only() {
+        let fields = SysBranchNodeFields::<dyn SysObj>::new("net".into(), SysAttrSet::new_empty());
+        let mut generation = fields.generation();
+        // Zero is reserved for the nodes whose children must not be cached
+        assert_ne!(generation, 0);
+        let mut assert_advanced = |fields: &SysBranchNodeFields<dyn SysObj>| {
+            assert!(fields.generation() > generation);
+            generation = fields.generation();
+        };
+
+        for child in new_leaves(&["eth0", "eth1"]) {
+            fields.add_child(child).unwrap();
+            assert_advanced(&fields);
+        }
+        fields.rename_child("eth1", "eth2".into()).unwrap();
+        assert_advanced(&fields);
+        fields.remove_child("eth0").unwrap();
+        assert_advanced(&fields);
+
+        // Reads and failed mutations leave the generation intact
+        let generation = fields.generation();
+        assert!(fields.contains("eth2"));
+        assert_eq!(fields.children_sorted().len(), 1);
+        assert!(fields.remove_child("eth0").is_none());
+        assert_eq!(fields.rename_child("eth0", "eth3".into()), Err(Error::NotFound));
+        assert_eq!(fields.generation(), generation);
+    }
+
+    #[ktest]
+    fn try_add_child_returns_existing_child() {
+        let fields = SysBranchNodeFields::<dyn SysObj>::new("net".into(), SysAttrSet::new_empty());
+        let mut children = new_leaves(&["eth0", "eth0"]).into_iter();
+        let (old_child, new_child) = (children.next().unwrap(), children.next().unwrap());
+        fields.try_add_child(old_child.clone()).unwrap();
+
+        let Err((err, existing_child)) = fields.try_add_child(new_child) else {
+            panic!("adding a child with a duplicate name should fail");
+        };
+        assert_eq!(err, AddChildError::NameExists);
+        assert!(Arc::ptr_eq(&existing_child.unwrap(), &old_child));
+        assert_eq!(fields.children_sorted().len(), 1);
+
+        // Other failures do not report any existing child
+        let invalid_child = new_leaf_unchecked("..");
+        let Err((err, existing_child)) = fields.try_add_child(invalid_child) else {
+            panic!("adding a child with an invalid name should fail");
+        };
+        assert_eq!(err, AddChildError::InvalidName);
+        assert!(existing_child.is_none());
+    }
+
+    /// Creates a leaf node with a name that may be invalid.
+    fn new_leaf_unchecked(name: &'static str) -> Arc<dyn SysObj> {
+        let leaf = SysLeafNode::new("unnamed".into(), SysAttrSet::new_empty());
+        // Renaming bypasses the validation upon creation, as a buggy node might
+        leaf.relocate(no_parent(), SysStr::Borrowed(name)).unwrap();
+        leaf
+    }
+
+    #[ktest]
+    fn add_child_rejects_reserved_names() {
+        let fields = SysBranchNodeFields::<dyn SysObj>::new("devices".into(), SysAttrSet::new_empty());
+        for name in [".", "..", ""] {
+            let child = new_leaf_unchecked(name);
+            assert_eq!(fields.add_child(child), Err(Error::InvalidArgument));
+        }
+        assert!(fields.children_sorted().is_empty());
+
+        // Names that merely contain dots are fine
+        for child in new_leaves(&["...", ".hidden", "eth0.1"]) {
+            fields.add_child(child).unwrap();
+        }
+    }
+
+    #[ktest]
+    fn parse_and_render_cpulist() {
+        assert_eq!(parse_cpulist("0-3,5").unwrap(), [0, 1, 2, 3, 5]);
+        assert_eq!(render_cpulist(&[0, 1, 2, 3, 5]), "0-3,5");
+        // Unsorted and overlapping ranges are normalized
+        assert_eq!(parse_cpulist("5,2-3,0-2\n").unwrap(), [0, 1, 2, 3, 5]);
+        assert_eq!(parse_cpulist("").unwrap(), Vec::<u32>::new());
+        assert_eq!(render_cpulist(&[]), "");
+
+        assert_eq!(parse_cpulist("3-1"), Err(Error::InvalidArgument));
+        assert_eq!(parse_cpulist("0-a"), Err(Error::InvalidArgument));
+        assert_eq!(parse_cpulist("0-4294967295"), Err(Error::InvalidArgument));
+    }
+
+    #[ktest]
+    fn parse_and_render_values() {
+        let values = parse_values("eth0  eth1\neth2\n");
+        assert_eq!(values, ["eth0", "eth1", "eth2"]);
+        assert_eq!(render_values(&values), "eth0 eth1 eth2\n");
+    }
+
+    fn write_bool(attr: &BoolAttr, value: &str) -> Result<usize> {
+        let mut reader = VmReader::from(value.as_bytes()).to_fallible();
+        attr.write(&mut reader)
+    }
+
+    fn read_bool(attr: &BoolAttr) -> Vec<u8> {
+        let mut buf = vec![0; 8];
+        let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+        let len = attr.read(0, &mut writer).unwrap();
+        buf.truncate(len);
+        buf
+    }
+
+    #[ktest]
+    fn bool_attr_accepts_lenient_inputs() {
+        let attr = BoolAttr::new(false);
+        assert_eq!(read_bool(&attr), b"0\n");
+
+        let inputs = [
+            ("1", true),
+            ("0", false),
+            ("Y", true),
+            ("N", false),
+            ("y", true),
+            ("n", false),
+            ("1\n", true),
+            (" n \n", false),
+        ];
+        for (input, expected) in inputs {
+            assert_eq!(write_bool(&attr, input), Ok(input.len()));
+            assert_eq!(attr.get(), expected);
+            let rendered: &[u8] = if expected { b"1\n" } else { b"0\n" };
+            assert_eq!(read_bool(&attr), rendered);
+        }
+
+        attr.set(true);
+        for input in ["2", "yes", "", "true", "0123456789"] {
+            assert_eq!(write_bool(&attr, input), Err(Error::InvalidArgument));
+        }
+        // The rejected writes leave the value intact
+        assert!(attr.get());
+    }
+
+    #[ktest]
+    fn stored_value_is_bounded_by_max_len() {
+        let value = SysStoredValue::new(b"");
+        assert_eq!(value.max_len(), PAGE_SIZE);
+        let page = vec![b'x'; PAGE_SIZE];
+        let mut reader = VmReader::from(page.as_slice()).to_fallible();
+        assert_eq!(value.write(0, &mut reader), Ok(PAGE_SIZE));
+        assert_eq!(value.len(), PAGE_SIZE);
+
+        let value = SysStoredValue::with_max_len(b"old", 4);
+        let mut reader = VmReader::from(b"1234".as_slice()).to_fallible();
+        assert_eq!(value.write(0, &mut reader), Ok(4));
+        let mut reader = VmReader::from(b"12345".as_slice()).to_fallible();
+        assert_eq!(value.write(0, &mut reader), Err(Error::TooLarge));
+        let mut reader = VmReader::from(b"5".as_slice()).to_fallible();
+        assert_eq!(value.write(4, &mut reader), Err(Error::TooLarge));
+        let mut reader = VmReader::from(b"5".as_slice()).to_fallible();
+        assert_eq!(value.append(&mut reader), Err(Error::TooLarge));
+        // The rejected writes leave the value intact
+        assert_eq!(value.to_vec(), b"1234");
+    }
+
+    fn read_streamed_value(value: &SysStreamedValue) -> Vec<u8> {
+        let mut buf = vec![0; value.max_len()];
+        let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+        let len = value.read(0, &mut writer).unwrap();
+        buf.truncate(len);
+        buf
+    }
+
+    #[ktest]
+    fn streamed_value_is_committed_atomically() {
+        const CHUNK_LEN: usize = 4096;
+        let value = SysStreamedValue::new(b"old");
+        let new_bytes: Vec<u8> = (0..10 * 1024).map(|i| (i % 251) as u8).collect();
+
+        for (i, chunk) in new_bytes.chunks(CHUNK_LEN).enumerate() {
+            let mut reader = VmReader::from(chunk).to_fallible();
+            assert_eq!(value.write(i * CHUNK_LEN, &mut reader), Ok(chunk.len()));
+            // Readers never observe the pending value
+            assert_eq!(read_streamed_value(&value), b"old");
+        }
+        value.commit(new_bytes.len()).unwrap();
+        assert_eq!(read_streamed_value(&value), new_bytes);
+    }
+
+    #[ktest]
+    fn streamed_value_rejects_interleaved_writers() {
+        let value = SysStreamedValue::new(b"old");
+        let write = |offset: usize, chunk: &[u8]| {
+            let mut reader = VmReader::from(chunk).to_fallible();
+            value.write(offset, &mut reader)
+        };
+
+        // A chunk must follow the pending value
+        assert_eq!(write(4, b"late"), Err(Error::InvalidArgument));
+
+        // The second writer abandons the value of the first one
+        write(0, b"aaaa").unwrap();
+        write(0, b"bbbb").unwrap();
+        write(4, b"bbbb").unwrap();
+        assert_eq!(write(4, b"aaaa"), Err(Error::InvalidArgument));
+        // So the first writer fails to commit
+        assert_eq!(value.commit(4), Err(Error::InvalidArgument));
+        assert_eq!(read_streamed_value(&value), b"old");
+        value.commit(8).unwrap();
+        assert_eq!(read_streamed_value(&value), b"bbbbbbbb");
+
+        let value = SysStreamedValue::with_max_len(b"", 4);
+        let mut reader = VmReader::from(b"12345".as_slice()).to_fallible();
+        assert_eq!(value.write(0, &mut reader), Err(Error::TooLarge));
+
+        // A rejected oversized chunk does not abandon the pending value of others
+        let mut reader = VmReader::from(b"12".as_slice()).to_fallible();
+        value.write(0, &mut reader).unwrap();
+        let mut reader = VmReader::from(b"abcde".as_slice()).to_fallible();
+        assert_eq!(value.write(0, &mut reader), Err(Error::TooLarge));
+        let mut reader = VmReader::from(b"34".as_slice()).to_fallible();
+        value.write(2, &mut reader).unwrap();
+        value.commit(4).unwrap();
+        assert_eq!(read_streamed_value(&value), b"1234");
+    }
+
+    fn parse_cpu(token: &str) -> Result<u32> {
+        token.parse().map_err(|_| Error::InvalidArgument)
+    }
+
+    #[ktest]
+    fn write_tokens_applies_all_tokens() {
+        for mode in [SysTokensMode::ParseAllFirst, SysTokensMode::BestEffort] {
+            let mut bound = Vec::new();
+            let res = write_tokens("0 1\n2", mode, parse_cpu, |cpu| {
+                bound.push(cpu);
+                Ok(())
+            });
+            assert_eq!(res, Ok(()));
+            assert_eq!(bound, [0, 1, 2]);
+        }
+    }
+
+    #[ktest]
+    fn write_tokens_with_failed_middle_token() {
+        // No token is applied if any of them fails to parse
+        let mut bound = Vec::new();
+        let res = write_tokens("0 x 2", SysTokensMode::ParseAllFirst, parse_cpu, |cpu| {
+            bound.push(cpu);
+            Ok(())
+        });
+        assert_eq!(res, Err(Error::InvalidArgument));
+        assert!(bound.is_empty());
+
+        // The other tokens are applied regardless of the failed one
+        let mut bound = Vec::new();
+        let res = write_tokens("0 x 2", SysTokensMode::BestEffort, parse_cpu, |cpu| {
+            bound.push(cpu);
+            Ok(())
+        });
+        assert_eq!(res, Err(Error::InvalidArgument));
+        assert_eq!(bound, [0, 2]);
+
+        // Applying stops at the first failure, without rolling back the applied tokens
+        let mut bound = Vec::new();
+        let res = write_tokens("0 1 2", SysTokensMode::ParseAllFirst, parse_cpu, |cpu| {
+            if cpu == 1 {
+                return Err(Error::TryAgain);
+            }
+            bound.push(cpu);
+            Ok(())
+        });
+        assert_eq!(res, Err(Error::TryAgain));
+        assert_eq!(bound, [0]);
+    }
+
+    #[ktest]
+    fn compressed_value_reads_original_bytes() {
+        // A firmware-like blob with a header followed by zero paddings
+        let mut blob = b"FWIMG\x01\x02".to_vec();
+        blob.resize(2 * PAGE_SIZE, 0);
+        let value = SysCompressedValue::new(&blob);
+        assert_eq!(value.len(), blob.len());
+        assert!(value.stored_len() < blob.len() / 10);
+        assert_eq!(value.to_vec(), blob);
+
+        let mut buf = vec![0u8; blob.len()];
+        let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+        assert_eq!(value.read(0, &mut writer).unwrap(), blob.len());
+        assert_eq!(buf, blob);
+
+        // Reading from an offset
+        let mut buf = vec![0u8; 4];
+        let mut writer = VmWriter::from(buf.as_mut_slice()).to_fallible();
+        assert_eq!(value.read(3, &mut writer).unwrap(), 4);
+        assert_eq!(buf, blob[3..7]);
+    }
+
+    #[ktest]
+    fn compressed_value_is_read_page_by_page() {
+        let mut blob = b"FWIMG".to_vec();
+        blob.resize(3 * PAGE_SIZE, 0xff);
+        blob.extend_from_slice(b"END");
+        let value = SysCompressedValue::new(&blob);
+
+        let mut read_bytes = Vec::new();
+        let mut page = vec![0u8; PAGE_SIZE];
+        loop {
+            let mut writer = VmWriter::from(page.as_mut_slice()).to_fallible();
+            let read_len = value.read(read_bytes.len(), &mut writer).unwrap();
+            if read_len == 0 {
+                break;
+            }
+            read_bytes.extend_from_slice(&page[..read_len]);
+        }
+        assert_eq!(read_bytes, blob);
+    }
+
+    #[ktest]
+    fn compressed_value_backs_writable_attr() {
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add(
+            "firmware".into(),
+            SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE | SysAttrFlags::IS_BINARY,
+        );
+        let leaf = SysLeafNode::new("dev0".into(), builder.build());
+        let firmware = Arc::new(SysCompressedValue::with_max_len(b"", 4 * PAGE_SIZE));
+        leaf.set_handler("firmware", firmware.clone()).unwrap();
+
+        let mut blob = vec![0u8; 2 * PAGE_SIZE];
+        blob[..4].copy_from_slice(b"FWv2");
+        leaf.store_attr_bytes("firmware", &blob).unwrap();
+        assert_eq!(leaf.read_attr_bytes("firmware").unwrap(), blob);
+        assert_eq!(leaf.attr_size("firmware").unwrap(), blob.len());
+        assert!(firmware.stored_len() < blob.len() / 10);
+
+        let oversized = vec![0u8; 4 * PAGE_SIZE + 1];
+        assert_eq!(leaf.store_attr_bytes("firmware", &oversized), Err(Error::TooLarge));
+        assert_eq!(leaf.read_attr_bytes("firmware").unwrap(), blob);
+    }
+
+    #[ktest]
+    fn incompressible_value_is_stored_as_is() {
+        let blob: Vec<u8> = (0..=255).collect();
+        let value = SysCompressedValue::new(&blob);
+        assert_eq!(value.stored_len(), blob.len());
+        assert_eq!(value.to_vec(), blob);
+    }
+
+    #[ktest]
+    fn lazy_branch_materializes_child_once() {
+        let num_populated = Arc::new(AtomicUsize::new(0));
+        let pids = new_lazy_pids(num_populated.clone());
+        assert_eq!(pids.count_children(), 0);
+
+        let first = pids.child("42").unwrap();
+        assert_eq!(num_populated.load(Ordering::Relaxed), 1);
+        assert_eq!(pids.count_cached(), 1);
+        assert!(Weak::ptr_eq(
+            &first.parent(),
+            &(Arc::downgrade(&pids) as Weak<dyn SysBranchNode>)
+        ));
+
+        // The second lookup hits the cached child
+        let mut second = None;
+        pids.visit_child_with("42", &mut |child| second = child.cloned());
+        let second = second.unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(num_populated.load(Ordering::Relaxed), 1);
+        assert_eq!(pids.children().len(), 1);
+    }
+
+    #[ktest]
+    fn lazy_branch_reports_missing_child() {
+        let num_populated = Arc::new(AtomicUsize::new(0));
+        let pids = new_lazy_pids(num_populated.clone());
+
+        assert!(pids.child("self").is_none());
+        assert_eq!(num_populated.load(Ordering::Relaxed), 0);
+        assert_eq!(pids.count_cached(), 0);
+        // The children of a lazy branch must not be cached by others
+        assert_eq!(pids.generation(), 0);
+    }
+
+    #[ktest]
+    fn lazy_branch_populates_all_enumerated_children() {
+        let pids = SysLazyBranchNode::new_enumerable(
+            "pids".into(),
+            |name, _parent| {
+                let child = SysLeafNode::new(name.to_string().into(), SysAttrSet::new_empty());
+                Some(child as Arc<dyn SysObj>)
+            },
+            || vec!["1".into(), "2".into(), "10".into()],
+        );
+        assert_eq!(pids.count_children(), 0);
+
+        pids.populate_all();
+        let names: Vec<SysStr> = pids
+            .children_sorted_by_name()
+            .iter()
+            .map(|child| child.name())
+            .collect();
+        assert_eq!(names, ["1", "10", "2"]);
+    }
+
+    #[ktest]
+    fn add_attr_at_runtime_extends_attr_set() {
+        let mut builder = SysAttrSetBuilder::new();
+        builder.try_add("mtu".into(), SysAttrFlags::CAN_READ).unwrap();
+        let fields = SysNormalNodeFields::new("eth0".into(), builder.build());
+        fields.set_attr_flags("mtu", SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE).unwrap();
+        let mtu_id = fields.attr_set().get("mtu").unwrap().id();
+
+        fields.add_attr("carrier".into(), SysAttrFlags::CAN_READ).unwrap();
+        let attr_set = fields.attr_set();
+        let carrier_id = attr_set.get("carrier").unwrap().id();
+        // The existing attributes keep their IDs and changed flags
+        assert_eq!(attr_set.get("mtu").unwrap().id(), mtu_id);
+        assert_ne!(carrier_id, mtu_id);
+        assert_eq!(
+            fields.attr_flags("mtu"),
+            Some(SysAttrFlags::CAN_READ | SysAttrFlags::CAN_WRITE)
+        );
+        assert_eq!(
+            fields.add_attr("carrier".into(), SysAttrFlags::CAN_READ),
+            Err(Error::AlreadyExists)
+        );
+
+        // A full attribute set has no space for more attributes
+        for i in attr_set.len()..SysAttrSet::MAX_ATTRS {
+            fields
+                .add_attr(format!("attr{}", i).into(), SysAttrFlags::CAN_READ)
+                .unwrap();
+        }
+        assert_eq!(
+            fields.add_attr("one_more".into(), SysAttrFlags::CAN_READ),
+            Err(Error::NoSpace)
+        );
+        assert_eq!(fields.attr_set().get("carrier").unwrap().id(), carrier_id);
+    }
+
+    #[ktest]
+    fn serialized_attr_handler_can_change_own_node_attrs() {
+        let mut builder = SysAttrSetBuilder::new();
+        builder.add("probe".into(), SysAttrFlags::CAN_READ | SysAttrFlags::IS_SERIALIZED);
+        let leaf = SysLeafNode::new("dev0".into(), builder.build());
+        let weak_leaf = Arc::downgrade(&leaf);
+        let probe = move |offset: usize, writer: &mut VmWriter<Fallible>| {
+            if offset > 0 {
+                return Ok(0);
+            }
+            // Changing the attributes of the node being accessed must not deadlock
+            let leaf = weak_leaf.upgrade().unwrap();
+            leaf.set_attr_flags("probe", SysAttrFlags::CAN_READ)?;
+            leaf.add_attr(SysAttr::new(0, "probed".into(), SysAttrFlags::CAN_READ))?;
+            let mut reader = VmReader::from(b"ok".as_slice());
+            writer
+                .write_fallible(&mut reader)
+                .map_err(|_| Error::BadAddress)
+        };
+        leaf.set_handler("probe", Arc::new(probe)).unwrap();
+
+        assert_eq!(leaf.show_attr("probe").unwrap(), "ok");
+        assert!(leaf.node_attrs().contains("probed"));
+        assert_eq!(leaf.attr_flags("probe"), Some(SysAttrFlags::CAN_READ));
+    }
 }